@@ -0,0 +1,207 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use gasyori100knock_rs::{draw, font};
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{to_rgb, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // thirds: rule-of-thirds grid, golden: lines at 0.382 and 0.618 of each side
+    ParamSpec {
+        name: "grid",
+        kind: Kind::Choice(&["thirds", "golden", "none"]),
+        default: Some("thirds"),
+        constraints: &[],
+    },
+    // crosshair through the middle
+    ParamSpec {
+        name: "center",
+        kind: Kind::Choice(&["off", "on"]),
+        default: Some("off"),
+        constraints: &[],
+    },
+    // inset of the safe-area frame in percent of each side, none when 0
+    ParamSpec {
+        name: "safe",
+        kind: Kind::Float,
+        default: Some("0"),
+        constraints: &[Constraint::Range(0., 49.)],
+    },
+    // of the guides, and of the caption when overlaid
+    ParamSpec {
+        name: "color",
+        kind: Kind::Choice(&[
+            "white", "black", "red", "yellow", "green", "cyan", "magenta",
+        ]),
+        default: Some("white"),
+        constraints: &[],
+    },
+    // `{time}` becomes the UTC time of the run and `{size}` the image size; a
+    // literal `\n` starts a new line. Letters print as capitals
+    ParamSpec {
+        name: "caption",
+        kind: Kind::Text,
+        default: None,
+        constraints: &[],
+    },
+    // below and above add a white band for the caption, overlay writes into the
+    // bottom left of the image
+    ParamSpec {
+        name: "placement",
+        kind: Kind::Choice(&["below", "above", "overlay"]),
+        default: Some("below"),
+        constraints: &[],
+    },
+    // of the caption font, growing with the image when unset
+    ParamSpec {
+        name: "scale",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(1., 32.)],
+    },
+];
+
+const GOLDEN: f64 = 0.381_966_011_250_105_1;
+
+fn color(name: &str) -> [u8; 3] {
+    match name {
+        "white" => [255, 255, 255],
+        "black" => [0, 0, 0],
+        "red" => [255, 0, 0],
+        "yellow" => [255, 255, 0],
+        "green" => [0, 255, 0],
+        "cyan" => [0, 255, 255],
+        "magenta" => [255, 0, 255],
+        _ => unreachable!(),
+    }
+}
+
+// `YYYY-MM-DD HH:MM UTC` of the current time
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs()) as i64;
+    // civil-from-days of Howard Hinnant's date algorithms
+    let z = secs.div_euclid(86400) + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + (month <= 2) as i64;
+    let minutes = secs.rem_euclid(86400) / 60;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        minutes / 60,
+        minutes % 60
+    )
+}
+
+fn guides(bytes: &mut [u8], width: usize, height: usize, params: &Params) {
+    let color = color(params.str("color"));
+    let (w, h) = (width as isize, height as isize);
+    let fractions: &[f64] = match params.str("grid") {
+        "thirds" => &[1. / 3., 2. / 3.],
+        "golden" => &[GOLDEN, 1. - GOLDEN],
+        "none" => &[],
+        _ => unreachable!(),
+    };
+    for &f in fractions {
+        let (x, y) = (
+            (f * w as f64).round() as isize,
+            (f * h as f64).round() as isize,
+        );
+        draw::line(bytes, width, height, (x, 0), (x, h - 1), &color);
+        draw::line(bytes, width, height, (0, y), (w - 1, y), &color);
+    }
+    if params.str("center") == "on" {
+        let (cx, cy) = (w / 2, h / 2);
+        let arm = (w.min(h) / 16).max(2);
+        draw::line(bytes, width, height, (cx - arm, cy), (cx + arm, cy), &color);
+        draw::line(bytes, width, height, (cx, cy - arm), (cx, cy + arm), &color);
+    }
+    let safe = params.float("safe") / 100.;
+    if safe > 0. {
+        let (dx, dy) = (
+            (safe * w as f64).round() as isize,
+            (safe * h as f64).round() as isize,
+        );
+        draw::rectangle(
+            bytes,
+            width,
+            height,
+            (dx, dy),
+            (w - 2 * dx, h - 2 * dy),
+            &color,
+        );
+    }
+}
+
+/// Overlays composition guides and stamps a caption, for presenting results
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = to_rgb(img);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mut bytes = img.bytes;
+    guides(&mut bytes, width, height, params);
+
+    let Some(caption) = params.str_opt("caption") else {
+        return Image {
+            bytes,
+            info: img.info,
+            mask: None,
+        };
+    };
+    let caption = caption
+        .replace("{time}", &timestamp())
+        .replace("{size}", &format!("{}x{}", width, height));
+    let lines: Vec<&str> = caption.split("\\n").collect();
+    let scale = params
+        .int_opt("scale")
+        .map_or((width / 320).max(1), |s| s as usize);
+    let line_height = (font::HEIGHT + 2) * scale;
+    let band = lines.len() * line_height + scale;
+    // top of the caption band in the output
+    let (out_height, top) = match params.str("placement") {
+        "below" => (height + band, height),
+        "above" => (height + band, 0),
+        "overlay" => (height, height.saturating_sub(band)),
+        _ => unreachable!(),
+    };
+    let overlay = out_height == height;
+    let mut out = if overlay {
+        bytes
+    } else {
+        let mut out = vec![255; width * out_height * 3];
+        let offset = if top == 0 { band } else { 0 };
+        out[offset * width * 3..(offset + height) * width * 3].copy_from_slice(&bytes);
+        out
+    };
+    for (k, line) in lines.iter().enumerate() {
+        let origin = (
+            (2 * scale) as isize,
+            (top + k * line_height + scale) as isize,
+        );
+        if overlay {
+            // a dark shadow keeps the text legible on any background
+            let shadow = (origin.0 + scale as isize, origin.1 + scale as isize);
+            font::text(&mut out, width, out_height, shadow, line, scale, &[0, 0, 0]);
+            let color = color(params.str("color"));
+            font::text(&mut out, width, out_height, origin, line, scale, &color);
+        } else {
+            font::text(&mut out, width, out_height, origin, line, scale, &[0, 0, 0]);
+        }
+    }
+    Image {
+        bytes: out,
+        info: Info {
+            height: out_height as u32,
+            ..img.info
+        },
+        mask: None,
+    }
+}
@@ -0,0 +1,180 @@
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{read_param_image, Image};
+
+/// The second operand, same size as the input. A grayscale one applies to every channel.
+pub const OTHER: ParamSpec = ParamSpec {
+    name: "other",
+    kind: Kind::Path,
+    default: None,
+    constraints: &[Constraint::Required],
+};
+
+pub static PARAMS: &[ParamSpec] = &[OTHER];
+
+pub static SUBTRACT_PARAMS: &[ParamSpec] = &[
+    OTHER,
+    // saturate: clamp at 0, offset: a - b + offset, abs: |a - b|
+    ParamSpec {
+        name: "mode",
+        kind: Kind::Choice(&["saturate", "offset", "abs"]),
+        default: Some("saturate"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "offset",
+        kind: Kind::Float,
+        default: Some("128"),
+        constraints: &[Constraint::Range(-255., 510.)],
+    },
+];
+
+pub static MULTIPLY_PARAMS: &[ParamSpec] = &[
+    OTHER,
+    ParamSpec {
+        name: "scale",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive],
+    },
+];
+
+pub static DIVIDE_PARAMS: &[ParamSpec] = &[
+    OTHER,
+    // the mean of `other` when unset, which is what flat-field correction wants
+    ParamSpec {
+        name: "scale",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+];
+
+pub static BLEND_PARAMS: &[ParamSpec] = &[
+    OTHER,
+    ParamSpec {
+        name: "alpha",
+        kind: Kind::Float,
+        default: Some("0.5"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "beta",
+        kind: Kind::Float,
+        default: Some("0.5"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "gamma",
+        kind: Kind::Float,
+        default: Some("0"),
+        constraints: &[],
+    },
+];
+
+pub fn read_other(img: &Image, params: &Params) -> Image {
+    let other = read_param_image(params, OTHER.name);
+    if (other.info.width, other.info.height) != (img.info.width, img.info.height) {
+        die!(
+            "[ERROR] other image is {}x{} but the input is {}x{}",
+            other.info.width,
+            other.info.height,
+            img.info.width,
+            img.info.height
+        );
+    }
+    if other.info.color != img.info.color && other.info.color != png::ColorType::Grayscale {
+        die!(
+            "[ERROR] other image is {:?} but the input is {:?}",
+            other.info.color,
+            img.info.color
+        );
+    }
+    other
+}
+
+// evaluates `f` on every pair of samples in floating point, then rounds and clamps
+fn combine(img: Image, other: &Image, f: impl Fn(f64, f64) -> f64) -> Image {
+    let channels = img.info.color.samples();
+    let other_channels = other.info.color.samples();
+    let bytes = img
+        .bytes
+        .iter()
+        .enumerate()
+        .map(|(i, &a)| {
+            let b = if other_channels == channels {
+                other.bytes[i]
+            } else {
+                other.bytes[i / channels]
+            };
+            f(a as f64, b as f64).round().clamp(0., 255.) as u8
+        })
+        .collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+pub fn add(img: Image, params: &Params) -> Image {
+    let other = read_other(&img, params);
+    combine(img, &other, |a, b| a + b)
+}
+
+pub fn subtract(img: Image, params: &Params) -> Image {
+    let other = read_other(&img, params);
+    let offset = params.float("offset");
+    match params.str("mode") {
+        "saturate" => combine(img, &other, |a, b| a - b),
+        "offset" => combine(img, &other, |a, b| a - b + offset),
+        "abs" => combine(img, &other, |a, b| (a - b).abs()),
+        _ => unreachable!(),
+    }
+}
+
+/// `a * b / 255 * scale`, i.e. multiplication of [0, 1] intensities
+pub fn multiply(img: Image, params: &Params) -> Image {
+    let other = read_other(&img, params);
+    let scale = params.float("scale");
+    combine(img, &other, |a, b| a * b / 255. * scale)
+}
+
+/// `a / b * scale`, a zero divisor saturates unless `a` is zero too
+pub fn divide(img: Image, params: &Params) -> Image {
+    let other = read_other(&img, params);
+    let scale = params.float_opt("scale").unwrap_or_else(|| {
+        other.bytes.iter().map(|&v| v as f64).sum::<f64>() / other.bytes.len() as f64
+    });
+    combine(img, &other, |a, b| {
+        if b == 0. {
+            if a == 0. {
+                0.
+            } else {
+                255.
+            }
+        } else {
+            a / b * scale
+        }
+    })
+}
+
+pub fn min(img: Image, params: &Params) -> Image {
+    let other = read_other(&img, params);
+    combine(img, &other, f64::min)
+}
+
+pub fn max(img: Image, params: &Params) -> Image {
+    let other = read_other(&img, params);
+    combine(img, &other, f64::max)
+}
+
+/// `alpha * a + beta * b + gamma`
+pub fn blend(img: Image, params: &Params) -> Image {
+    let other = read_other(&img, params);
+    let (alpha, beta, gamma) = (
+        params.float("alpha"),
+        params.float("beta"),
+        params.float("gamma"),
+    );
+    combine(img, &other, |a, b| alpha * a + beta * b + gamma)
+}
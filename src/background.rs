@@ -0,0 +1,100 @@
+use gasyori100knock_rs::resize;
+
+use crate::filter::gaussian_blur;
+use crate::morphology::{morph, Step, StructuringElement};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // of the ball, or the sigma of the blur; larger than the features to keep
+    ParamSpec {
+        name: "radius",
+        kind: Kind::Int,
+        default: Some("25"),
+        constraints: &[Constraint::Range(1., 1000.)],
+    },
+    ParamSpec {
+        name: "method",
+        kind: Kind::Choice(&["opening", "blur"]),
+        default: Some("opening"),
+        constraints: &[],
+    },
+    // light: dark features on a light background (documents), closing instead of opening
+    ParamSpec {
+        name: "background",
+        kind: Kind::Choice(&["light", "dark"]),
+        default: Some("light"),
+        constraints: &[],
+    },
+    // subtract: remove the background, divide: scale by mean / background
+    ParamSpec {
+        name: "mode",
+        kind: Kind::Choice(&["subtract", "divide"]),
+        default: Some("subtract"),
+        constraints: &[],
+    },
+];
+
+// the opening runs on a shrunk copy so that its element stays about this small
+const MAX_ELEMENT_RADIUS: usize = 8;
+
+fn estimate(img: &Image, params: &Params) -> Vec<f64> {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let radius = params.int("radius") as usize;
+    let plane: Vec<f64> = img.bytes.iter().map(|&v| v as f64).collect();
+    if params.str("method") == "blur" {
+        return gaussian_blur(&plane, width, height, radius as f64);
+    }
+
+    let shrink = radius.div_ceil(MAX_ELEMENT_RADIUS);
+    let (w, h) = (width.div_ceil(shrink), height.div_ceil(shrink));
+    let small: Vec<u8> = resize::bilinear(&plane, width, height, w, h)
+        .iter()
+        .map(|v| v.round() as u8)
+        .collect();
+    let size = 2 * (radius / shrink).max(1) + 1;
+    let steps = match params.str("background") {
+        "dark" => [Step::Erode, Step::Dilate],
+        "light" => [Step::Dilate, Step::Erode],
+        _ => unreachable!(),
+    };
+    let opened = morph(
+        &small,
+        w,
+        h,
+        &StructuringElement::ellipse(size, size),
+        &steps,
+    );
+    let opened: Vec<f64> = opened.iter().map(|&v| v as f64).collect();
+    resize::bilinear(&opened, w, h, width, height)
+}
+
+/// Rolling-ball style illumination flattening: estimates the background with a
+/// large opening (closing for light backgrounds) or blur and removes it
+pub fn flatten(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let background = estimate(&img, params);
+    let light = params.str("background") == "light";
+    let mean = background.iter().sum::<f64>() / background.len() as f64;
+    let bytes = img
+        .bytes
+        .iter()
+        .zip(&background)
+        .map(|(&v, &bg)| {
+            let v = v as f64;
+            let out = match params.str("mode") {
+                "subtract" if light => 255. - (bg - v).max(0.),
+                "subtract" => (v - bg).max(0.),
+                "divide" if bg <= 0. => 255.,
+                "divide" => v / bg * mean,
+                _ => unreachable!(),
+            };
+            out.round().clamp(0., 255.) as u8
+        })
+        .collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt::Write;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::time::Instant;
+
+use crate::ops::{self, Op, OPS};
+use crate::params::{Constraint, Flags, Kind, ParamSpec, Params};
+use crate::{read_input, write_output, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // written next to the outputs as index.html or README.md
+    ParamSpec {
+        name: "gallery",
+        kind: Kind::Choice(&["none", "html", "markdown"]),
+        default: Some("none"),
+        constraints: &[],
+    },
+    // longer side of the gallery thumbnails
+    ParamSpec {
+        name: "thumbnail",
+        kind: Kind::Int,
+        default: Some("160"),
+        constraints: &[Constraint::Range(16., 1024.)],
+    },
+];
+
+struct Outcome {
+    op: &'static Op,
+    args: Vec<String>,
+    millis: f64,
+    // error message when the operation failed
+    error: Option<String>,
+    // what the operation printed besides [INFO] lines, or its JSON report
+    metrics: String,
+}
+
+/// The name of a `name=value` argument
+pub fn param_name(arg: &str) -> &str {
+    arg.split_once('=').map_or(arg, |(name, _)| name)
+}
+
+fn is_knock(op: &Op) -> bool {
+    op.name.len() > 1 && op.name[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Box-filtered copy whose longer side is at most `size`
+pub fn thumbnail(img: &Image, size: usize) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let factor = width.max(height).div_ceil(size).max(1);
+    let (tw, th) = (width.div_ceil(factor), height.div_ceil(factor));
+    let channels = img.info.color.samples();
+    let mut bytes = Vec::with_capacity(tw * th * channels);
+    for ty in 0..th {
+        for tx in 0..tw {
+            let (ys, xs) = (
+                ty * factor..((ty + 1) * factor).min(height),
+                tx * factor..((tx + 1) * factor).min(width),
+            );
+            let n = ys.len() * xs.len();
+            for c in 0..channels {
+                let sum: usize = ys
+                    .clone()
+                    .flat_map(|y| xs.clone().map(move |x| (y * width + x) * channels + c))
+                    .map(|i| img.bytes[i] as usize)
+                    .sum();
+                bytes.push(((sum + n / 2) / n) as u8);
+            }
+        }
+    }
+    Image {
+        info: Info {
+            width: tw as u32,
+            height: th as u32,
+            ..img.info.clone()
+        },
+        bytes,
+        mask: None,
+    }
+}
+
+// writes the thumbnail of `path` to `thumbs/name.png` and returns its relative path
+fn write_thumbnail(dir: &Path, path: &Path, name: &str, size: usize) -> Option<String> {
+    let img = read_input(path).ok()?;
+    if img.info.depth != png::BitDepth::Eight {
+        return None;
+    }
+    let relative = format!("thumbs/{}.png", name);
+    let thumb = thumbnail(&img, size);
+    write_output(dir.join(&relative), &thumb.info, &thumb.bytes).ok()?;
+    Some(relative)
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn gallery(
+    format: &str,
+    input: &str,
+    input_thumb: Option<&str>,
+    outcomes: &[(Outcome, Option<String>)],
+) -> String {
+    let mut out = String::new();
+    let status = |o: &Outcome| match &o.error {
+        Some(e) => format!("failed: {}", e),
+        None => format!("{:.1} ms", o.millis),
+    };
+    if format == "html" {
+        writeln!(
+            out,
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+             <style>body {{ font-family: sans-serif; }} td {{ vertical-align: top; padding: 4px 8px; }} pre {{ margin: 0; }}</style>\n\
+             </head>\n<body>\n<h1>{0}</h1>",
+            escape_html(input)
+        )
+        .unwrap();
+        if let Some(thumb) = input_thumb {
+            writeln!(out, "<p><img src=\"{}\" alt=\"input\"></p>", thumb).unwrap();
+        }
+        writeln!(
+            out,
+            "<table>\n<tr><th>Operation</th><th>Output</th><th>Parameters</th><th>Result</th><th>Metrics</th></tr>"
+        )
+        .unwrap();
+        for (o, thumb) in outcomes {
+            let image = match thumb {
+                Some(t) => format!(
+                    "<a href=\"{0}.png\"><img src=\"{1}\" alt=\"{0}\"></a>",
+                    o.op.name, t
+                ),
+                None => String::new(),
+            };
+            writeln!(
+                out,
+                "<tr><td><b>{}</b><br>{}</td><td>{}</td><td><code>{}</code></td><td>{}</td><td><pre>{}</pre></td></tr>",
+                o.op.name,
+                escape_html(o.op.help),
+                image,
+                escape_html(&o.args.join(" ")),
+                escape_html(&status(o)),
+                escape_html(o.metrics.trim_end())
+            )
+            .unwrap();
+        }
+        writeln!(out, "</table>\n</body>\n</html>").unwrap();
+    } else {
+        writeln!(out, "# {}\n", input).unwrap();
+        if let Some(thumb) = input_thumb {
+            writeln!(out, "![input]({})\n", thumb).unwrap();
+        }
+        for (o, thumb) in outcomes {
+            writeln!(out, "## {}\n\n{}\n", o.op.name, o.op.help).unwrap();
+            if let Some(t) = thumb {
+                writeln!(out, "[![{0}]({1})]({0}.png)\n", o.op.name, t).unwrap();
+            }
+            if !o.args.is_empty() {
+                writeln!(out, "Parameters: `{}`\n", o.args.join(" ")).unwrap();
+            }
+            writeln!(out, "Result: {}\n", status(o)).unwrap();
+            if !o.metrics.trim().is_empty() {
+                writeln!(out, "```\n{}\n```\n", o.metrics.trim_end()).unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// The switches to pass on to a child process
+pub fn flag_args(flags: Flags) -> Vec<&'static str> {
+    [
+        (flags.integer_math, "--integer-math"),
+        (flags.fast, "--fast"),
+        (flags.knock, "--knock"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|&(_, flag)| flag)
+    .collect()
+}
+
+/// The error message of a child process that failed
+pub fn failure(result: &Output) -> Option<String> {
+    (!result.status.success()).then(|| {
+        String::from_utf8_lossy(&result.stderr)
+            .lines()
+            .last()
+            .unwrap_or("no message")
+            .trim_start_matches("[ERROR] ")
+            .to_owned()
+    })
+}
+
+/// Runs every operation in `names`, or every knock when none are given, on
+/// `input` with the outputs written to `dir` as `<name>.png`. Each runs in a
+/// child process so that a failing one does not stop the others; parameters
+/// are passed to the operations that declare them, and `mask` to those that
+/// respect one. Exits with 1 when any failed.
+pub fn run(
+    input: &str,
+    dir: &str,
+    names: &[String],
+    args: &[String],
+    flags: Flags,
+    mask: Option<&str>,
+) {
+    let (own, shared): (Vec<String>, Vec<String>) = args
+        .iter()
+        .cloned()
+        .partition(|arg| PARAMS.iter().any(|p| p.name == param_name(arg)));
+    let params = Params::parse(PARAMS, &own, Flags::default())
+        .unwrap_or_else(|e| die!("[ERROR] invalid parameters for batch ({})", e));
+
+    let selected: Vec<&'static Op> = if names.is_empty() {
+        OPS.iter()
+            .filter(|op| is_knock(op))
+            // the ones that cannot run without more input are left out
+            .filter(|op| {
+                op.params.iter().all(|p| {
+                    !p.constraints
+                        .iter()
+                        .any(|c| matches!(c, Constraint::Required))
+                        || shared.iter().any(|arg| param_name(arg) == p.name)
+                })
+            })
+            .collect()
+    } else {
+        names
+            .iter()
+            .map(|name| {
+                ops::find(name).unwrap_or_else(|| die!("[ERROR] no function named {}", name))
+            })
+            .collect()
+    };
+    for arg in &shared {
+        let name = param_name(arg);
+        if !selected
+            .iter()
+            .any(|op| op.params.iter().any(|p| p.name == name))
+        {
+            die!("[ERROR] no selected operation takes the parameter {}", name);
+        }
+    }
+
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir)
+        .unwrap_or_else(|e| die!("[ERROR] failed to create {} ({})", dir.display(), e));
+    let exe = std::env::current_exe()
+        .unwrap_or_else(|e| die!("[ERROR] failed to locate the executable ({})", e));
+    let flag_args = flag_args(flags);
+
+    let mut outcomes = Vec::new();
+    for op in selected {
+        let mut op_args: Vec<String> = shared
+            .iter()
+            .filter(|arg| op.params.iter().any(|p| p.name == param_name(arg)))
+            .cloned()
+            .collect();
+        let report = dir.join(format!("{}.json", op.name));
+        let reports = op.params.iter().any(|p| p.name == "report")
+            && !op_args.iter().any(|arg| param_name(arg) == "report");
+        if reports {
+            op_args.push(format!("report={}", report.display()));
+        }
+        let mask_arg = mask
+            .filter(|_| ops::MASKED.contains(&op.name))
+            .map(|path| format!("--mask={}", path));
+        let started = Instant::now();
+        let result = Command::new(&exe)
+            .arg(input)
+            .arg(dir.join(format!("{}.png", op.name)))
+            .arg(op.name)
+            .args(&op_args)
+            .args(&flag_args)
+            .args(&mask_arg)
+            .output()
+            .unwrap_or_else(|e| die!("[ERROR] failed to run {} ({})", op.name, e));
+        let millis = started.elapsed().as_secs_f64() * 1000.;
+        let error = failure(&result);
+        let metrics = match std::fs::read_to_string(&report) {
+            Ok(text) if reports => text,
+            _ => String::from_utf8_lossy(&result.stdout)
+                .lines()
+                .filter(|line| !line.starts_with("[INFO]"))
+                .map(|line| format!("{}\n", line))
+                .collect(),
+        };
+        match &error {
+            Some(e) => println!("[INFO] {} failed ({})", op.name, e),
+            None => println!("[INFO] {} done in {:.1} ms", op.name, millis),
+        }
+        if reports {
+            op_args.pop();
+        }
+        outcomes.push(Outcome {
+            op,
+            args: op_args,
+            millis,
+            error,
+            metrics,
+        });
+    }
+    let failed = outcomes.iter().filter(|o| o.error.is_some()).count();
+    println!(
+        "[INFO] {} of {} operations succeeded",
+        outcomes.len() - failed,
+        outcomes.len()
+    );
+
+    let format = params.str("gallery");
+    if format != "none" {
+        std::fs::create_dir_all(dir.join("thumbs"))
+            .unwrap_or_else(|e| die!("[ERROR] failed to create thumbnail directory ({})", e));
+        let size = params.int("thumbnail") as usize;
+        let input_thumb = write_thumbnail(dir, Path::new(input), "input", size);
+        let with_thumbs: Vec<(Outcome, Option<String>)> = outcomes
+            .into_iter()
+            .map(|o| {
+                let thumb = o.error.is_none().then(|| {
+                    let path = dir.join(format!("{}.png", o.op.name));
+                    write_thumbnail(dir, &path, o.op.name, size)
+                });
+                (o, thumb.flatten())
+            })
+            .collect();
+        let text = gallery(format, input, input_thumb.as_deref(), &with_thumbs);
+        let path = dir.join(if format == "html" {
+            "index.html"
+        } else {
+            "README.md"
+        });
+        std::fs::write(&path, text)
+            .unwrap_or_else(|e| die!("[ERROR] failed to write gallery ({})", e));
+        println!("[INFO] wrote gallery {}", path.display());
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,65 @@
+use crate::arith::{self, OTHER};
+use crate::params::{ParamSpec, Params};
+use crate::Image;
+
+pub static PARAMS: &[ParamSpec] = &[OTHER];
+
+// `what` names the image in the error message
+pub fn expect_mask(img: &Image, what: &str) {
+    if img.info.color != png::ColorType::Grayscale {
+        die!(
+            "[ERROR] {} image must be a binary grayscale mask, got {:?} (binarize it with q3 or q4 first)",
+            what,
+            img.info.color
+        );
+    }
+    if let Some(pos) = img.bytes.iter().position(|&v| v != 0 && v != 255) {
+        let width = img.info.width as usize;
+        die!(
+            "[ERROR] {} image must be a binary mask with values 0 and 255 only, got {} at ({}, {})",
+            what,
+            img.bytes[pos],
+            pos % width,
+            pos / width
+        );
+    }
+}
+
+fn combine(img: Image, params: &Params, f: fn(u8, u8) -> u8) -> Image {
+    expect_mask(&img, "input");
+    let other = arith::read_other(&img, params);
+    expect_mask(&other, "other");
+    let bytes = img
+        .bytes
+        .iter()
+        .zip(&other.bytes)
+        .map(|(&a, &b)| f(a, b))
+        .collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+pub fn and(img: Image, params: &Params) -> Image {
+    combine(img, params, |a, b| a & b)
+}
+
+pub fn or(img: Image, params: &Params) -> Image {
+    combine(img, params, |a, b| a | b)
+}
+
+pub fn xor(img: Image, params: &Params) -> Image {
+    combine(img, params, |a, b| a ^ b)
+}
+
+pub fn not(img: Image, _: &Params) -> Image {
+    expect_mask(&img, "input");
+    let bytes = img.bytes.iter().map(|&v| !v).collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,103 @@
+use gasyori100knock_rs::pyramid::{Plane, Pyramid};
+
+use crate::arith::{self, OTHER};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, read_param_image, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    OTHER,
+    // grayscale, 255 takes the input and 0 takes `other`; the one attached with
+    // --mask when not given
+    ParamSpec {
+        name: "mask",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // capped at what the image size allows
+    ParamSpec {
+        name: "levels",
+        kind: Kind::Int,
+        default: Some("6"),
+        constraints: &[Constraint::Range(1., 32.)],
+    },
+];
+
+fn read_mask(img: &Image, params: &Params) -> Plane {
+    if params.str_opt("mask").is_none() {
+        let Some(mask) = &img.mask else {
+            die!("[ERROR] laplacian-blend needs a mask, as the mask parameter or --mask");
+        };
+        return Plane::new(
+            img.info.width as usize,
+            img.info.height as usize,
+            mask.iter().map(|&v| v as f64 / 255.).collect(),
+        );
+    }
+    let mask = ensure_grayscale(read_param_image(params, "mask"));
+    if (mask.info.width, mask.info.height) != (img.info.width, img.info.height) {
+        die!(
+            "[ERROR] mask image is {}x{} but the input is {}x{}",
+            mask.info.width,
+            mask.info.height,
+            img.info.width,
+            img.info.height
+        );
+    }
+    Plane::new(
+        mask.info.width as usize,
+        mask.info.height as usize,
+        mask.bytes.iter().map(|&v| v as f64 / 255.).collect(),
+    )
+}
+
+fn channel(img: &Image, c: usize) -> Plane {
+    let channels = img.info.color.samples();
+    // a grayscale `other` serves every channel
+    let c = c.min(channels - 1);
+    Plane::new(
+        img.info.width as usize,
+        img.info.height as usize,
+        img.bytes
+            .iter()
+            .skip(c)
+            .step_by(channels)
+            .map(|&v| v as f64)
+            .collect(),
+    )
+}
+
+/// Blends the Laplacian pyramids of the input and `other` weighted by the
+/// Gaussian pyramid of the mask, so each band is mixed over a matching width
+pub fn laplacian_blend(img: Image, params: &Params) -> Image {
+    let other = arith::read_other(&img, params);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let levels = (params.int("levels") as usize).min(Pyramid::max_levels(width, height));
+    let weights = Pyramid::gaussian(read_mask(&img, params), levels);
+
+    let channels = img.info.color.samples();
+    let mut bytes = vec![0u8; img.bytes.len()];
+    for c in 0..channels {
+        let a = Pyramid::laplacian(channel(&img, c), levels);
+        let b = Pyramid::laplacian(channel(&other, c), levels);
+        let mixed = Pyramid {
+            levels: (0..levels)
+                .map(|l| {
+                    let (a, b, w) = (a.level(l), b.level(l), weights.level(l));
+                    let data = (0..a.data.len())
+                        .map(|i| w.data[i] * a.data[i] + (1. - w.data[i]) * b.data[i])
+                        .collect();
+                    Plane::new(a.width, a.height, data)
+                })
+                .collect(),
+        };
+        for (i, v) in mixed.collapse().data.iter().enumerate() {
+            bytes[i * channels + c] = v.round().clamp(0., 255.) as u8;
+        }
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,94 @@
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Hsv {
+    pub h: f64, // [0, 360]
+    pub s: f64, // [0, 1]
+    pub v: f64, // [0, 1]
+}
+
+impl Hsv {
+    pub fn from_rgb(r: u8, g: u8, b: u8) -> Self {
+        let colors = [r as f64 / 255., g as f64 / 255., b as f64 / 255.];
+        let v = *colors
+            .iter()
+            .max_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap();
+        let v_min = *colors
+            .iter()
+            .min_by(|a, b| a.partial_cmp(b).unwrap())
+            .unwrap();
+        let s = v - v_min;
+        let [r, g, b] = colors;
+        let h = if s == 0. {
+            0.
+        } else if b == v_min {
+            60. * ((g - r) / s) + 60.
+        } else if r == v_min {
+            60. * ((b - g) / s) + 180.
+        } else if g == v_min {
+            60. * ((r - b) / s) + 300.
+        } else {
+            unreachable!()
+        };
+        assert!((0.0..360.0).contains(&h));
+        assert!((0.0..=1.0).contains(&s));
+        assert!((0.0..=1.0).contains(&v));
+        Self { h, s, v }
+    }
+
+    pub fn into_rgb(self) -> [u8; 3] {
+        let s = self.s;
+        let h_prime = self.h / 60.;
+        let x = s * (1. - (h_prime % 2. - 1.).abs());
+        let z = 0.;
+        let rgb_float = match h_prime {
+            _ if h_prime < 1. => [s, x, z],
+            _ if h_prime < 2. => [x, s, z],
+            _ if h_prime < 3. => [z, s, x],
+            _ if h_prime < 4. => [z, x, s],
+            _ if h_prime < 5. => [x, z, s],
+            _ if h_prime < 6. => [s, z, x],
+            _ => unreachable!("h_prime should be [0, 6), got {}", h_prime),
+        };
+
+        rgb_float.map(|val| {
+            let modded = val + (self.v - s);
+            assert!((0.0..=1.0).contains(&modded));
+            (modded * 255.) as u8
+        })
+    }
+}
+
+/// BT.709 luma, truncated
+pub fn luma(r: u8, g: u8, b: u8) -> u8 {
+    (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64) as u8
+}
+
+/// Writes the luma of each RGB triple in `rgb` to `gray`
+pub fn rgb_to_gray(rgb: &[u8], gray: &mut [u8]) {
+    assert!(rgb.len().is_multiple_of(3));
+    assert_eq!(rgb.len() / 3, gray.len());
+    for (chunk, out) in rgb.chunks_exact(3).zip(gray) {
+        *out = luma(chunk[0], chunk[1], chunk[2]);
+    }
+}
+
+/// RGB <-> BGR in place
+pub fn swap_red_blue(buf: &mut [u8]) {
+    assert!(buf.len().is_multiple_of(3));
+    for chunk in buf.chunks_exact_mut(3) {
+        chunk.swap(0, 2);
+    }
+}
+
+pub fn rgb_to_hsv(rgb: &[u8]) -> Vec<Hsv> {
+    assert!(rgb.len().is_multiple_of(3));
+    rgb.chunks_exact(3)
+        .map(|chunk| Hsv::from_rgb(chunk[0], chunk[1], chunk[2]))
+        .collect()
+}
+
+pub fn hsv_to_rgb(hsvs: &[Hsv]) -> Vec<u8> {
+    hsvs.iter().flat_map(|hsv| hsv.into_rgb()).collect()
+}
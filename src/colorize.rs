@@ -0,0 +1,185 @@
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // built-in gradient, used when no `gradient` file is given
+    ParamSpec {
+        name: "map",
+        kind: Kind::Choice(&["viridis", "hot", "jet", "gray"]),
+        default: Some("viridis"),
+        constraints: &[],
+    },
+    // color stops, one `position color` pair per line with the color as #rrggbb
+    // or `r g b`; JSON arrays like [[0, "#000000"], [1, "#ffffff"]] read as well.
+    // Positions only need to increase, the first stop lands on `low` and the last
+    // on `high`
+    ParamSpec {
+        name: "gradient",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // gray levels mapped to the ends of the gradient, values outside are clamped
+    ParamSpec {
+        name: "low",
+        kind: Kind::Int,
+        default: Some("0"),
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    ParamSpec {
+        name: "high",
+        kind: Kind::Int,
+        default: Some("255"),
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    ParamSpec {
+        name: "reverse",
+        kind: Kind::Choice(&["off", "on"]),
+        default: Some("off"),
+        constraints: &[],
+    },
+];
+
+type Stop = (f64, [u8; 3]);
+
+const VIRIDIS: &[Stop] = &[
+    (0., [68, 1, 84]),
+    (0.125, [71, 44, 122]),
+    (0.25, [59, 81, 139]),
+    (0.375, [44, 113, 142]),
+    (0.5, [33, 144, 141]),
+    (0.625, [39, 173, 129]),
+    (0.75, [92, 200, 99]),
+    (0.875, [170, 220, 50]),
+    (1., [253, 231, 37]),
+];
+
+const HOT: &[Stop] = &[
+    (0., [0, 0, 0]),
+    (0.375, [255, 0, 0]),
+    (0.75, [255, 255, 0]),
+    (1., [255, 255, 255]),
+];
+
+const JET: &[Stop] = &[
+    (0., [0, 0, 128]),
+    (0.125, [0, 0, 255]),
+    (0.375, [0, 255, 255]),
+    (0.625, [255, 255, 0]),
+    (0.875, [255, 0, 0]),
+    (1., [128, 0, 0]),
+];
+
+const GRAY: &[Stop] = &[(0., [0, 0, 0]), (1., [255, 255, 255])];
+
+fn parse_number(token: &str, path: &str) -> f64 {
+    token
+        .parse()
+        .unwrap_or_else(|_| die!("[ERROR] gradient {}: `{}` is not a number", path, token))
+}
+
+fn parse_hex(token: &str, path: &str) -> [u8; 3] {
+    let hex = &token[1..];
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        die!(
+            "[ERROR] gradient {}: `{}` is not a #rrggbb color",
+            path,
+            token
+        );
+    }
+    [0, 2, 4].map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+}
+
+/// Reads color stops from `path`, accepting both the line format and JSON
+/// arrays by splitting on brackets, quotes and commas as well as whitespace
+fn load_stops(path: &str) -> Vec<Stop> {
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| die!("[ERROR] failed to read gradient {} ({})", path, e));
+    let mut tokens = text
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("//"))
+        .flat_map(|line| {
+            line.split(|c: char| c.is_whitespace() || "[]{}\",:".contains(c))
+                .filter(|s| !s.is_empty())
+        });
+    let mut stops: Vec<Stop> = Vec::new();
+    while let Some(token) = tokens.next() {
+        let position = parse_number(token, path);
+        let color = match tokens.next() {
+            Some(hex) if hex.starts_with('#') => parse_hex(hex, path),
+            Some(r) => {
+                let channel = |token: Option<&str>| {
+                    let token = token.unwrap_or_else(|| {
+                        die!("[ERROR] gradient {}: stop {} is incomplete", path, position)
+                    });
+                    let v = parse_number(token, path);
+                    if !(0. ..=255.).contains(&v) {
+                        die!("[ERROR] gradient {}: channel {} is not in 0..=255", path, v);
+                    }
+                    v.round() as u8
+                };
+                [
+                    channel(Some(r)),
+                    channel(tokens.next()),
+                    channel(tokens.next()),
+                ]
+            }
+            None => die!("[ERROR] gradient {}: stop {} has no color", path, position),
+        };
+        if stops.last().is_some_and(|&(p, _)| position <= p) {
+            die!("[ERROR] gradient {}: positions must increase", path);
+        }
+        stops.push((position, color));
+    }
+    if stops.len() < 2 {
+        die!("[ERROR] gradient {}: needs at least two stops", path);
+    }
+    stops
+}
+
+// color at `t` in [0, 1] of the first to last stop, linear between neighbours
+fn sample(stops: &[Stop], t: f64) -> [u8; 3] {
+    let (first, last) = (stops[0].0, stops[stops.len() - 1].0);
+    let position = first + t * (last - first);
+    let k = stops[1..]
+        .iter()
+        .position(|&(p, _)| position <= p)
+        .unwrap_or(stops.len() - 2);
+    let ((p0, c0), (p1, c1)) = (stops[k], stops[k + 1]);
+    let f = ((position - p0) / (p1 - p0)).clamp(0., 1.);
+    [0, 1, 2].map(|c| (c0[c] as f64 + f * (c1[c] as f64 - c0[c] as f64)).round() as u8)
+}
+
+/// False color: maps the gray levels through a built-in or user-supplied gradient
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let stops = match params.str_opt("gradient") {
+        Some(path) => load_stops(path),
+        None => match params.str("map") {
+            "viridis" => VIRIDIS.to_vec(),
+            "hot" => HOT.to_vec(),
+            "jet" => JET.to_vec(),
+            "gray" => GRAY.to_vec(),
+            _ => unreachable!(),
+        },
+    };
+    let (low, high) = (params.int("low") as f64, params.int("high") as f64);
+    if low >= high {
+        die!("[ERROR] low ({}) must be below high ({})", low, high);
+    }
+    let reverse = params.str("reverse") == "on";
+    let lut: Vec<[u8; 3]> = (0..256)
+        .map(|v| {
+            let t = ((v as f64 - low) / (high - low)).clamp(0., 1.);
+            sample(&stops, if reverse { 1. - t } else { t })
+        })
+        .collect();
+    Image {
+        bytes: img.bytes.iter().flat_map(|&v| lut[v as usize]).collect(),
+        info: Info {
+            color: png::ColorType::Rgb,
+            ..img.info
+        },
+        mask: None,
+    }
+}
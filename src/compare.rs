@@ -0,0 +1,418 @@
+use std::fmt::Write;
+
+use gasyori100knock_rs::draw;
+
+use crate::filter::{gaussian_blur, split_channels};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::phase::phase_correlation;
+use crate::warp::per_channel;
+use crate::{ensure_grayscale, read_input, report, write_output, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // side of the square tiles the deviation is summarized over
+    ParamSpec {
+        name: "tile",
+        kind: Kind::Int,
+        default: Some("16"),
+        constraints: &[Constraint::Range(1., 65535.)],
+    },
+    // largest per-sample difference that still counts as equal
+    ParamSpec {
+        name: "tolerance",
+        kind: Kind::Int,
+        default: Some("0"),
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    // where to write the highlighted difference image
+    ParamSpec {
+        name: "diff",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // phase: undo a translation found by phase correlation, affine: undo `affine`
+    ParamSpec {
+        name: "align",
+        kind: Kind::Choice(&["none", "phase", "affine"]),
+        default: Some("none"),
+        constraints: &[],
+    },
+    // file with `a b c d e f`, taking reference (x, y) to (ax + by + c, dx + ey + f) in the image
+    ParamSpec {
+        name: "affine",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // where to write the two images combined as chosen by `visual_mode`
+    ParamSpec {
+        name: "visual",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // checkerboard: alternating cells, image first; blend: 50% of each; wipe: image
+    // left of `split`, reference right of it
+    ParamSpec {
+        name: "visual_mode",
+        kind: Kind::Choice(&["checkerboard", "blend", "wipe"]),
+        default: Some("checkerboard"),
+        constraints: &[],
+    },
+    // side of the checkerboard cells
+    ParamSpec {
+        name: "cell",
+        kind: Kind::Int,
+        default: Some("32"),
+        constraints: &[Constraint::Range(1., 65535.)],
+    },
+    // position of the wipe as a fraction of the width
+    ParamSpec {
+        name: "split",
+        kind: Kind::Float,
+        default: Some("0.5"),
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    report::PARAM,
+];
+
+// from reference to image coordinates
+type Map = Box<dyn Fn(f64, f64) -> (f64, f64)>;
+
+// of the SSIM window, as in Wang et al.
+const SSIM_SIGMA: f64 = 1.5;
+
+fn read(path: &str, what: &str) -> Image {
+    let img = read_input(path).unwrap_or_else(|e| die!("[ERROR] failed to read {} ({})", what, e));
+    if img.info.depth != png::BitDepth::Eight {
+        die!("[ERROR] the only supported bit depth is 8");
+    }
+    img
+}
+
+struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    max: u8,
+    mean: f64,
+}
+
+/// Largest difference over the channels of each pixel, 0 where `valid` is unset
+fn deviations(a: &Image, b: &Image, valid: &[bool]) -> Vec<u8> {
+    let channels = a.info.color.samples();
+    a.bytes
+        .chunks_exact(channels)
+        .zip(b.bytes.chunks_exact(channels))
+        .zip(valid)
+        .map(|((p, q), &v)| {
+            if v {
+                p.iter().zip(q).map(|(x, y)| x.abs_diff(*y)).max().unwrap()
+            } else {
+                0
+            }
+        })
+        .collect()
+}
+
+fn read_affine(path: &str) -> [f64; 6] {
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| die!("[ERROR] failed to read affine {} ({})", path, e));
+    let values: Vec<f64> = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse()
+                .unwrap_or_else(|_| die!("[ERROR] affine: `{}` is not a number", s))
+        })
+        .collect();
+    values
+        .try_into()
+        .unwrap_or_else(|v: Vec<f64>| die!("[ERROR] affine: expected 6 numbers, got {}", v.len()))
+}
+
+/// Resamples `img` onto the reference grid as the `align` parameter asks. Returns
+/// the aligned image, which pixels came from inside the image and a JSON
+/// description of the alignment.
+fn align(img: Image, reference: &Image, params: &Params) -> (Image, Vec<bool>, String) {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let (map, description): (Map, String) = match params.str("align") {
+        "none" => return (img, vec![true; width * height], "null".to_owned()),
+        "phase" => {
+            let plane = |img: &Image| split_channels(&ensure_grayscale(img.clone())).swap_remove(0);
+            let (dx, dy, peak) = phase_correlation(&plane(reference), &plane(&img), width, height);
+            (
+                Box::new(move |x, y| (x + dx, y + dy)),
+                format!(
+                    "{{\"mode\": \"phase\", \"shift\": [{:.3}, {:.3}], \"peak\": {:.4}}}",
+                    dx, dy, peak
+                ),
+            )
+        }
+        "affine" => {
+            if params.str_opt("affine").is_none() {
+                die!("[ERROR] align=affine needs the `affine` parameter");
+            }
+            let [a, b, c, d, e, f] = read_affine(params.str("affine"));
+            (
+                Box::new(move |x, y| (a * x + b * y + c, d * x + e * y + f)),
+                format!(
+                    "{{\"mode\": \"affine\", \"matrix\": [{}, {}, {}, {}, {}, {}]}}",
+                    a, b, c, d, e, f
+                ),
+            )
+        }
+        _ => unreachable!(),
+    };
+    let valid = (0..width * height)
+        .map(|i| {
+            let (sx, sy) = map((i % width) as f64, (i / width) as f64);
+            (0. ..=(width - 1) as f64).contains(&sx) && (0. ..=(height - 1) as f64).contains(&sy)
+        })
+        .collect();
+    let maps: Vec<&dyn Fn(f64, f64) -> (f64, f64)> = vec![&*map; img.info.color.samples()];
+    (per_channel(&img, &maps), valid, description)
+}
+
+/// Mean SSIM over the valid pixels, averaged over the channels
+fn ssim(a: &Image, b: &Image, valid: &[bool]) -> f64 {
+    let (width, height) = (a.info.width as usize, a.info.height as usize);
+    let (c1, c2) = ((0.01f64 * 255.).powi(2), (0.03f64 * 255.).powi(2));
+    let blur = |p: &[f64]| gaussian_blur(p, width, height, SSIM_SIGMA);
+    let product =
+        |p: &[f64], q: &[f64]| -> Vec<f64> { p.iter().zip(q).map(|(x, y)| x * y).collect() };
+    let count = valid.iter().filter(|&&v| v).count().max(1) as f64;
+    let planes = split_channels(a).into_iter().zip(split_channels(b));
+    let channels = a.info.color.samples() as f64;
+    planes
+        .map(|(p, q)| {
+            let (mp, mq) = (blur(&p), blur(&q));
+            let (pp, qq, pq) = (
+                blur(&product(&p, &p)),
+                blur(&product(&q, &q)),
+                blur(&product(&p, &q)),
+            );
+            (0..p.len())
+                .filter(|&i| valid[i])
+                .map(|i| {
+                    let (vp, vq) = (pp[i] - mp[i] * mp[i], qq[i] - mq[i] * mq[i]);
+                    let cov = pq[i] - mp[i] * mq[i];
+                    (2. * mp[i] * mq[i] + c1) * (2. * cov + c2)
+                        / ((mp[i] * mp[i] + mq[i] * mq[i] + c1) * (vp + vq + c2))
+                })
+                .sum::<f64>()
+                / count
+        })
+        .sum::<f64>()
+        / channels
+}
+
+fn tiles(dev: &[u8], width: usize, height: usize, size: usize) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for y in (0..height).step_by(size) {
+        for x in (0..width).step_by(size) {
+            let (w, h) = (size.min(width - x), size.min(height - y));
+            let values = (y..y + h).flat_map(|yy| dev[yy * width + x..yy * width + x + w].iter());
+            let (max, sum) = values.fold((0, 0u64), |(m, s), &v| (m.max(v), s + v as u64));
+            tiles.push(Tile {
+                x,
+                y,
+                width: w,
+                height: h,
+                max,
+                mean: sum as f64 / (w * h) as f64,
+            });
+        }
+    }
+    tiles
+}
+
+// the reference dimmed to gray, differing pixels in red by their deviation and
+// failing tiles outlined in yellow
+fn highlight(reference: &Image, dev: &[u8], failed: &[&Tile]) -> Image {
+    let (width, height) = (
+        reference.info.width as usize,
+        reference.info.height as usize,
+    );
+    let channels = reference.info.color.samples();
+    let mut bytes: Vec<u8> = reference
+        .bytes
+        .chunks_exact(channels)
+        .zip(dev)
+        .flat_map(|(p, &d)| {
+            // mean of the color channels, ignoring alpha
+            let colors = if channels >= 3 { 3 } else { 1 };
+            let gray = p[..colors].iter().map(|&v| v as u32).sum::<u32>() / colors as u32;
+            let base = (gray / 3) as u8;
+            if d == 0 {
+                [base; 3]
+            } else {
+                [base.max(128).saturating_add(d), base, base]
+            }
+        })
+        .collect();
+    for t in failed {
+        let (x0, y0) = (t.x as isize, t.y as isize);
+        let (x1, y1) = (x0 + t.width as isize - 1, y0 + t.height as isize - 1);
+        let yellow = [255, 255, 0];
+        for (from, to) in [
+            ((x0, y0), (x1, y0)),
+            ((x1, y0), (x1, y1)),
+            ((x1, y1), (x0, y1)),
+            ((x0, y1), (x0, y0)),
+        ] {
+            draw::line(&mut bytes, width, height, from, to, &yellow);
+        }
+    }
+    Image {
+        info: Info {
+            color: png::ColorType::Rgb,
+            ..reference.info.clone()
+        },
+        bytes,
+        mask: None,
+    }
+}
+
+/// Both images in one for viewing, see the `visual_mode` parameter
+fn visual(img: &Image, reference: &Image, params: &Params) -> Image {
+    let (width, channels) = (img.info.width as usize, img.info.color.samples());
+    let cell = params.int("cell") as usize;
+    let split = (params.float("split") * width as f64).round() as usize;
+    let mode = params.str("visual_mode");
+    let bytes = img
+        .bytes
+        .iter()
+        .zip(&reference.bytes)
+        .enumerate()
+        .map(|(i, (&a, &b))| {
+            let (x, y) = (i / channels % width, i / channels / width);
+            match mode {
+                "checkerboard" => {
+                    if (x / cell + y / cell).is_multiple_of(2) {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                "blend" => ((a as u16 + b as u16).div_ceil(2)) as u8,
+                "wipe" => {
+                    if x < split {
+                        a
+                    } else {
+                        b
+                    }
+                }
+                _ => unreachable!(),
+            }
+        })
+        .collect();
+    Image {
+        info: img.info.clone(),
+        bytes,
+        mask: None,
+    }
+}
+
+/// Compares `image` against `reference` tile by tile, exiting with an error when
+/// a tile deviates by more than the tolerance
+pub fn run(image: &str, reference: &str, params: &Params) {
+    let img = read(image, "image");
+    let reference = read(reference, "reference");
+    if (img.info.width, img.info.height, img.info.color)
+        != (
+            reference.info.width,
+            reference.info.height,
+            reference.info.color,
+        )
+    {
+        die!(
+            "[ERROR] image is {}x{} {:?} but the reference is {}x{} {:?}",
+            img.info.width,
+            img.info.height,
+            img.info.color,
+            reference.info.width,
+            reference.info.height,
+            reference.info.color
+        );
+    }
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let tolerance = params.int("tolerance") as u8;
+    let size = params.int("tile") as usize;
+
+    let (img, valid, alignment) = align(img, &reference, params);
+    let dev = deviations(&img, &reference, &valid);
+    let tiles = tiles(&dev, width, height, size);
+    let failed: Vec<&Tile> = tiles.iter().filter(|t| t.max > tolerance).collect();
+
+    let channels = img.info.color.samples();
+    let counted = valid.iter().filter(|&&v| v).count();
+    let mse = img
+        .bytes
+        .iter()
+        .zip(&reference.bytes)
+        .enumerate()
+        .filter(|(i, _)| valid[i / channels])
+        .map(|(_, (a, b))| (*a as f64 - *b as f64).powi(2))
+        .sum::<f64>()
+        / (counted * channels).max(1) as f64;
+    let psnr = if mse == 0. {
+        "null".to_owned()
+    } else {
+        format!("{}", 10. * (255. * 255. / mse).log10())
+    };
+    let columns = width.div_ceil(size);
+    let grid: Vec<String> = tiles
+        .chunks(columns)
+        .map(|row| {
+            let cells: Vec<String> = row.iter().map(|t| t.max.to_string()).collect();
+            format!("    [{}]", cells.join(", "))
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"alignment\": {},\n  \"excluded_pixels\": {},\n  \"max_deviation\": {},\n  \"mean_deviation\": {},\n  \"psnr\": {},\n  \"ssim\": {},\n  \"tile\": {},\n  \"tolerance\": {},\n  \"tiles_failed\": {},\n  \"failed\": [{}],\n  \"grid\": [\n{}\n  ]\n}}",
+        alignment,
+        valid.len() - counted,
+        dev.iter().copied().max().unwrap_or(0),
+        tiles.iter().map(|t| t.mean * (t.width * t.height) as f64).sum::<f64>() / counted.max(1) as f64,
+        psnr,
+        ssim(&img, &reference, &valid),
+        size,
+        tolerance,
+        failed.len(),
+        failed
+            .iter()
+            .map(|t| format!("[{}, {}]", t.x / size, t.y / size))
+            .collect::<Vec<_>>()
+            .join(", "),
+        grid.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    if let Some(path) = params.str_opt("diff") {
+        let out = highlight(&reference, &dev, &failed);
+        write_output(path, &out.info, &out.bytes)
+            .unwrap_or_else(|e| die!("[ERROR] failed to write diff image ({})", e));
+        println!("[INFO] wrote diff {:?}", out.info);
+    }
+    if let Some(path) = params.str_opt("visual") {
+        let out = visual(&img, &reference, params);
+        write_output(path, &out.info, &out.bytes)
+            .unwrap_or_else(|e| die!("[ERROR] failed to write visual image ({})", e));
+        println!("[INFO] wrote visual {:?}", out.info);
+    }
+
+    if !failed.is_empty() {
+        die!(
+            "[ERROR] {} of {} tiles differ by more than {}",
+            failed.len(),
+            tiles.len(),
+            tolerance
+        );
+    }
+    println!("[INFO] images match within {}", tolerance);
+}
@@ -0,0 +1,364 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::batch;
+use crate::compare;
+use crate::focus;
+use crate::ops::{Op, OPS};
+use crate::params::{ParamSpec, FLAGS};
+use crate::stack;
+use crate::sweep;
+
+const BIN: &str = env!("CARGO_BIN_NAME");
+const SUBCOMMANDS: &str = "compare verify rank stack batch sweep completions man";
+const SHELLS: &str = "bash zsh fish";
+
+#[derive(Clone, Copy, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl FromStr for Shell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(Shell::Bash),
+            "zsh" => Ok(Shell::Zsh),
+            "fish" => Ok(Shell::Fish),
+            _ => Err(format!("unknown shell `{}`, expected one of {}", s, SHELLS)),
+        }
+    }
+}
+
+pub fn generate(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+// every word that selects `op` on the command line
+fn op_words(op: &Op) -> Vec<String> {
+    let mut words: Vec<String> = std::iter::once(op.name)
+        .chain(op.aliases.iter().copied())
+        .map(String::from)
+        .collect();
+    let numbers: Vec<String> = words
+        .iter()
+        .filter_map(|word| word.strip_prefix('q'))
+        .filter(|num| num.parse::<usize>().is_ok())
+        .map(String::from)
+        .collect();
+    words.extend(numbers);
+    words
+}
+
+fn param_words(params: &[ParamSpec]) -> String {
+    params
+        .iter()
+        .map(|spec| format!("{}=", spec.name))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn flag_words() -> String {
+    FLAGS
+        .iter()
+        .map(|(flag, _)| *flag)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn func_name() -> String {
+    format!("_{}", BIN.replace('-', "_"))
+}
+
+fn bash() -> String {
+    let all_words: Vec<String> = OPS.iter().flat_map(op_words).collect();
+    let mut param_cases = String::new();
+    let mut sweep_cases = String::new();
+    for op in OPS.iter().filter(|op| !op.params.is_empty()) {
+        writeln!(
+            param_cases,
+            "                        {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+            op_words(op).join("|"),
+            param_words(op.params)
+        )
+        .unwrap();
+        writeln!(
+            sweep_cases,
+            "                            {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+            op_words(op).join("|"),
+            param_words(op.params)
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"{func}() {{
+    local cur=${{COMP_WORDS[COMP_CWORD]}}
+    if [[ $cur == --* ]]; then
+        COMPREPLY=($(compgen -W "{flags}" -- "$cur"))
+        return
+    fi
+    case $COMP_CWORD in
+        1) COMPREPLY=($(compgen -f -W "{subcommands}" -- "$cur")) ;;
+        2)
+            case ${{COMP_WORDS[1]}} in
+                completions) COMPREPLY=($(compgen -W "{shells}" -- "$cur")) ;;
+                man) ;;
+                rank) COMPREPLY=($(compgen -d -- "$cur")) ;;
+                *) COMPREPLY=($(compgen -f -- "$cur")) ;;
+            esac
+            ;;
+        3)
+            case ${{COMP_WORDS[1]}} in
+                compare|verify) COMPREPLY=($(compgen -f -- "$cur")) ;;
+                rank) compopt -o nospace; COMPREPLY=($(compgen -W "{rank_params}" -- "$cur")) ;;
+                stack) COMPREPLY=($(compgen -f -- "$cur")) ;;
+                batch|sweep) COMPREPLY=($(compgen -d -- "$cur")) ;;
+                *) COMPREPLY=($(compgen -W "{ops}" -- "$cur")) ;;
+            esac
+            ;;
+        *)
+            compopt -o nospace
+            case ${{COMP_WORDS[1]}} in
+                compare|verify) COMPREPLY=($(compgen -W "{compare_params}" -- "$cur")) ;;
+                rank) COMPREPLY=($(compgen -W "{rank_params}" -- "$cur")) ;;
+                stack) COMPREPLY=($(compgen -f -W "{stack_params}" -- "$cur")) ;;
+                batch) COMPREPLY=($(compgen -W "{ops} {batch_params}" -- "$cur")) ;;
+                sweep)
+                    if [[ $COMP_CWORD == 4 ]]; then
+                        compopt +o nospace
+                        COMPREPLY=($(compgen -W "{ops}" -- "$cur"))
+                    else
+                        case ${{COMP_WORDS[4]}} in
+{sweep_cases}                        esac
+                        COMPREPLY+=($(compgen -W "{sweep_params}" -- "$cur"))
+                    fi
+                    ;;
+                *)
+                    case ${{COMP_WORDS[3]}} in
+{param_cases}                    esac
+                    ;;
+            esac
+            ;;
+    esac
+}}
+complete -F {func} {bin}
+"#,
+        func = func_name(),
+        subcommands = SUBCOMMANDS,
+        shells = SHELLS,
+        ops = all_words.join(" "),
+        compare_params = param_words(compare::PARAMS),
+        rank_params = param_words(focus::RANK_PARAMS),
+        stack_params = param_words(stack::PARAMS),
+        batch_params = param_words(batch::PARAMS),
+        sweep_params = param_words(sweep::PARAMS),
+        param_cases = param_cases,
+        sweep_cases = sweep_cases,
+        bin = BIN,
+        flags = flag_words(),
+    )
+}
+
+fn zsh() -> String {
+    let mut descriptions = String::new();
+    for op in OPS {
+        for word in op_words(op) {
+            writeln!(
+                descriptions,
+                "        '{}:{}'",
+                word,
+                op.help.replace('\'', "'\\''")
+            )
+            .unwrap();
+        }
+    }
+    let mut flag_descriptions = String::new();
+    for (flag, help) in FLAGS {
+        writeln!(
+            flag_descriptions,
+            "        '{}:{}'",
+            flag.replace(':', "\\:"),
+            help.replace('\'', "'\\''")
+        )
+        .unwrap();
+    }
+    let mut param_cases = String::new();
+    let mut sweep_cases = String::new();
+    for op in OPS.iter().filter(|op| !op.params.is_empty()) {
+        writeln!(
+            param_cases,
+            "                        {}) compadd -S '' -- {} ;;",
+            op_words(op).join("|"),
+            param_words(op.params)
+        )
+        .unwrap();
+        writeln!(
+            sweep_cases,
+            "                            {}) compadd -S '' -- {} ;;",
+            op_words(op).join("|"),
+            param_words(op.params)
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"#compdef {bin}
+
+{func}() {{
+    local -a ops flags
+    ops=(
+{descriptions}    )
+    flags=(
+{flag_descriptions}    )
+    if [[ $PREFIX == --* ]]; then
+        _describe flag flags
+        return
+    fi
+    case $CURRENT in
+        2) _alternative 'subcommands:subcommand:({subcommands})' 'files:input:_files' ;;
+        3)
+            case $words[2] in
+                completions) _values shell {shells} ;;
+                man) ;;
+                rank) _files -/ ;;
+                *) _files ;;
+            esac
+            ;;
+        4)
+            case $words[2] in
+                compare|verify) _files ;;
+                rank) compadd -S '' -- {rank_params} ;;
+                stack) _files ;;
+                batch|sweep) _files -/ ;;
+                *) _describe operation ops ;;
+            esac
+            ;;
+        *)
+            case $words[2] in
+                compare|verify) compadd -S '' -- {compare_params} ;;
+                rank) compadd -S '' -- {rank_params} ;;
+                stack) _alternative 'params:parameter:compadd -S "" -- {stack_params}' 'files:frame:_files' ;;
+                batch) _alternative 'params:parameter:compadd -S "" -- {batch_params}' 'operations:operation:_describe operation ops' ;;
+                sweep)
+                    if (( CURRENT == 5 )); then
+                        _describe operation ops
+                    else
+                        case $words[5] in
+{sweep_cases}                        esac
+                        compadd -S '' -- {sweep_params}
+                    fi
+                    ;;
+                *)
+                    case $words[4] in
+{param_cases}                    esac
+                    ;;
+            esac
+            ;;
+    esac
+}}
+
+{func} "$@"
+"#,
+        bin = BIN,
+        func = func_name(),
+        descriptions = descriptions,
+        flag_descriptions = flag_descriptions,
+        subcommands = SUBCOMMANDS,
+        shells = SHELLS,
+        compare_params = param_words(compare::PARAMS),
+        rank_params = param_words(focus::RANK_PARAMS),
+        stack_params = param_words(stack::PARAMS),
+        batch_params = param_words(batch::PARAMS),
+        sweep_params = param_words(sweep::PARAMS),
+        param_cases = param_cases,
+        sweep_cases = sweep_cases,
+    )
+}
+
+fn fish() -> String {
+    let nargs = format!("{}_nargs", func_name());
+    let mut out = format!(
+        r#"function {nargs}
+    count (commandline -opc)
+end
+
+complete -c {bin} -f
+complete -c {bin} -n 'test ({nargs}) -eq 1' -F -a '{subcommands}'
+complete -c {bin} -n 'test ({nargs}) -eq 2; and __fish_seen_subcommand_from completions' -a '{shells}'
+complete -c {bin} -n 'test ({nargs}) -eq 2; and not __fish_seen_subcommand_from {subcommands}' -F
+complete -c {bin} -n 'test ({nargs}) -le 3; and __fish_seen_subcommand_from compare verify' -F
+complete -c {bin} -n 'test ({nargs}) -ge 4; and __fish_seen_subcommand_from compare verify' -a '{compare_params}'
+complete -c {bin} -n 'test ({nargs}) -eq 2; and __fish_seen_subcommand_from rank' -a '(__fish_complete_directories)'
+complete -c {bin} -n 'test ({nargs}) -ge 3; and __fish_seen_subcommand_from rank' -a '{rank_params}'
+complete -c {bin} -n 'test ({nargs}) -ge 2; and __fish_seen_subcommand_from stack' -F -a '{stack_params}'
+complete -c {bin} -n 'test ({nargs}) -eq 2; and __fish_seen_subcommand_from batch' -F
+complete -c {bin} -n 'test ({nargs}) -eq 3; and __fish_seen_subcommand_from batch' -a '(__fish_complete_directories)'
+complete -c {bin} -n 'test ({nargs}) -ge 4; and __fish_seen_subcommand_from batch' -a '{ops} {batch_params}'
+complete -c {bin} -n 'test ({nargs}) -eq 2; and __fish_seen_subcommand_from sweep' -F
+complete -c {bin} -n 'test ({nargs}) -eq 3; and __fish_seen_subcommand_from sweep' -a '(__fish_complete_directories)'
+complete -c {bin} -n 'test ({nargs}) -eq 4; and __fish_seen_subcommand_from sweep' -a '{ops}'
+complete -c {bin} -n 'test ({nargs}) -ge 5; and __fish_seen_subcommand_from sweep' -a '{sweep_params}'
+"#,
+        nargs = nargs,
+        bin = BIN,
+        subcommands = SUBCOMMANDS,
+        shells = SHELLS,
+        compare_params = param_words(compare::PARAMS),
+        rank_params = param_words(focus::RANK_PARAMS),
+        stack_params = param_words(stack::PARAMS),
+        batch_params = param_words(batch::PARAMS),
+        sweep_params = param_words(sweep::PARAMS),
+        ops = OPS.iter().map(|op| op.name).collect::<Vec<_>>().join(" "),
+    );
+    for (flag, help) in FLAGS {
+        writeln!(
+            out,
+            "complete -c {} -l {} -d '{}'",
+            BIN,
+            flag.trim_start_matches('-'),
+            help.replace('\'', "\\'")
+        )
+        .unwrap();
+    }
+    for op in OPS {
+        let words = op_words(op);
+        writeln!(
+            out,
+            "complete -c {} -n 'test ({}) -eq 3; and not __fish_seen_subcommand_from compare verify rank stack batch sweep' -a '{}' -d '{}'",
+            BIN,
+            nargs,
+            words.join(" "),
+            op.help.replace('\'', "\\'")
+        )
+        .unwrap();
+        if !op.params.is_empty() {
+            writeln!(
+                out,
+                "complete -c {} -n 'test ({}) -ge 4; and contains -- (commandline -opc)[4] {}' -a '{}'",
+                BIN,
+                nargs,
+                words.join(" "),
+                param_words(op.params)
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "complete -c {} -n 'test ({}) -ge 5; and __fish_seen_subcommand_from sweep; and contains -- (commandline -opc)[5] {}' -a '{}'",
+                BIN,
+                nargs,
+                words.join(" "),
+                param_words(op.params)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
@@ -0,0 +1,181 @@
+use std::io::Write;
+
+use png::{BitDepth, ColorType};
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::rng::XorShift;
+use crate::{Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "to",
+        kind: Kind::Choice(&["gray8", "gray16", "rgb8", "rgb16", "rgba8", "rgba16"]),
+        default: None,
+        constraints: &[Constraint::Required],
+    },
+    // nearest, truncate, ordered (4x4 Bayer) or random dither; all keep exact values exact
+    ParamSpec {
+        name: "rounding",
+        kind: Kind::Choice(&["nearest", "truncate", "ordered", "random"]),
+        default: Some("nearest"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "seed",
+        kind: Kind::Int,
+        default: Some("1"),
+        constraints: &[],
+    },
+    // also writes the converted samples before quantization as a PFM, without alpha
+    ParamSpec {
+        name: "float",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+];
+
+const BAYER: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+// a sample already on the target grid must not drop a level to float error
+const EPS: f64 = 1e-9;
+
+fn max_value(depth: BitDepth) -> f64 {
+    match depth {
+        BitDepth::Eight => 255.,
+        BitDepth::Sixteen => 65535.,
+        other => die!("[ERROR] unsupported bit depth {:?}", other),
+    }
+}
+
+/// Pixels as [r, g, b, a] in [0, 1], from gray, gray-alpha, RGB or RGBA at 8 or 16 bits
+fn decode(img: &Image) -> Vec<[f64; 4]> {
+    let channels = match img.info.color {
+        ColorType::Grayscale => 1,
+        ColorType::GrayscaleAlpha => 2,
+        ColorType::Rgb => 3,
+        ColorType::Rgba => 4,
+        ColorType::Indexed => die!("[ERROR] indexed images are not supported"),
+    };
+    let max = max_value(img.info.depth);
+    let samples: Vec<f64> = match img.info.depth {
+        BitDepth::Sixteen => img
+            .bytes
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 / max)
+            .collect(),
+        _ => img.bytes.iter().map(|&v| v as f64 / max).collect(),
+    };
+    samples
+        .chunks_exact(channels)
+        .map(|p| match *p {
+            [v] => [v, v, v, 1.],
+            [v, a] => [v, v, v, a],
+            [r, g, b] => [r, g, b, 1.],
+            [r, g, b, a] => [r, g, b, a],
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+fn luma([r, g, b, _]: [f64; 4]) -> f64 {
+    if r == g && g == b {
+        r
+    } else {
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+}
+
+// little-endian Portable Float Map, stored bottom row first
+fn write_pfm(path: &str, width: usize, height: usize, channels: usize, values: &[f64]) {
+    let write = || -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path)?);
+        let magic = if channels == 1 { "Pf" } else { "PF" };
+        write!(out, "{}\n{} {}\n-1.0\n", magic, width, height)?;
+        for row in values.chunks_exact(width * channels).rev() {
+            for &v in row {
+                out.write_all(&(v as f32).to_le_bytes())?;
+            }
+        }
+        out.flush()
+    };
+    write().unwrap_or_else(|e| die!("[ERROR] failed to write float image ({})", e));
+    println!(
+        "[INFO] wrote float {}x{}, {} channels",
+        width, height, channels
+    );
+}
+
+/// Converts between color types and bit depths with explicit rounding
+pub fn run(img: Image, params: &Params) -> Image {
+    let (color, depth) = match params.str("to") {
+        "gray8" => (ColorType::Grayscale, BitDepth::Eight),
+        "gray16" => (ColorType::Grayscale, BitDepth::Sixteen),
+        "rgb8" => (ColorType::Rgb, BitDepth::Eight),
+        "rgb16" => (ColorType::Rgb, BitDepth::Sixteen),
+        "rgba8" => (ColorType::Rgba, BitDepth::Eight),
+        "rgba16" => (ColorType::Rgba, BitDepth::Sixteen),
+        _ => unreachable!(),
+    };
+    let width = img.info.width as usize;
+    let pixels = decode(&img);
+    let channels = match color {
+        ColorType::Grayscale => 1,
+        ColorType::Rgb => 3,
+        _ => 4,
+    };
+    let values: Vec<f64> = pixels
+        .iter()
+        .flat_map(|&p| match channels {
+            1 => vec![luma(p)],
+            3 => p[..3].to_vec(),
+            _ => p.to_vec(),
+        })
+        .collect();
+
+    if params.str_opt("float").is_some() {
+        let color_values: Vec<f64> = if channels == 4 {
+            values
+                .chunks_exact(4)
+                .flat_map(|p| p[..3].to_vec())
+                .collect()
+        } else {
+            values.clone()
+        };
+        write_pfm(
+            params.str("float"),
+            width,
+            img.info.height as usize,
+            channels.min(3),
+            &color_values,
+        );
+    }
+
+    let max = max_value(depth);
+    let mut rng = XorShift::new(params.int("seed") as u64);
+    let rounding = params.str("rounding");
+    let quantized = values.iter().enumerate().map(|(i, &v)| {
+        let pixel = i / channels;
+        let offset = match rounding {
+            "nearest" => 0.5,
+            "truncate" => 0.,
+            "ordered" => (BAYER[pixel / width % 4][pixel % width % 4] as f64 + 0.5) / 16.,
+            "random" => rng.next_f64(),
+            _ => unreachable!(),
+        };
+        (v * max + offset + EPS).floor().clamp(0., max)
+    });
+    let bytes = match depth {
+        BitDepth::Sixteen => quantized.flat_map(|v| (v as u16).to_be_bytes()).collect(),
+        _ => quantized.map(|v| v as u8).collect(),
+    };
+    Image {
+        info: Info {
+            color,
+            depth,
+            ..img.info
+        },
+        bytes,
+        mask: None,
+    }
+}
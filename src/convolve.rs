@@ -0,0 +1,236 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub struct Kernel {
+    pub width: usize,
+    pub height: usize,
+    pub weights: Vec<f64>,
+}
+
+impl Kernel {
+    pub fn anchor(&self) -> (usize, usize) {
+        (self.width / 2, self.height / 2)
+    }
+}
+
+/// First derivative operators, a smoothing profile across a central difference
+#[derive(Clone, Copy, Debug)]
+pub enum Operator {
+    Sobel,
+    Prewitt,
+    Scharr,
+}
+
+impl Operator {
+    fn smoothing(self) -> [i32; 3] {
+        match self {
+            Operator::Sobel => [1, 2, 1],
+            Operator::Prewitt => [1, 1, 1],
+            Operator::Scharr => [3, 10, 3],
+        }
+    }
+
+    /// Response of the derivative kernels to a unit step, i.e. the sum of the smoothing profile
+    pub fn gain(self) -> i32 {
+        self.smoothing().iter().sum()
+    }
+}
+
+/// Integer weights applied as `sum(weight * value) / divisor`, rounded half up
+pub struct IntKernel {
+    pub width: usize,
+    pub height: usize,
+    pub weights: Vec<i32>,
+    pub divisor: i32,
+}
+
+impl IntKernel {
+    pub fn mean(size: usize) -> Self {
+        Self {
+            width: size,
+            height: size,
+            weights: vec![1; size * size],
+            divisor: (size * size) as i32,
+        }
+    }
+
+    /// Exact Gaussians for 3x3 and 5x5, the outer products of the binomial rows
+    /// 1-2-1 and 1-4-6-4-1 with sigma 1/sqrt(2) and 1; `None` for other sizes
+    pub fn binomial(size: usize) -> Option<Self> {
+        let row: &[i32] = match size {
+            3 => &[1, 2, 1],
+            5 => &[1, 4, 6, 4, 1],
+            _ => return None,
+        };
+        let sum: i32 = row.iter().sum();
+        Some(Self {
+            width: size,
+            height: size,
+            weights: (0..size * size)
+                .map(|i| row[i / size] * row[i % size])
+                .collect(),
+            divisor: sum * sum,
+        })
+    }
+
+    pub fn sobel(vertical: bool) -> Self {
+        let weights = if vertical {
+            vec![1, 2, 1, 0, 0, 0, -1, -2, -1]
+        } else {
+            vec![1, 0, -1, 2, 0, -2, 1, 0, -1]
+        };
+        Self {
+            width: 3,
+            height: 3,
+            weights,
+            divisor: 1,
+        }
+    }
+
+    /// d/dx (right minus left) and d/dy (bottom minus top) kernels of `operator`
+    pub fn gradient(operator: Operator) -> (Self, Self) {
+        let smooth = operator.smoothing();
+        let deriv = [-1, 0, 1];
+        let dx = (0..9).map(|i| smooth[i / 3] * deriv[i % 3]).collect();
+        let dy = (0..9).map(|i| deriv[i / 3] * smooth[i % 3]).collect();
+        (
+            Self {
+                width: 3,
+                height: 3,
+                weights: dx,
+                divisor: 1,
+            },
+            Self {
+                width: 3,
+                height: 3,
+                weights: dy,
+                divisor: 1,
+            },
+        )
+    }
+
+    /// d2/dx2, d2/dy2 and d2/dxdy kernels of `operator`, its first derivatives applied twice
+    pub fn second_derivatives(operator: Operator) -> [Self; 3] {
+        let (dx, dy) = Self::gradient(operator);
+        [dx.then(&dx), dy.then(&dy), dx.then(&dy)]
+    }
+
+    /// The kernel equivalent to applying `self` and then `other`
+    pub fn then(&self, other: &Self) -> Self {
+        let (width, height) = (self.width + other.width - 1, self.height + other.height - 1);
+        let mut weights = vec![0; width * height];
+        for ay in 0..self.height {
+            for ax in 0..self.width {
+                for by in 0..other.height {
+                    for bx in 0..other.width {
+                        weights[(ay + by) * width + ax + bx] += self.weights[ay * self.width + ax]
+                            * other.weights[by * other.width + bx];
+                    }
+                }
+            }
+        }
+        Self {
+            width,
+            height,
+            weights,
+            divisor: self.divisor * other.divisor,
+        }
+    }
+
+    pub fn anchor(&self) -> (usize, usize) {
+        (self.width / 2, self.height / 2)
+    }
+
+    pub fn to_float(&self) -> Kernel {
+        Kernel {
+            width: self.width,
+            height: self.height,
+            weights: self
+                .weights
+                .iter()
+                .map(|&w| w as f64 / self.divisor as f64)
+                .collect(),
+        }
+    }
+}
+
+/// Applies `kernel` centered on each pixel of `src` without flipping it, as the
+/// knocks do, and writes the result to `dst`. Pixels outside the image count as zero.
+pub fn convolve(src: &[f64], dst: &mut [f64], width: usize, height: usize, kernel: &Kernel) {
+    assert_eq!(src.len(), width * height);
+    assert_eq!(dst.len(), src.len());
+    let (ax, ay) = kernel.anchor();
+    for y in 0..height {
+        for x in 0..width {
+            let mut acc = 0.;
+            for ky in 0..kernel.height {
+                let sy = (y + ky).wrapping_sub(ay);
+                if sy >= height {
+                    continue;
+                }
+                for kx in 0..kernel.width {
+                    let sx = (x + kx).wrapping_sub(ax);
+                    if sx >= width {
+                        continue;
+                    }
+                    acc += kernel.weights[ky * kernel.width + kx] * src[sy * width + sx];
+                }
+            }
+            dst[y * width + x] = acc;
+        }
+    }
+}
+
+/// Integer-only counterpart of `convolve`
+pub fn convolve_int(src: &[i32], dst: &mut [i32], width: usize, height: usize, kernel: &IntKernel) {
+    assert_eq!(src.len(), width * height);
+    assert_eq!(dst.len(), src.len());
+    let (ax, ay) = kernel.anchor();
+    let divisor = kernel.divisor as i64;
+    for y in 0..height {
+        for x in 0..width {
+            let mut acc = 0i64;
+            for ky in 0..kernel.height {
+                let sy = (y + ky).wrapping_sub(ay);
+                if sy >= height {
+                    continue;
+                }
+                for kx in 0..kernel.width {
+                    let sx = (x + kx).wrapping_sub(ax);
+                    if sx >= width {
+                        continue;
+                    }
+                    acc +=
+                        kernel.weights[ky * kernel.width + kx] as i64 * src[sy * width + sx] as i64;
+                }
+            }
+            dst[y * width + x] = (2 * acc + divisor).div_euclid(2 * divisor) as i32;
+        }
+    }
+}
+
+/// Mean over the (2 * `radius` + 1)^2 window around each pixel from a summed-area
+/// table, so the cost does not depend on `radius`. The window is clipped at the
+/// borders and the mean taken over the pixels inside.
+pub fn box_blur(src: &[f64], dst: &mut [f64], width: usize, height: usize, radius: usize) {
+    assert_eq!(src.len(), width * height);
+    assert_eq!(dst.len(), src.len());
+    let stride = width + 1;
+    let mut table = vec![0.; stride * (height + 1)];
+    for y in 0..height {
+        let mut row = 0.;
+        for x in 0..width {
+            row += src[y * width + x];
+            table[(y + 1) * stride + x + 1] = table[y * stride + x + 1] + row;
+        }
+    }
+    for y in 0..height {
+        let (y0, y1) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+        for x in 0..width {
+            let (x0, x1) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+            let sum = table[y1 * stride + x1] - table[y0 * stride + x1] - table[y1 * stride + x0]
+                + table[y0 * stride + x0];
+            dst[y * width + x] = sum / ((y1 - y0) * (x1 - x0)) as f64;
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::fmt::Write;
+
+use gasyori100knock_rs::draw;
+
+use crate::gradient::OPERATOR;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::tensor::StructureTensor;
+use crate::{ensure_grayscale, report, to_rgb, Image};
+
+pub static HARRIS_PARAMS: &[ParamSpec] = &[
+    OPERATOR,
+    // of the window the gradient products are averaged over
+    ParamSpec {
+        name: "sigma",
+        kind: Kind::Float,
+        default: Some("1.5"),
+        constraints: &[Constraint::Positive],
+    },
+    ParamSpec {
+        name: "k",
+        kind: Kind::Float,
+        default: Some("0.04"),
+        constraints: &[Constraint::Range(0., 0.25)],
+    },
+    // fraction of the strongest response a corner needs
+    ParamSpec {
+        name: "threshold",
+        kind: Kind::Float,
+        default: Some("0.1"),
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    report::PARAM,
+];
+
+pub struct Corner {
+    pub x: usize,
+    pub y: usize,
+    // refined position
+    pub fx: f64,
+    pub fy: f64,
+    pub response: f64,
+}
+
+/// Harris response `det(M) - k * trace(M)^2` of the structure tensor
+pub fn harris_response(img: &Image, params: &Params) -> Vec<f64> {
+    let tensor = StructureTensor::from_params(img, params);
+    let k = params.float("k");
+    (0..tensor.xx.len()).map(|i| tensor.harris(i, k)).collect()
+}
+
+/// Strict 3x3 local maxima of `response` above `threshold`
+pub fn local_maxima(response: &[f64], width: usize, height: usize, threshold: f64) -> Vec<usize> {
+    let mut found = Vec::new();
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let v = response[y * width + x];
+            let is_max = v >= threshold
+                && (y - 1..=y + 1).all(|ny| {
+                    (x - 1..=x + 1).all(|nx| {
+                        (nx, ny) == (x, y)
+                            || v > response[ny * width + nx]
+                            || (v == response[ny * width + nx] && ny * width + nx > y * width + x)
+                    })
+                });
+            if is_max {
+                found.push(y * width + x);
+            }
+        }
+    }
+    found
+}
+
+/// Offset of the peak of the quadratic fitted to the 3x3 neighborhood of `i`,
+/// zero when the fit has no maximum within half a pixel
+pub fn refine(response: &[f64], width: usize, i: usize) -> (f64, f64) {
+    let r = |dx: isize, dy: isize| response[(i as isize + dy * width as isize + dx) as usize];
+    let (gx, gy) = ((r(1, 0) - r(-1, 0)) / 2., (r(0, 1) - r(0, -1)) / 2.);
+    let hxx = r(1, 0) - 2. * r(0, 0) + r(-1, 0);
+    let hyy = r(0, 1) - 2. * r(0, 0) + r(0, -1);
+    let hxy = (r(1, 1) - r(1, -1) - r(-1, 1) + r(-1, -1)) / 4.;
+    let det = hxx * hyy - hxy * hxy;
+    // negative definite, i.e. a maximum
+    if det <= 0. || hxx >= 0. {
+        return (0., 0.);
+    }
+    let dx = -(hyy * gx - hxy * gy) / det;
+    let dy = -(hxx * gy - hxy * gx) / det;
+    if dx.abs() > 0.5 || dy.abs() > 0.5 {
+        return (0., 0.);
+    }
+    (dx, dy)
+}
+
+pub fn corners(img: &Image, params: &Params) -> Vec<Corner> {
+    let width = img.info.width as usize;
+    let response = harris_response(img, params);
+    let max = response.iter().copied().fold(0., f64::max);
+    let threshold = (max * params.float("threshold")).max(f64::MIN_POSITIVE);
+    local_maxima(&response, width, img.info.height as usize, threshold)
+        .into_iter()
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            let (dx, dy) = refine(&response, width, i);
+            Corner {
+                x,
+                y,
+                fx: x as f64 + dx,
+                fy: y as f64 + dy,
+                response: response[i],
+            }
+        })
+        .collect()
+}
+
+pub fn report_corners(params: &Params, corners: &[Corner]) {
+    let entries: Vec<String> = corners
+        .iter()
+        .map(|c| {
+            format!(
+                "    {{\"x\": {}, \"y\": {}, \"subpixel\": [{:.3}, {:.3}], \"response\": {}}}",
+                c.x, c.y, c.fx, c.fy, c.response
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"corners\": [\n{}\n  ]\n}}",
+        entries.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+}
+
+/// q83, corners circled in red on the input
+pub fn harris(img: Image, params: &Params) -> Image {
+    let gray = ensure_grayscale(img.clone());
+    let corners = corners(&gray, params);
+    report_corners(params, &corners);
+
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mut out = to_rgb(img);
+    for c in &corners {
+        draw::circle(
+            &mut out.bytes,
+            width,
+            height,
+            (c.x as isize, c.y as isize),
+            3,
+            &[255, 0, 0],
+        );
+    }
+    out
+}
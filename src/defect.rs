@@ -0,0 +1,184 @@
+use std::fmt::Write;
+
+use crate::label::NEIGHBORS_8;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{report, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // hot: stuck bright, dead: stuck dark
+    ParamSpec {
+        name: "kind",
+        kind: Kind::Choice(&["both", "hot", "dead"]),
+        default: Some("both"),
+        constraints: &[],
+    },
+    // allowed distance from the neighbor median in robust standard deviations (1.4826 MAD)
+    ParamSpec {
+        name: "k",
+        kind: Kind::Float,
+        default: Some("6"),
+        constraints: &[Constraint::Positive],
+    },
+    // smallest distance from the neighbor median that counts, so flat areas stay untouched
+    ParamSpec {
+        name: "min_diff",
+        kind: Kind::Int,
+        default: Some("40"),
+        constraints: &[Constraint::Range(1., 255.)],
+    },
+    report::PARAM,
+];
+
+struct Defect {
+    x: usize,
+    y: usize,
+    channel: usize,
+    hot: bool,
+    value: u8,
+    replaced: u8,
+}
+
+fn median(values: &mut [u8]) -> f64 {
+    values.sort_unstable();
+    let n = values.len();
+    if n % 2 == 1 {
+        values[n / 2] as f64
+    } else {
+        (values[n / 2 - 1] as f64 + values[n / 2] as f64) / 2.
+    }
+}
+
+/// Finds samples far from the median of their 8 neighbors, relative to the
+/// neighbors' own spread, and replaces them by the mean of the neighbors that are
+/// not defective themselves. Each channel is treated on its own.
+pub fn repair(img: Image, params: &Params) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let channels = img.info.color.samples();
+    let (k, min_diff) = (params.float("k"), params.int("min_diff") as f64);
+    let (find_hot, find_dead) = match params.str("kind") {
+        "both" => (true, true),
+        "hot" => (true, false),
+        "dead" => (false, true),
+        _ => unreachable!(),
+    };
+    let neighbors = |x: usize, y: usize| {
+        NEIGHBORS_8.iter().filter_map(move |(dx, dy)| {
+            let (nx, ny) = (x as isize + dx, y as isize + dy);
+            ((0..width as isize).contains(&nx) && (0..height as isize).contains(&ny))
+                .then(|| ny as usize * width + nx as usize)
+        })
+    };
+
+    let mut flagged = vec![false; img.bytes.len()];
+    let mut defects = Vec::new();
+    for y in 0..height {
+        for x in 0..width {
+            for c in 0..channels {
+                let mut around: Vec<u8> = neighbors(x, y)
+                    .map(|j| img.bytes[j * channels + c])
+                    .collect();
+                // a lone pixel has nothing to be compared with
+                if around.is_empty() {
+                    continue;
+                }
+                let med = median(&mut around);
+                let mut deviations: Vec<u8> = around
+                    .iter()
+                    .map(|&v| (v as f64 - med).abs().round() as u8)
+                    .collect();
+                let spread = 1.4826 * median(&mut deviations);
+                let value = img.bytes[(y * width + x) * channels + c];
+                let d = value as f64 - med;
+                let hot = d > 0.;
+                if d.abs() >= min_diff.max(k * spread) && (if hot { find_hot } else { find_dead }) {
+                    flagged[(y * width + x) * channels + c] = true;
+                    defects.push(Defect {
+                        x,
+                        y,
+                        channel: c,
+                        hot,
+                        value,
+                        replaced: value,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut bytes = img.bytes.clone();
+    for defect in &mut defects {
+        let (x, y, c) = (defect.x, defect.y, defect.channel);
+        let good: Vec<u8> = neighbors(x, y)
+            .map(|j| j * channels + c)
+            .filter(|&j| !flagged[j])
+            .map(|j| img.bytes[j])
+            .collect();
+        // a cluster of defects falls back to the median of everything around
+        let replaced = if good.is_empty() {
+            let mut around: Vec<u8> = neighbors(x, y)
+                .map(|j| img.bytes[j * channels + c])
+                .collect();
+            median(&mut around)
+        } else {
+            good.iter().map(|&v| v as f64).sum::<f64>() / good.len() as f64
+        };
+        defect.replaced = replaced.round() as u8;
+        bytes[(y * width + x) * channels + c] = defect.replaced;
+    }
+
+    let entries: Vec<String> = defects
+        .iter()
+        .map(|d| {
+            format!(
+                "    {{\"x\": {}, \"y\": {}, \"channel\": {}, \"kind\": \"{}\", \"value\": {}, \"replaced\": {}}}",
+                d.x,
+                d.y,
+                d.channel,
+                if d.hot { "hot" } else { "dead" },
+                d.value,
+                d.replaced
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"repaired\": {},\n  \"pixels\": [\n{}\n  ]\n}}",
+        defects.len(),
+        entries.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::params::Flags;
+    use crate::Info;
+
+    #[test]
+    fn single_pixel() {
+        for kind in ["both", "hot", "dead"] {
+            let args = [format!("kind={}", kind), "min_diff=1".to_owned()];
+            let params = Params::parse(PARAMS, &args, Flags::default()).unwrap();
+            let img = Image {
+                info: Info {
+                    width: 1,
+                    height: 1,
+                    color: png::ColorType::Rgb,
+                    depth: png::BitDepth::Eight,
+                },
+                bytes: vec![255, 0, 128],
+                mask: None,
+            };
+            assert_eq!(repair(img, &params).bytes, [255, 0, 128]);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use alloc::vec;
+
+/// Error diffusion weights over the current row and the rows below it. The
+/// current pixel sits at column `anchor` of the first row; the weights at and
+/// before it are zero. The weights need not add up to `divisor`, Atkinson
+/// deliberately drops a quarter of the error.
+pub struct Diffusion {
+    pub width: usize,
+    pub anchor: usize,
+    pub weights: &'static [i32],
+    pub divisor: i32,
+}
+
+#[rustfmt::skip]
+pub const FLOYD_STEINBERG: Diffusion = Diffusion {
+    width: 3,
+    anchor: 1,
+    weights: &[
+        0, 0, 7,
+        3, 5, 1,
+    ],
+    divisor: 16,
+};
+
+#[rustfmt::skip]
+pub const JARVIS_JUDICE_NINKE: Diffusion = Diffusion {
+    width: 5,
+    anchor: 2,
+    weights: &[
+        0, 0, 0, 7, 5,
+        3, 5, 7, 5, 3,
+        1, 3, 5, 3, 1,
+    ],
+    divisor: 48,
+};
+
+#[rustfmt::skip]
+pub const STUCKI: Diffusion = Diffusion {
+    width: 5,
+    anchor: 2,
+    weights: &[
+        0, 0, 0, 8, 4,
+        2, 4, 8, 4, 2,
+        1, 2, 4, 2, 1,
+    ],
+    divisor: 42,
+};
+
+#[rustfmt::skip]
+pub const ATKINSON: Diffusion = Diffusion {
+    width: 4,
+    anchor: 1,
+    weights: &[
+        0, 0, 1, 1,
+        1, 1, 1, 0,
+        0, 1, 0, 0,
+    ],
+    divisor: 8,
+};
+
+// nearest of `levels` evenly spaced values from 0 to 255
+fn quantize(value: i32, levels: u32) -> u8 {
+    let n = levels as i32 - 1;
+    let q = (value.clamp(0, 255) * n + 127) / 255;
+    ((q * 255 + n / 2) / n) as u8
+}
+
+/// Quantizes each channel of interleaved `buf` to `levels` values, spreading
+/// the error of every pixel onto its unvisited neighbors by `kernel`. With
+/// `serpentine` every other row is scanned right to left with the kernel mirrored.
+pub fn diffuse(
+    buf: &mut [u8],
+    width: usize,
+    height: usize,
+    channels: usize,
+    levels: u32,
+    kernel: &Diffusion,
+    serpentine: bool,
+) {
+    assert_eq!(buf.len(), width * height * channels);
+    assert!((2..=256).contains(&levels));
+    let divisor = kernel.divisor;
+    // pending error times `divisor`, so no rounding happens until it is applied
+    let mut pending = vec![0i32; buf.len()];
+    for y in 0..height {
+        let reverse = serpentine && y % 2 == 1;
+        for step in 0..width {
+            let x = if reverse { width - 1 - step } else { step };
+            for c in 0..channels {
+                let i = (y * width + x) * channels + c;
+                let value = buf[i] as i32 + (2 * pending[i] + divisor).div_euclid(2 * divisor);
+                buf[i] = quantize(value, levels);
+                let error = value - buf[i] as i32;
+                for (k, &weight) in kernel.weights.iter().enumerate() {
+                    if weight == 0 {
+                        continue;
+                    }
+                    let dx = (k % kernel.width) as isize - kernel.anchor as isize;
+                    let nx = x as isize + if reverse { -dx } else { dx };
+                    let ny = y + k / kernel.width;
+                    if !(0..width as isize).contains(&nx) || ny >= height {
+                        continue;
+                    }
+                    pending[(ny * width + nx as usize) * channels + c] += error * weight;
+                }
+            }
+        }
+    }
+}
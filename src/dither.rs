@@ -0,0 +1,58 @@
+use gasyori100knock_rs::diffusion::{self, Diffusion};
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::Image;
+
+pub static PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "kernel",
+        kind: Kind::Choice(&[
+            "floyd-steinberg",
+            "jarvis-judice-ninke",
+            "stucki",
+            "atkinson",
+        ]),
+        default: Some("floyd-steinberg"),
+        constraints: &[],
+    },
+    // output values per channel, evenly spaced from 0 to 255
+    ParamSpec {
+        name: "levels",
+        kind: Kind::Int,
+        default: Some("2"),
+        constraints: &[Constraint::Range(2., 256.)],
+    },
+    // serpentine: every other row right to left, which breaks up directional worms
+    ParamSpec {
+        name: "scan",
+        kind: Kind::Choice(&["raster", "serpentine"]),
+        default: Some("raster"),
+        constraints: &[],
+    },
+];
+
+/// Error-diffusion dithering of every channel
+pub fn run(img: Image, params: &Params) -> Image {
+    let kernel: &Diffusion = match params.str("kernel") {
+        "floyd-steinberg" => &diffusion::FLOYD_STEINBERG,
+        "jarvis-judice-ninke" => &diffusion::JARVIS_JUDICE_NINKE,
+        "stucki" => &diffusion::STUCKI,
+        "atkinson" => &diffusion::ATKINSON,
+        _ => unreachable!(),
+    };
+    let mut bytes = img.bytes;
+    diffusion::diffuse(
+        &mut bytes,
+        img.info.width as usize,
+        img.info.height as usize,
+        img.info.color.samples(),
+        params.int("levels") as u32,
+        kernel,
+        params.str("scan") == "serpentine",
+    );
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,187 @@
+use std::fmt::Write;
+
+use gasyori100knock_rs::draw;
+
+use crate::filter::gaussian_blur;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, report, to_rgb, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "sigma1",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive],
+    },
+    ParamSpec {
+        name: "sigma2",
+        kind: Kind::Float,
+        default: Some("1.6"),
+        constraints: &[Constraint::Positive],
+    },
+    // abs: |d|, offset: 128 + d so that the sign stays visible
+    ParamSpec {
+        name: "output",
+        kind: Kind::Choice(&["abs", "offset"]),
+        default: Some("offset"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "scale",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive],
+    },
+];
+
+pub static BLOB_PARAMS: &[ParamSpec] = &[
+    // of the finest level
+    ParamSpec {
+        name: "sigma",
+        kind: Kind::Float,
+        default: Some("1.6"),
+        constraints: &[Constraint::Positive],
+    },
+    // ratio between consecutive sigmas
+    ParamSpec {
+        name: "k",
+        kind: Kind::Float,
+        default: Some("1.26"),
+        constraints: &[Constraint::Range(1.01, 4.)],
+    },
+    // number of DoG levels, extrema are searched in all but the first and last
+    ParamSpec {
+        name: "levels",
+        kind: Kind::Int,
+        default: Some("6"),
+        constraints: &[Constraint::Range(3., 16.)],
+    },
+    // minimum |DoG| response in intensity units
+    ParamSpec {
+        name: "threshold",
+        kind: Kind::Float,
+        default: Some("4"),
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    report::PARAM,
+];
+
+fn plane(img: &Image) -> Vec<f64> {
+    img.bytes.iter().map(|&v| v as f64).collect()
+}
+
+/// `G(sigma1) - G(sigma2)`
+pub fn dog(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let plane = plane(&img);
+    let a = gaussian_blur(&plane, width, height, params.float("sigma1"));
+    let b = gaussian_blur(&plane, width, height, params.float("sigma2"));
+    let scale = params.float("scale");
+    let offset = params.str("output") == "offset";
+    let bytes = a
+        .iter()
+        .zip(&b)
+        .map(|(a, b)| {
+            let d = (a - b) * scale;
+            let v = if offset { 128. + d } else { d.abs() };
+            v.round().clamp(0., 255.) as u8
+        })
+        .collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+struct Blob {
+    x: usize,
+    y: usize,
+    sigma: f64,
+    response: f64,
+}
+
+/// Local extrema of a DoG stack over space and scale, drawn as circles of radius sqrt(2) * sigma
+pub fn blobs(img: Image, params: &Params) -> Image {
+    let gray = ensure_grayscale(img.clone());
+    let (width, height) = (gray.info.width as usize, gray.info.height as usize);
+    let plane = plane(&gray);
+    let (sigma0, k) = (params.float("sigma"), params.float("k"));
+    let levels = params.int("levels") as usize;
+    let threshold = params.float("threshold");
+
+    let sigmas: Vec<f64> = (0..=levels).map(|i| sigma0 * k.powi(i as i32)).collect();
+    let blurred: Vec<Vec<f64>> = sigmas
+        .iter()
+        .map(|&s| gaussian_blur(&plane, width, height, s))
+        .collect();
+    let stack: Vec<Vec<f64>> = blurred
+        .windows(2)
+        .map(|pair| pair[1].iter().zip(&pair[0]).map(|(b, a)| b - a).collect())
+        .collect();
+
+    let mut found = Vec::new();
+    for level in 1..levels - 1 {
+        for y in 1..height.saturating_sub(1) {
+            for x in 1..width.saturating_sub(1) {
+                let v = stack[level][y * width + x];
+                if v.abs() < threshold {
+                    continue;
+                }
+                let neighbors = (level - 1..=level + 1).flat_map(|l| {
+                    (y - 1..=y + 1).flat_map(move |ny| (x - 1..=x + 1).map(move |nx| (l, ny, nx)))
+                });
+                let extremum = neighbors
+                    .filter(|&n| n != (level, y, x))
+                    .all(|(l, ny, nx)| {
+                        let n = stack[l][ny * width + nx];
+                        if v > 0. {
+                            v > n
+                        } else {
+                            v < n
+                        }
+                    });
+                if extremum {
+                    found.push(Blob {
+                        x,
+                        y,
+                        sigma: sigmas[level],
+                        response: v,
+                    });
+                }
+            }
+        }
+    }
+
+    let entries: Vec<String> = found
+        .iter()
+        .map(|b| {
+            format!(
+                "    {{\"x\": {}, \"y\": {}, \"sigma\": {}, \"radius\": {}, \"response\": {}}}",
+                b.x,
+                b.y,
+                b.sigma,
+                b.sigma * std::f64::consts::SQRT_2,
+                b.response
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(text, "{{\n  \"blobs\": [\n{}\n  ]\n}}", entries.join(",\n")).unwrap();
+    report::emit(params, &text);
+
+    let mut out = to_rgb(img);
+    for b in &found {
+        let radius = (b.sigma * std::f64::consts::SQRT_2).round() as isize;
+        draw::circle(
+            &mut out.bytes,
+            width,
+            height,
+            (b.x as isize, b.y as isize),
+            radius,
+            &[255, 0, 0],
+        );
+    }
+    out
+}
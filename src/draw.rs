@@ -0,0 +1,117 @@
+/// Sets the pixel at (`x`, `y`) of an interleaved buffer to `color` if it lies inside
+pub fn plot(buf: &mut [u8], width: usize, height: usize, x: isize, y: isize, color: &[u8]) {
+    if !(0..width as isize).contains(&x) || !(0..height as isize).contains(&y) {
+        return;
+    }
+    let channels = color.len();
+    let start = (y as usize * width + x as usize) * channels;
+    buf[start..start + channels].copy_from_slice(color);
+}
+
+/// Outline of a circle by the midpoint algorithm, clipped to the buffer
+pub fn circle(
+    buf: &mut [u8],
+    width: usize,
+    height: usize,
+    (cx, cy): (isize, isize),
+    radius: isize,
+    color: &[u8],
+) {
+    assert_eq!(buf.len(), width * height * color.len());
+    let (mut x, mut y, mut err) = (radius, 0, 1 - radius);
+    while x >= y {
+        for (dx, dy) in [
+            (x, y),
+            (y, x),
+            (-y, x),
+            (-x, y),
+            (-x, -y),
+            (-y, -x),
+            (y, -x),
+            (x, -y),
+        ] {
+            plot(buf, width, height, cx + dx, cy + dy, color);
+        }
+        y += 1;
+        if err < 0 {
+            err += 2 * y + 1;
+        } else {
+            x -= 1;
+            err += 2 * (y - x) + 1;
+        }
+    }
+}
+
+/// Bresenham line from `from` to `to`, both ends included, clipped to the buffer
+pub fn line(
+    buf: &mut [u8],
+    width: usize,
+    height: usize,
+    from: (isize, isize),
+    to: (isize, isize),
+    color: &[u8],
+) {
+    assert_eq!(buf.len(), width * height * color.len());
+    let (mut x, mut y) = from;
+    let (dx, dy) = ((to.0 - x).abs(), -(to.1 - y).abs());
+    let (sx, sy) = ((to.0 - x).signum(), (to.1 - y).signum());
+    let mut err = dx + dy;
+    loop {
+        plot(buf, width, height, x, y, color);
+        if (x, y) == to {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
+
+/// One pixel wide outline of the `size.0` x `size.1` rectangle with its top left at `corner`
+pub fn rectangle(
+    buf: &mut [u8],
+    width: usize,
+    height: usize,
+    corner: (isize, isize),
+    size: (isize, isize),
+    color: &[u8],
+) {
+    let (x0, y0) = corner;
+    let (x1, y1) = (x0 + size.0 - 1, y0 + size.1 - 1);
+    for (from, to) in [
+        ((x0, y0), (x1, y0)),
+        ((x1, y0), (x1, y1)),
+        ((x1, y1), (x0, y1)),
+        ((x0, y1), (x0, y0)),
+    ] {
+        line(buf, width, height, from, to, color);
+    }
+}
+
+/// Filled disc of the pixels whose centers lie within `radius` of `center`, clipped to the buffer
+pub fn disc(
+    buf: &mut [u8],
+    width: usize,
+    height: usize,
+    center: (f64, f64),
+    radius: f64,
+    color: &[u8],
+) {
+    assert_eq!(buf.len(), width * height * color.len());
+    let (cx, cy) = center;
+    // one pixel of slack on each side, as the casts truncate toward zero
+    for y in (cy - radius) as isize - 1..=(cy + radius) as isize + 1 {
+        for x in (cx - radius) as isize - 1..=(cx + radius) as isize + 1 {
+            let (dx, dy) = (x as f64 + 0.5 - cx, y as f64 + 0.5 - cy);
+            if dx * dx + dy * dy <= radius * radius {
+                plot(buf, width, height, x, y, color);
+            }
+        }
+    }
+}
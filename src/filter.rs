@@ -0,0 +1,402 @@
+use gasyori100knock_rs::convolve::{self, IntKernel, Kernel};
+use gasyori100knock_rs::median;
+
+use crate::fft::{fft2d, Complex};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, Image, Info};
+
+pub static GAUSSIAN_PARAMS: &[ParamSpec] = &[
+    // 1.3 when not given
+    ParamSpec {
+        name: "sigma",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    // covers 3 sigma when not given, the knock fixes it to 3
+    ParamSpec {
+        name: "size",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(1., 255.), Constraint::Odd],
+    },
+];
+
+pub static BOX_PARAMS: &[ParamSpec] = &[ParamSpec {
+    name: "size",
+    kind: Kind::Int,
+    default: Some("3"),
+    constraints: &[Constraint::Range(1., 255.), Constraint::Odd],
+}];
+
+pub static MEDIAN_PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "size",
+        kind: Kind::Int,
+        default: Some("3"),
+        constraints: &[Constraint::Range(1., 255.), Constraint::Odd],
+    },
+    // auto: sorting for small windows, the sliding histogram from 5x5 up
+    ParamSpec {
+        name: "method",
+        kind: Kind::Choice(&["auto", "sort", "histogram"]),
+        default: Some("auto"),
+        constraints: &[],
+    },
+];
+
+pub static SOBEL_PARAMS: &[ParamSpec] = &[ParamSpec {
+    name: "direction",
+    kind: Kind::Choice(&["vertical", "horizontal"]),
+    default: Some("vertical"),
+    constraints: &[],
+}];
+
+// kernels with more taps than 11x11 are applied through the FFT
+const FFT_MIN_AREA: usize = 11 * 11;
+
+// fractional bits of quantized kernels
+const FIXED_SHIFT: u32 = 12;
+
+// box blurs making up the --fast Gaussian
+const BOXES: usize = 3;
+
+/// Normalized `size` x `size` Gaussian
+pub fn gaussian_kernel(size: usize, sigma: f64) -> Kernel {
+    let half = (size / 2) as f64;
+    let mut weights: Vec<f64> = (0..size * size)
+        .map(|i| {
+            let x = (i % size) as f64 - half;
+            let y = (i / size) as f64 - half;
+            (-(x * x + y * y) / (2. * sigma * sigma)).exp()
+        })
+        .collect();
+    let sum: f64 = weights.iter().sum();
+    weights.iter_mut().for_each(|w| *w /= sum);
+    Kernel {
+        width: size,
+        height: size,
+        weights,
+    }
+}
+
+/// Quantizes `kernel` to fixed point, keeping the sum of its weights
+pub fn quantize(kernel: &Kernel) -> IntKernel {
+    let scale = (1 << FIXED_SHIFT) as f64;
+    let mut weights: Vec<i32> = kernel
+        .weights
+        .iter()
+        .map(|w| (w * scale).round() as i32)
+        .collect();
+    // the rounding error goes to the center tap so that flat areas stay flat
+    let target = (kernel.weights.iter().sum::<f64>() * scale).round() as i32;
+    let (ax, ay) = kernel.anchor();
+    weights[ay * kernel.width + ax] += target - weights.iter().sum::<i32>();
+    IntKernel {
+        width: kernel.width,
+        height: kernel.height,
+        weights,
+        divisor: 1 << FIXED_SHIFT,
+    }
+}
+
+/// Applies `kernel` centered on each pixel without flipping it, as the knocks do.
+/// Pixels outside the image count as zero.
+pub fn convolve(plane: &[f64], width: usize, height: usize, kernel: &Kernel) -> Vec<f64> {
+    assert_eq!(plane.len(), width * height);
+    if kernel.weights.len() > FFT_MIN_AREA {
+        convolve_fft(plane, width, height, kernel)
+    } else {
+        let mut out = vec![0.; plane.len()];
+        convolve::convolve(plane, &mut out, width, height, kernel);
+        out
+    }
+}
+
+// zero-padded linear convolution with the flipped kernel, which equals the
+// spatial correlation above
+fn convolve_fft(plane: &[f64], width: usize, height: usize, kernel: &Kernel) -> Vec<f64> {
+    let pw = (width + kernel.width - 1).next_power_of_two();
+    let ph = (height + kernel.height - 1).next_power_of_two();
+
+    let mut image = vec![Complex::default(); pw * ph];
+    for y in 0..height {
+        for x in 0..width {
+            image[y * pw + x].re = plane[y * width + x];
+        }
+    }
+    let mut taps = vec![Complex::default(); pw * ph];
+    for ky in 0..kernel.height {
+        for kx in 0..kernel.width {
+            let (fx, fy) = (kernel.width - 1 - kx, kernel.height - 1 - ky);
+            taps[fy * pw + fx].re = kernel.weights[ky * kernel.width + kx];
+        }
+    }
+
+    fft2d(&mut image, pw, ph, false);
+    fft2d(&mut taps, pw, ph, false);
+    for (a, b) in image.iter_mut().zip(&taps) {
+        *a = *a * *b;
+    }
+    fft2d(&mut image, pw, ph, true);
+
+    let (ax, ay) = kernel.anchor();
+    let (ox, oy) = (kernel.width - 1 - ax, kernel.height - 1 - ay);
+    let mut out = Vec::with_capacity(plane.len());
+    for y in 0..height {
+        for x in 0..width {
+            out.push(image[(y + oy) * pw + x + ox].re);
+        }
+    }
+    out
+}
+
+/// Splits interleaved samples into one plane per channel
+pub fn split_channels(img: &Image) -> Vec<Vec<f64>> {
+    let channels = img.info.color.samples();
+    (0..channels)
+        .map(|c| {
+            img.bytes
+                .iter()
+                .skip(c)
+                .step_by(channels)
+                .map(|&v| v as f64)
+                .collect()
+        })
+        .collect()
+}
+
+/// Interleaves planes back into an image, rounding and clamping to [0, 255]
+pub fn merge_channels(planes: &[Vec<f64>], info: Info) -> Image {
+    let len = planes.first().map_or(0, Vec::len);
+    let mut bytes = Vec::with_capacity(len * planes.len());
+    for i in 0..len {
+        for plane in planes {
+            bytes.push(plane[i].round().clamp(0., 255.) as u8);
+        }
+    }
+    Image {
+        info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// Applies `kernel` to every channel
+pub fn filter_image(img: Image, kernel: &Kernel) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let planes: Vec<Vec<f64>> = split_channels(&img)
+        .iter()
+        .map(|plane| convolve(plane, width, height, kernel))
+        .collect();
+    merge_channels(&planes, img.info)
+}
+
+/// Applies `kernel` to every channel with integer arithmetic only
+pub fn filter_image_int(img: Image, kernel: &IntKernel) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let channels = img.info.color.samples();
+    let mut bytes = vec![0u8; img.bytes.len()];
+    for c in 0..channels {
+        let plane: Vec<i32> = img
+            .bytes
+            .iter()
+            .skip(c)
+            .step_by(channels)
+            .map(|&v| v as i32)
+            .collect();
+        let mut out = vec![0; plane.len()];
+        convolve::convolve_int(&plane, &mut out, width, height, kernel);
+        for (i, v) in out.into_iter().enumerate() {
+            bytes[i * channels + c] = v.clamp(0, 255) as u8;
+        }
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+// kernels with exact integer weights, run as-is under --integer-math
+fn apply_exact(img: Image, params: &Params, kernel: &IntKernel) -> Image {
+    if params.flags.integer_math {
+        filter_image_int(img, kernel)
+    } else {
+        filter_image(img, &kernel.to_float())
+    }
+}
+
+// normalized convolution over the masked pixels, blended into the input by the mask
+fn filter_masked(img: Image, kernel: &Kernel) -> Image {
+    let mask = img.mask.as_deref().expect("no mask attached");
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let weights: Vec<f64> = mask.iter().map(|&m| m as f64 / 255.).collect();
+    let mass = convolve(&weights, width, height, kernel);
+    let planes: Vec<Vec<f64>> = split_channels(&img)
+        .iter()
+        .map(|plane| {
+            let weighted: Vec<f64> = plane.iter().zip(&weights).map(|(v, w)| v * w).collect();
+            let sum = convolve(&weighted, width, height, kernel);
+            (0..plane.len())
+                .map(|i| {
+                    let filtered = if mass[i] > 1e-9 {
+                        sum[i] / mass[i]
+                    } else {
+                        plane[i]
+                    };
+                    weights[i] * filtered + (1. - weights[i]) * plane[i]
+                })
+                .collect()
+        })
+        .collect();
+    merge_channels(&planes, img.info)
+}
+
+const SIGMA: f64 = 1.3;
+
+/// Under --integer-math a 3x3 or 5x5 kernel without a given sigma is the binomial one,
+/// any other is quantized once; either way the per-pixel work is integer only.
+/// With a mask only the masked pixels are blurred, from each other alone.
+pub fn gaussian(img: Image, params: &Params) -> Image {
+    if img.mask.is_some() && (params.flags.fast || params.flags.integer_math) {
+        die!("[ERROR] --mask cannot be combined with --fast or --integer-math");
+    }
+    if params.flags.fast {
+        if params.flags.integer_math {
+            die!("[ERROR] --fast and --integer-math cannot be combined");
+        }
+        return gaussian_fast(img, params.float_opt("sigma").unwrap_or(SIGMA));
+    }
+    let given = params.float_opt("sigma");
+    let sigma = given.unwrap_or(SIGMA);
+    let size = params.int_opt("size").map_or_else(
+        || 2 * (3. * sigma).ceil() as usize + 1,
+        |size| size as usize,
+    );
+    if params.flags.integer_math && given.is_none() {
+        if let Some(kernel) = IntKernel::binomial(size) {
+            return filter_image_int(img, &kernel);
+        }
+    }
+    let kernel = gaussian_kernel(size, sigma);
+    if img.mask.is_some() {
+        filter_masked(img, &kernel)
+    } else if params.flags.integer_math {
+        filter_image_int(img, &quantize(&kernel))
+    } else {
+        filter_image(img, &kernel)
+    }
+}
+
+/// Gaussian blur of a plane with a kernel covering 3 sigma, normalized by the kernel
+/// mass inside the image so that the borders do not darken
+pub fn gaussian_blur(plane: &[f64], width: usize, height: usize, sigma: f64) -> Vec<f64> {
+    let size = 2 * (3. * sigma).ceil() as usize + 1;
+    let kernel = gaussian_kernel(size, sigma);
+    let mass = convolve(&vec![1.; plane.len()], width, height, &kernel);
+    convolve(plane, width, height, &kernel)
+        .iter()
+        .zip(&mass)
+        .map(|(v, m)| v / m)
+        .collect()
+}
+
+/// Widths of `BOXES` successive box blurs whose combined variance matches `sigma`
+/// (Kovesi, "Fast almost-Gaussian filtering"); all odd, the narrower ones first
+fn box_sizes(sigma: f64) -> [usize; BOXES] {
+    let n = BOXES as f64;
+    let ideal = (12. * sigma * sigma / n + 1.).sqrt();
+    let mut lower = ideal.floor() as usize;
+    if lower.is_multiple_of(2) {
+        lower -= 1;
+    }
+    let wl = lower as f64;
+    let narrow = ((12. * sigma * sigma - n * wl * wl - 4. * n * wl - 3. * n) / (-4. * wl - 4.))
+        .round()
+        .clamp(0., n) as usize;
+    std::array::from_fn(|i| if i < narrow { lower } else { lower + 2 })
+}
+
+/// `size` is ignored, the extent follows from `sigma`
+fn gaussian_fast(img: Image, sigma: f64) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mut planes = split_channels(&img);
+    let mut scratch = vec![0.; width * height];
+    for plane in &mut planes {
+        for size in box_sizes(sigma) {
+            convolve::box_blur(plane, &mut scratch, width, height, size / 2);
+            std::mem::swap(plane, &mut scratch);
+        }
+    }
+    merge_channels(&planes, img.info)
+}
+
+pub fn mean(img: Image, params: &Params) -> Image {
+    apply_exact(img, params, &IntKernel::mean(params.int("size") as usize))
+}
+
+/// q10, median of every channel over a `size` x `size` window (zero padding)
+pub fn median(img: Image, params: &Params) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let channels = img.info.color.samples();
+    let radius = params.int("size") as usize / 2;
+    let filter = match params.str("method") {
+        "auto" => median::median_filter,
+        "sort" => median::median_sort,
+        "histogram" => median::median_histogram,
+        _ => unreachable!(),
+    };
+    let mut bytes = vec![0; img.bytes.len()];
+    let mut out = vec![0; width * height];
+    for c in 0..channels {
+        let plane: Vec<u8> = img
+            .bytes
+            .iter()
+            .skip(c)
+            .step_by(channels)
+            .copied()
+            .collect();
+        filter(&plane, &mut out, width, height, radius);
+        for (dst, &v) in bytes.iter_mut().skip(c).step_by(channels).zip(&out) {
+            *dst = v;
+        }
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+pub fn sobel(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let vertical = params.str("direction") == "vertical";
+    apply_exact(img, params, &IntKernel::sobel(vertical))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fft_matches_direct() {
+        let (width, height) = (40, 29);
+        // deterministic noise, and an asymmetric kernel so that a flip shows
+        let plane: Vec<f64> = (0..width * height)
+            .map(|i| ((i * 7919) % 256) as f64)
+            .collect();
+        let kernel = Kernel {
+            width: 13,
+            height: 13,
+            weights: (0..13 * 13).map(|i| ((i * 31) % 17) as f64 - 8.).collect(),
+        };
+        assert!(kernel.weights.len() > FFT_MIN_AREA);
+        let mut direct = vec![0.; plane.len()];
+        convolve::convolve(&plane, &mut direct, width, height, &kernel);
+        let fft = convolve_fft(&plane, width, height, &kernel);
+        for (a, b) in direct.iter().zip(&fft) {
+            assert!((a - b).abs() < 1e-6, "direct {} fft {}", a, b);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt::Write;
+
+use gasyori100knock_rs::draw;
+
+use crate::label::{label, Labels, NEIGHBORS_8};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::threshold::{histogram, otsu_threshold};
+use crate::{ensure_grayscale, report, to_rgb, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // darker samples are modules, Otsu's when not given
+    ParamSpec {
+        name: "threshold",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    // allowed deviation of each run from its ideal length, in modules
+    ParamSpec {
+        name: "tolerance",
+        kind: Kind::Float,
+        default: Some("0.5"),
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    report::PARAM,
+];
+
+pub struct Pattern {
+    pub x: f64,
+    pub y: f64,
+    // estimated module size in pixels
+    pub module: f64,
+}
+
+// (dark, start, length) of each run along a line
+fn runs(line: impl Iterator<Item = bool>) -> Vec<(bool, usize, usize)> {
+    let mut out: Vec<(bool, usize, usize)> = Vec::new();
+    for (i, dark) in line.enumerate() {
+        match out.last_mut() {
+            Some(run) if run.0 == dark => run.2 += 1,
+            _ => out.push((dark, i, 1)),
+        }
+    }
+    out
+}
+
+// module size when five consecutive runs read 1:1:3:1:1
+fn ratio(lengths: &[usize], tolerance: f64) -> Option<f64> {
+    let total: usize = lengths.iter().sum();
+    let module = total as f64 / 7.;
+    let ok = lengths
+        .iter()
+        .zip([1., 1., 3., 1., 1.])
+        .all(|(&len, ideal)| {
+            (len as f64 - ideal * module).abs() <= tolerance * module * ideal.sqrt()
+        });
+    ok.then_some(module)
+}
+
+// the five runs centered on the dark run covering `at`, with the center of that run
+fn centered(runs: &[(bool, usize, usize)], at: usize) -> Option<([usize; 5], f64)> {
+    let i = runs.iter().position(|r| (r.1..r.1 + r.2).contains(&at))?;
+    if !runs[i].0 || i < 2 || i + 2 >= runs.len() {
+        return None;
+    }
+    let lengths = [
+        runs[i - 2].2,
+        runs[i - 1].2,
+        runs[i].2,
+        runs[i + 1].2,
+        runs[i + 2].2,
+    ];
+    Some((lengths, runs[i].1 as f64 + (runs[i].2 as f64 - 1.) / 2.))
+}
+
+// the stone must be its own component, enclosed by a separate ring whose area
+// is about 24 / 9 of it
+fn verify(labels: &Labels, width: usize, x: usize, y: usize, ring_at: usize) -> bool {
+    let (stone, ring) = (labels.labels[y * width + x], labels.labels[ring_at]);
+    if stone == 0 || ring == 0 || stone == ring {
+        return false;
+    }
+    let share = labels.areas[ring as usize - 1] as f64 / labels.areas[stone as usize - 1] as f64;
+    (1.5..=4.5).contains(&share)
+}
+
+/// Finder patterns of a dark-on-light QR code: rows are scanned for 1:1:3:1:1
+/// runs, the column through each hit must show the same, and the labels must
+/// separate the stone from its ring
+pub fn find(mask: &[bool], width: usize, height: usize, tolerance: f64) -> Vec<Pattern> {
+    let labels = label(mask, width, height, NEIGHBORS_8);
+    let mut hits: Vec<Pattern> = Vec::new();
+    for y in 0..height {
+        let row = runs(mask[y * width..(y + 1) * width].iter().copied());
+        for i in 0..row.len().saturating_sub(4) {
+            if !row[i].0 {
+                continue;
+            }
+            let lengths: Vec<usize> = row[i..i + 5].iter().map(|r| r.2).collect();
+            let Some(module) = ratio(&lengths, tolerance) else {
+                continue;
+            };
+            let cx = row[i + 2].1 + row[i + 2].2 / 2;
+            let column = runs((0..height).map(|yy| mask[yy * width + cx]));
+            let Some((vertical, cy)) = centered(&column, y) else {
+                continue;
+            };
+            let Some(vmodule) = ratio(&vertical, tolerance) else {
+                continue;
+            };
+            if (vmodule - module).abs() > tolerance * module.max(vmodule)
+                || !verify(&labels, width, cx, y, y * width + row[i].1)
+            {
+                continue;
+            }
+            let x = row[i + 2].1 as f64 + (row[i + 2].2 as f64 - 1.) / 2.;
+            hits.push(Pattern {
+                x,
+                y: cy,
+                module: (module + vmodule) / 2.,
+            });
+        }
+    }
+
+    // every row through a pattern hits it, so nearby hits are averaged
+    let mut patterns: Vec<(Pattern, usize)> = Vec::new();
+    for hit in hits {
+        match patterns.iter_mut().find(|(p, _)| {
+            (p.x - hit.x).abs() <= 2. * p.module && (p.y - hit.y).abs() <= 2. * p.module
+        }) {
+            Some((p, n)) => {
+                let k = *n as f64;
+                p.x = (p.x * k + hit.x) / (k + 1.);
+                p.y = (p.y * k + hit.y) / (k + 1.);
+                p.module = (p.module * k + hit.module) / (k + 1.);
+                *n += 1;
+            }
+            None => patterns.push((hit, 1)),
+        }
+    }
+    patterns.into_iter().map(|(p, _)| p).collect()
+}
+
+/// QR finder patterns outlined in red on the input, reported as JSON
+pub fn run(img: Image, params: &Params) -> Image {
+    let gray = ensure_grayscale(img.clone());
+    let (width, height) = (gray.info.width as usize, gray.info.height as usize);
+    let threshold = match params.int_opt("threshold") {
+        Some(t) => t as u8,
+        None => otsu_threshold(&histogram(&gray.bytes, None)).unwrap_or_else(|| {
+            die!("[ERROR] failed to find a threshold, the image has fewer than two gray levels")
+        }),
+    };
+    let mask: Vec<bool> = gray.bytes.iter().map(|&v| v < threshold).collect();
+    let patterns = find(&mask, width, height, params.float("tolerance"));
+    println!("patterns: {}", patterns.len());
+
+    let entries: Vec<String> = patterns
+        .iter()
+        .map(|p| {
+            format!(
+                "    {{\"x\": {:.2}, \"y\": {:.2}, \"module\": {:.2}}}",
+                p.x, p.y, p.module
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"threshold\": {},\n  \"patterns\": [\n{}\n  ]\n}}",
+        threshold,
+        entries.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    let mut out = to_rgb(img);
+    for p in &patterns {
+        let side = (7. * p.module).round() as isize;
+        let corner = (
+            (p.x - 3.5 * p.module).round() as isize,
+            (p.y - 3.5 * p.module).round() as isize,
+        );
+        draw::rectangle(
+            &mut out.bytes,
+            width,
+            height,
+            corner,
+            (side, side),
+            &[255, 0, 0],
+        );
+    }
+    out
+}
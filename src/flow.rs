@@ -0,0 +1,208 @@
+use gasyori100knock_rs::color::Hsv;
+use gasyori100knock_rs::resize::sample;
+
+use crate::gradient::OPERATOR;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::tensor::{StructureTensor, SIGMA};
+use crate::{ensure_grayscale, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[OPERATOR, SIGMA];
+
+pub static LINE_PARAMS: &[ParamSpec] = &[
+    OPERATOR,
+    SIGMA,
+    // of the DoG across the edge, the surround uses 1.6 times it
+    ParamSpec {
+        name: "sigma_c",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive],
+    },
+    // weight of the surround, lower values give thinner lines
+    ParamSpec {
+        name: "rho",
+        kind: Kind::Float,
+        default: Some("0.99"),
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    // of the smoothing along the flow, longer lines for larger values
+    ParamSpec {
+        name: "sigma_m",
+        kind: Kind::Float,
+        default: Some("3"),
+        constraints: &[Constraint::Positive],
+    },
+    // pixels with a negative response and 1 + tanh(response) below it turn black
+    ParamSpec {
+        name: "tau",
+        kind: Kind::Float,
+        default: Some("0.99"),
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    // each pass darkens the lines found so far in the input and filters again
+    ParamSpec {
+        name: "iterations",
+        kind: Kind::Int,
+        default: Some("1"),
+        constraints: &[Constraint::Range(1., 16.)],
+    },
+];
+
+/// Edge tangent flow: per pixel the unit direction along the edges, i.e. the
+/// minor eigenvector of the smoothed structure tensor, and how clearly it stands
+/// out as `(l1 - l2) / (l1 + l2)` in [0, 1]
+pub struct Flow {
+    pub width: usize,
+    pub height: usize,
+    pub tangents: Vec<(f64, f64)>,
+    pub coherence: Vec<f64>,
+}
+
+impl Flow {
+    pub fn new(img: &Image, params: &Params) -> Self {
+        let tensor = StructureTensor::from_params(img, params);
+        let len = tensor.xx.len();
+        Self {
+            width: tensor.width,
+            height: tensor.height,
+            tangents: (0..len)
+                .map(|i| {
+                    let phi = tensor.orientation(i);
+                    (-phi.sin(), phi.cos())
+                })
+                .collect(),
+            coherence: (0..len).map(|i| tensor.coherence(i)).collect(),
+        }
+    }
+
+    // tangent of the pixel nearest to (x, y), clamped to the image
+    fn tangent_at(&self, x: f64, y: f64) -> (f64, f64) {
+        let x = x.round().clamp(0., (self.width - 1) as f64) as usize;
+        let y = y.round().clamp(0., (self.height - 1) as f64) as usize;
+        self.tangents[y * self.width + x]
+    }
+}
+
+// normalized 1D Gaussian weights for offsets -reach..=reach
+fn gaussian_weights(sigma: f64, reach: usize) -> Vec<f64> {
+    let w: Vec<f64> = (-(reach as isize)..=reach as isize)
+        .map(|t| (-(t * t) as f64 / (2. * sigma * sigma)).exp())
+        .collect();
+    let sum: f64 = w.iter().sum();
+    w.into_iter().map(|v| v / sum).collect()
+}
+
+/// Edge tangent flow as a color image: the orientation as hue (red along x,
+/// cyan along y) and the coherence as value
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let flow = Flow::new(&img, params);
+    Image {
+        info: Info {
+            color: png::ColorType::Rgb,
+            ..img.info
+        },
+        bytes: flow
+            .tangents
+            .iter()
+            .zip(&flow.coherence)
+            .flat_map(|(&(tx, ty), &c)| {
+                // orientations repeat every 180 degrees, so the angle is doubled
+                let angle = 2. * ty.atan2(tx).to_degrees();
+                Hsv {
+                    h: (angle + 720.) % 360.,
+                    s: c,
+                    v: c,
+                }
+                .into_rgb()
+            })
+            .collect(),
+        mask: None,
+    }
+}
+
+// DoG across the edge at every pixel, sampled along the gradient direction
+fn cross_dog(plane: &[f64], flow: &Flow, sigma_c: f64, rho: f64) -> Vec<f64> {
+    let sigma_s = 1.6 * sigma_c;
+    let reach = (3. * sigma_s).ceil() as usize;
+    let (center, surround) = (
+        gaussian_weights(sigma_c, reach),
+        gaussian_weights(sigma_s, reach),
+    );
+    let (width, height) = (flow.width, flow.height);
+    (0..plane.len())
+        .map(|i| {
+            let (x, y) = ((i % width) as f64, (i / width) as f64);
+            // the gradient is perpendicular to the tangent
+            let (nx, ny) = (flow.tangents[i].1, -flow.tangents[i].0);
+            (0..center.len())
+                .map(|k| {
+                    let t = k as f64 - reach as f64;
+                    let v = sample(plane, width, height, x + t * nx, y + t * ny);
+                    (center[k] - rho * surround[k]) * v
+                })
+                .sum()
+        })
+        .collect()
+}
+
+// Gaussian-weighted sum of `dog` along the flow line through every pixel
+fn along_flow(dog: &[f64], flow: &Flow, sigma_m: f64) -> Vec<f64> {
+    let reach = (3. * sigma_m).ceil() as usize;
+    let weights = gaussian_weights(sigma_m, reach);
+    let (width, height) = (flow.width, flow.height);
+    (0..dog.len())
+        .map(|i| {
+            let start = ((i % width) as f64, (i / width) as f64);
+            let mut sum = weights[reach] * dog[i];
+            for sign in [1., -1.] {
+                let (mut x, mut y) = start;
+                let (tx, ty) = flow.tangents[i];
+                let mut dir = (sign * tx, sign * ty);
+                for weight in &weights[reach + 1..] {
+                    x += dir.0;
+                    y += dir.1;
+                    if !(0. ..=(width - 1) as f64).contains(&x)
+                        || !(0. ..=(height - 1) as f64).contains(&y)
+                    {
+                        break;
+                    }
+                    sum += weight * sample(dog, width, height, x, y);
+                    // keep heading the same way, tangents have no sign
+                    let (tx, ty) = flow.tangent_at(x, y);
+                    dir = if tx * dir.0 + ty * dir.1 < 0. {
+                        (-tx, -ty)
+                    } else {
+                        (tx, ty)
+                    };
+                }
+            }
+            sum
+        })
+        .collect()
+}
+
+/// Coherent line drawing (Kang et al.): a flow-based DoG, across the edges and
+/// then smoothed along the edge tangent flow, thresholded to black lines on white
+pub fn coherent_lines(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let flow = Flow::new(&img, params);
+    let tau = params.float("tau");
+    let mut plane: Vec<f64> = img.bytes.iter().map(|&v| v as f64 / 255.).collect();
+    let mut lines = vec![false; plane.len()];
+    for _ in 0..params.int("iterations") {
+        let dog = cross_dog(&plane, &flow, params.float("sigma_c"), params.float("rho"));
+        let response = along_flow(&dog, &flow, params.float("sigma_m"));
+        for (i, &h) in response.iter().enumerate() {
+            lines[i] = h < 0. && 1. + h.tanh() < tau;
+            if lines[i] {
+                plane[i] = 0.;
+            }
+        }
+    }
+    Image {
+        info: img.info,
+        bytes: lines.iter().map(|&l| if l { 0 } else { 255 }).collect(),
+        mask: None,
+    }
+}
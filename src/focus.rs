@@ -0,0 +1,220 @@
+use std::fmt::Write;
+
+use gasyori100knock_rs::convolve::Operator;
+
+use crate::gradient::derivatives;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, read_input, report, write_param_image, Image, Info};
+
+// laplacian: variance of the Laplacian, tenengrad: mean squared Sobel gradient
+const METRIC: ParamSpec = ParamSpec {
+    name: "metric",
+    kind: Kind::Choice(&["laplacian", "tenengrad"]),
+    default: Some("laplacian"),
+    constraints: &[],
+};
+
+pub static PARAMS: &[ParamSpec] = &[
+    METRIC,
+    // side of the square tiles scored on their own, none when unset
+    ParamSpec {
+        name: "tile",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(2., 65535.)],
+    },
+    // where to write the tile scores as an image, brightest for the sharpest; needs `tile`
+    ParamSpec {
+        name: "heatmap",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    report::PARAM,
+];
+
+pub static RANK_PARAMS: &[ParamSpec] = &[METRIC, report::PARAM];
+
+#[derive(Clone, Copy)]
+enum Metric {
+    Laplacian,
+    Tenengrad,
+}
+
+impl Metric {
+    fn from_params(params: &Params) -> Self {
+        match params.str(METRIC.name) {
+            "laplacian" => Metric::Laplacian,
+            "tenengrad" => Metric::Tenengrad,
+            _ => unreachable!(),
+        }
+    }
+
+    // per-pixel quantity the score summarizes
+    fn responses(self, img: &Image) -> Vec<f64> {
+        let (width, height) = (img.info.width as usize, img.info.height as usize);
+        match self {
+            Metric::Laplacian => {
+                // edges replicated, so the border adds no response of its own
+                let at = |x: isize, y: isize| {
+                    let x = x.clamp(0, width as isize - 1) as usize;
+                    let y = y.clamp(0, height as isize - 1) as usize;
+                    img.bytes[y * width + x] as f64
+                };
+                (0..width * height)
+                    .map(|i| {
+                        let (x, y) = ((i % width) as isize, (i / width) as isize);
+                        at(x - 1, y) + at(x + 1, y) + at(x, y - 1) + at(x, y + 1) - 4. * at(x, y)
+                    })
+                    .collect()
+            }
+            Metric::Tenengrad => {
+                let (gx, gy) = derivatives(img, Operator::Sobel);
+                gx.iter().zip(&gy).map(|(x, y)| x * x + y * y).collect()
+            }
+        }
+    }
+
+    // larger is sharper
+    fn score(self, values: impl Iterator<Item = f64> + Clone) -> f64 {
+        let n = values.clone().count() as f64;
+        let mean = values.clone().sum::<f64>() / n;
+        match self {
+            Metric::Laplacian => values.map(|v| (v - mean).powi(2)).sum::<f64>() / n,
+            Metric::Tenengrad => mean,
+        }
+    }
+}
+
+/// Global sharpness of a grayscale image
+fn global_score(img: &Image, metric: Metric) -> f64 {
+    metric.score(metric.responses(img).into_iter())
+}
+
+/// Reports the sharpness of the image and optionally of each tile, passing the input through
+pub fn run(img: Image, params: &Params) -> Image {
+    let gray = ensure_grayscale(img.clone());
+    let metric = Metric::from_params(params);
+    let (width, height) = (gray.info.width as usize, gray.info.height as usize);
+    let responses = metric.responses(&gray);
+    let score = metric.score(responses.iter().copied());
+
+    let mut text = String::new();
+    write!(
+        text,
+        "{{\n  \"metric\": \"{}\",\n  \"score\": {}",
+        params.str(METRIC.name),
+        score
+    )
+    .unwrap();
+    match params.int_opt("tile") {
+        Some(size) => {
+            let size = size as usize;
+            let (columns, rows) = (width.div_ceil(size), height.div_ceil(size));
+            let scores: Vec<f64> = (0..columns * rows)
+                .map(|t| {
+                    let (x0, y0) = (t % columns * size, t / columns * size);
+                    let (x1, y1) = ((x0 + size).min(width), (y0 + size).min(height));
+                    let values = (y0..y1)
+                        .flat_map(|y| responses[y * width + x0..y * width + x1].iter().copied());
+                    metric.score(values)
+                })
+                .collect();
+            let grid: Vec<String> = scores
+                .chunks(columns)
+                .map(|row| {
+                    let cells: Vec<String> = row.iter().map(|s| format!("{:.2}", s)).collect();
+                    format!("    [{}]", cells.join(", "))
+                })
+                .collect();
+            writeln!(
+                text,
+                ",\n  \"tile\": {},\n  \"grid\": [\n{}\n  ]\n}}",
+                size,
+                grid.join(",\n")
+            )
+            .unwrap();
+
+            let max = scores.iter().copied().fold(0., f64::max);
+            let heatmap = Image {
+                info: Info {
+                    color: png::ColorType::Grayscale,
+                    ..gray.info.clone()
+                },
+                bytes: (0..width * height)
+                    .map(|i| {
+                        let t = i / width / size * columns + i % width / size;
+                        if max > 0. {
+                            (scores[t] / max * 255.).round() as u8
+                        } else {
+                            0
+                        }
+                    })
+                    .collect(),
+                mask: None,
+            };
+            write_param_image(params, "heatmap", &heatmap);
+        }
+        None => {
+            if params.str_opt("heatmap").is_some() {
+                die!("[ERROR] heatmap needs `tile` to be set");
+            }
+            writeln!(text, "\n}}").unwrap();
+        }
+    }
+    report::emit(params, &text);
+    img
+}
+
+/// Scores every PNG in `dir` and reports them sharpest first
+pub fn rank(dir: &str, params: &Params) {
+    let metric = Metric::from_params(params);
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .unwrap_or_else(|e| die!("[ERROR] failed to read directory {} ({})", dir, e))
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("png"))
+        })
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        die!("[ERROR] no PNG files in {}", dir);
+    }
+    let mut scored: Vec<(String, f64)> = paths
+        .iter()
+        .map(|path| {
+            let img = read_input(path)
+                .unwrap_or_else(|e| die!("[ERROR] failed to read {} ({})", path.display(), e));
+            if img.info.depth != png::BitDepth::Eight {
+                die!(
+                    "[ERROR] the only supported bit depth is 8 ({})",
+                    path.display()
+                );
+            }
+            let score = global_score(&ensure_grayscale(img), metric);
+            (path.display().to_string(), score)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let entries: Vec<String> = scored
+        .iter()
+        .map(|(path, score)| {
+            format!(
+                "    {{\"file\": \"{}\", \"score\": {}}}",
+                path.replace('\\', "\\\\").replace('"', "\\\""),
+                score
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"metric\": \"{}\",\n  \"ranking\": [\n{}\n  ]\n}}",
+        params.str(METRIC.name),
+        entries.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+}
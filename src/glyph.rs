@@ -0,0 +1,144 @@
+use std::f64::consts::PI;
+
+use gasyori100knock_rs::draw;
+
+use crate::gradient::{self, derivatives, OPERATOR};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::tensor::{StructureTensor, SIGMA};
+use crate::{ensure_grayscale, read_param_image, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // gradient: from the derivatives, tensor: from the structure tensor with the
+    // coherence scaling the magnitude; ignored when `orientation` is given
+    ParamSpec {
+        name: "source",
+        kind: Kind::Choice(&["gradient", "tensor"]),
+        default: Some("gradient"),
+        constraints: &[],
+    },
+    // orientation image as written by gradient with orientation_map=gray; the input
+    // is then taken as the magnitude
+    ParamSpec {
+        name: "orientation",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    OPERATOR,
+    SIGMA,
+    // side of the square cells that get one glyph each
+    ParamSpec {
+        name: "cell",
+        kind: Kind::Int,
+        default: Some("8"),
+        constraints: &[Constraint::Range(3., 256.)],
+    },
+    // orientation bins over 0..180 degrees
+    ParamSpec {
+        name: "bins",
+        kind: Kind::Int,
+        default: Some("9"),
+        constraints: &[Constraint::Range(1., 36.)],
+    },
+];
+
+/// Per pixel gradient angle in radians and weight
+fn orientations(img: &Image, params: &Params) -> (Vec<f64>, Vec<f64>) {
+    if params.str_opt("orientation").is_some() {
+        let orientation = ensure_grayscale(read_param_image(params, "orientation"));
+        if (orientation.info.width, orientation.info.height) != (img.info.width, img.info.height) {
+            die!(
+                "[ERROR] orientation is {}x{} but the input is {}x{}",
+                orientation.info.width,
+                orientation.info.height,
+                img.info.width,
+                img.info.height
+            );
+        }
+        let angles = orientation
+            .bytes
+            .iter()
+            .map(|&v| v as f64 / 255. * 2. * PI - PI)
+            .collect();
+        return (angles, img.bytes.iter().map(|&v| v as f64).collect());
+    }
+    let (gx, gy) = derivatives(img, gradient::operator(params));
+    let magnitude = gx.iter().zip(&gy).map(|(x, y)| x.hypot(*y));
+    match params.str("source") {
+        "gradient" => (
+            gx.iter().zip(&gy).map(|(x, y)| y.atan2(*x)).collect(),
+            magnitude.collect(),
+        ),
+        "tensor" => {
+            let tensor = StructureTensor::from_params(img, params);
+            (
+                (0..gx.len()).map(|i| tensor.orientation(i)).collect(),
+                magnitude
+                    .enumerate()
+                    .map(|(i, m)| m * tensor.coherence(i))
+                    .collect(),
+            )
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Orientation histograms of cells drawn as HOG-style glyphs: one line per bin
+/// through the cell center along the edges of that bin, as bright as its share of
+/// the largest bin in the image
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let (angles, weights) = orientations(&img, params);
+    let cell = params.int("cell") as usize;
+    let bins = params.int("bins") as usize;
+    let (columns, rows) = (width / cell, height / cell);
+    let mut histograms = vec![0.; columns * rows * bins];
+    for y in 0..rows * cell {
+        for x in 0..columns * cell {
+            let i = y * width + x;
+            // unsigned orientation in [0, pi), split between the two nearest bins
+            let t = angles[i].rem_euclid(PI) / PI * bins as f64 - 0.5;
+            let lower = t.floor();
+            let frac = t - lower;
+            let base = (y / cell * columns + x / cell) * bins;
+            let bin = |b: f64| (b as isize).rem_euclid(bins as isize) as usize;
+            histograms[base + bin(lower)] += weights[i] * (1. - frac);
+            histograms[base + bin(lower + 1.)] += weights[i] * frac;
+        }
+    }
+
+    let max = histograms.iter().copied().fold(0., f64::max);
+    let mut bytes = vec![0u8; width * height];
+    let half = (cell as f64 - 1.) / 2.;
+    for (c, histogram) in histograms.chunks(bins).enumerate() {
+        let center = (
+            (c % columns * cell) as f64 + half,
+            (c / columns * cell) as f64 + half,
+        );
+        // dimmer bins first so that the stronger ones stay on top where lines cross
+        let mut order: Vec<usize> = (0..bins).collect();
+        order.sort_by(|&a, &b| histogram[a].total_cmp(&histogram[b]));
+        for b in order {
+            if max <= 0. || histogram[b] <= 0. {
+                continue;
+            }
+            let value = (histogram[b] / max * 255.).round() as u8;
+            // edges run across the gradient
+            let theta = (b as f64 + 0.5) / bins as f64 * PI + PI / 2.;
+            let (dx, dy) = (theta.cos() * half, theta.sin() * half);
+            let end = |s: f64| {
+                (
+                    (center.0 + s * dx).round() as isize,
+                    (center.1 + s * dy).round() as isize,
+                )
+            };
+            draw::line(&mut bytes, width, height, end(-1.), end(1.), &[value]);
+        }
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
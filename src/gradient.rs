@@ -0,0 +1,162 @@
+use std::f64::consts::PI;
+
+use gasyori100knock_rs::color::Hsv;
+use gasyori100knock_rs::convolve::{IntKernel, Operator};
+
+use crate::filter::convolve;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, write_param_image, Image, Info};
+
+pub const OPERATOR: ParamSpec = ParamSpec {
+    name: "operator",
+    kind: Kind::Choice(&["sobel", "prewitt", "scharr"]),
+    default: Some("sobel"),
+    constraints: &[],
+};
+
+pub static PARAMS: &[ParamSpec] = &[
+    OPERATOR,
+    // where to write the orientation image, skipped when unset
+    ParamSpec {
+        name: "orientation",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // hue: direction as hue with the magnitude as value, gray: direction as intensity
+    ParamSpec {
+        name: "orientation_map",
+        kind: Kind::Choice(&["hue", "gray"]),
+        default: Some("hue"),
+        constraints: &[],
+    },
+];
+
+pub static DERIVATIVE_PARAMS: &[ParamSpec] = &[
+    OPERATOR,
+    ParamSpec {
+        name: "order",
+        kind: Kind::Choice(&["x", "y", "xx", "yy", "xy"]),
+        default: Some("x"),
+        constraints: &[],
+    },
+    // abs: |d|, offset: 128 + d so that the sign stays visible
+    ParamSpec {
+        name: "output",
+        kind: Kind::Choice(&["abs", "offset"]),
+        default: Some("abs"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "scale",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive],
+    },
+];
+
+pub fn operator(params: &Params) -> Operator {
+    match params.str(OPERATOR.name) {
+        "sobel" => Operator::Sobel,
+        "prewitt" => Operator::Prewitt,
+        "scharr" => Operator::Scharr,
+        _ => unreachable!(),
+    }
+}
+
+/// Horizontal and vertical derivatives scaled so that a step of `d` gives `d`
+pub fn derivatives(img: &Image, operator: Operator) -> (Vec<f64>, Vec<f64>) {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let plane: Vec<f64> = img.bytes.iter().map(|&v| v as f64).collect();
+    let (kx, ky) = IntKernel::gradient(operator);
+    let gain = operator.gain() as f64;
+    let scale = |d: Vec<f64>| d.into_iter().map(|v| v / gain).collect::<Vec<_>>();
+    (
+        scale(convolve(&plane, width, height, &kx.to_float())),
+        scale(convolve(&plane, width, height, &ky.to_float())),
+    )
+}
+
+pub fn gradient(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (gx, gy) = derivatives(&img, operator(params));
+    let magnitude: Vec<f64> = gx.iter().zip(&gy).map(|(x, y)| x.hypot(*y)).collect();
+
+    if params.str_opt("orientation").is_some() {
+        // radians in (-pi, pi], measured from +x towards +y (down)
+        let angles = gx.iter().zip(&gy).map(|(x, y)| y.atan2(*x));
+        let orientation = match params.str("orientation_map") {
+            "hue" => Image {
+                info: Info {
+                    color: png::ColorType::Rgb,
+                    ..img.info.clone()
+                },
+                bytes: angles
+                    .zip(&magnitude)
+                    .flat_map(|(angle, mag)| {
+                        // `s` is chroma here, so full saturation is `s == v`
+                        let v = mag.min(255.) / 255.;
+                        Hsv {
+                            h: (angle.to_degrees() + 360.) % 360.,
+                            s: v,
+                            v,
+                        }
+                        .into_rgb()
+                    })
+                    .collect(),
+                mask: None,
+            },
+            "gray" => Image {
+                info: img.info.clone(),
+                bytes: angles
+                    .map(|angle| ((angle + PI) / (2. * PI) * 255.).round() as u8)
+                    .collect(),
+                mask: None,
+            },
+            _ => unreachable!(),
+        };
+        write_param_image(params, "orientation", &orientation);
+    }
+
+    Image {
+        info: img.info,
+        bytes: magnitude
+            .iter()
+            .map(|m| m.round().clamp(0., 255.) as u8)
+            .collect(),
+        mask: None,
+    }
+}
+
+/// One first or second derivative, normalized by the operator's gain per order
+pub fn derivative(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let operator = operator(params);
+    let (kx, ky) = IntKernel::gradient(operator);
+    let [kxx, kyy, kxy] = IntKernel::second_derivatives(operator);
+    let (kernel, order) = match params.str("order") {
+        "x" => (kx, 1),
+        "y" => (ky, 1),
+        "xx" => (kxx, 2),
+        "yy" => (kyy, 2),
+        "xy" => (kxy, 2),
+        _ => unreachable!(),
+    };
+    let plane: Vec<f64> = img.bytes.iter().map(|&v| v as f64).collect();
+    let scale = params.float("scale") / (operator.gain() as f64).powi(order);
+    let offset = params.str("output") == "offset";
+    let bytes = convolve(&plane, width, height, &kernel.to_float())
+        .iter()
+        .map(|d| {
+            let d = d * scale;
+            let v = if offset { 128. + d } else { d.abs() };
+            v.round().clamp(0., 255.) as u8
+        })
+        .collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
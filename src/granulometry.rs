@@ -0,0 +1,132 @@
+use std::fmt::Write;
+
+use crate::morphology::{morph, Step, StructuringElement};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, report, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "shape",
+        kind: Kind::Choice(&["ellipse", "rect", "cross"]),
+        default: Some("ellipse"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "max_radius",
+        kind: Kind::Int,
+        default: Some("10"),
+        constraints: &[Constraint::Range(1., 49.)],
+    },
+    ParamSpec {
+        name: "format",
+        kind: Kind::Choice(&["json", "csv"]),
+        default: Some("json"),
+        constraints: &[],
+    },
+    report::PARAM,
+];
+
+const BAR_WIDTH: usize = 12;
+const BAR_GAP: usize = 4;
+const PLOT_HEIGHT: usize = 200;
+
+/// Opens the image with elements of radius 0, 1, ..., `max_radius` and reports
+/// how much area each step removes (the pattern spectrum), drawn as a bar chart
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let max_radius = params.int("max_radius") as usize;
+
+    // sum of intensities / 255, i.e. the foreground pixel count of a binary image
+    let areas: Vec<f64> = (0..=max_radius + 1)
+        .map(|r| {
+            let size = 2 * r + 1;
+            let se = match params.str("shape") {
+                "ellipse" => StructuringElement::ellipse(size, size),
+                "rect" => StructuringElement::rect(size, size),
+                "cross" => StructuringElement::cross(size, size),
+                _ => unreachable!(),
+            };
+            let opened = morph(&img.bytes, width, height, &se, &[Step::Erode, Step::Dilate]);
+            opened.iter().map(|&v| v as f64).sum::<f64>() / 255.
+        })
+        .collect();
+    let spectrum: Vec<f64> = areas.windows(2).map(|w| w[0] - w[1]).collect();
+    let total = areas[0];
+    let fraction = |removed: f64| if total > 0. { removed / total } else { 0. };
+
+    let text = match params.str("format") {
+        "csv" => {
+            let mut out = String::from("radius,size,area,removed,fraction\n");
+            for (r, removed) in spectrum.iter().enumerate() {
+                writeln!(
+                    out,
+                    "{},{},{},{},{}",
+                    r,
+                    2 * r + 1,
+                    areas[r],
+                    removed,
+                    fraction(*removed)
+                )
+                .unwrap();
+            }
+            out
+        }
+        "json" => {
+            let entries: Vec<String> = spectrum
+                .iter()
+                .enumerate()
+                .map(|(r, removed)| {
+                    format!(
+                        "    {{\"radius\": {}, \"size\": {}, \"area\": {}, \"removed\": {}, \"fraction\": {}}}",
+                        r,
+                        2 * r + 1,
+                        areas[r],
+                        removed,
+                        fraction(*removed)
+                    )
+                })
+                .collect();
+            format!(
+                "{{\n  \"total_area\": {},\n  \"spectrum\": [\n{}\n  ]\n}}\n",
+                total,
+                entries.join(",\n")
+            )
+        }
+        _ => unreachable!(),
+    };
+    report::emit(params, &text);
+
+    plot(&spectrum, img.info)
+}
+
+// white background with one black bar per radius, scaled to the tallest one
+fn plot(values: &[f64], info: Info) -> Image {
+    let width = values.len() * (BAR_WIDTH + BAR_GAP) + BAR_GAP;
+    let mut bytes = vec![255u8; width * PLOT_HEIGHT];
+    let max = values.iter().copied().fold(0., f64::max);
+    for (i, value) in values.iter().enumerate() {
+        let bar = if max > 0. {
+            (value.max(0.) / max * (PLOT_HEIGHT - 1) as f64).round() as usize
+        } else {
+            0
+        };
+        let left = BAR_GAP + i * (BAR_WIDTH + BAR_GAP);
+        for y in PLOT_HEIGHT - bar..PLOT_HEIGHT {
+            bytes[y * width + left..y * width + left + BAR_WIDTH].fill(0);
+        }
+    }
+    // baseline
+    bytes[(PLOT_HEIGHT - 1) * width..].fill(0);
+
+    Image {
+        info: Info {
+            width: width as u32,
+            height: PLOT_HEIGHT as u32,
+            color: png::ColorType::Grayscale,
+            ..info
+        },
+        bytes,
+        mask: None,
+    }
+}
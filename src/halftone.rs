@@ -0,0 +1,100 @@
+use std::f64::consts::PI;
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::Image;
+
+const ANGLE: Constraint = Constraint::Range(-360., 360.);
+
+pub static PARAMS: &[ParamSpec] = &[
+    // dot pitch in pixels
+    ParamSpec {
+        name: "cell",
+        kind: Kind::Float,
+        default: Some("8"),
+        constraints: &[Constraint::Positive],
+    },
+    // screen angle in degrees for grayscale input
+    ParamSpec {
+        name: "angle",
+        kind: Kind::Float,
+        default: Some("45"),
+        constraints: &[ANGLE],
+    },
+    // screen angles of the cyan, magenta and yellow separations of RGB input,
+    // the customary ones keep the screens from beating against each other
+    ParamSpec {
+        name: "angle_c",
+        kind: Kind::Float,
+        default: Some("15"),
+        constraints: &[ANGLE],
+    },
+    ParamSpec {
+        name: "angle_m",
+        kind: Kind::Float,
+        default: Some("75"),
+        constraints: &[ANGLE],
+    },
+    ParamSpec {
+        name: "angle_y",
+        kind: Kind::Float,
+        default: Some("0"),
+        constraints: &[ANGLE],
+    },
+];
+
+/// Screens one channel, 255 meaning no ink: each pixel is inked when the round
+/// spot function of its position in the rotated cell exceeds the ink coverage
+fn screen(
+    channel: impl Iterator<Item = u8>,
+    width: usize,
+    cell: f64,
+    angle: f64,
+) -> impl Iterator<Item = u8> {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    channel.enumerate().map(move |(i, v)| {
+        let (x, y) = ((i % width) as f64 + 0.5, (i / width) as f64 + 0.5);
+        let (u, w) = ((x * cos + y * sin) / cell, (y * cos - x * sin) / cell);
+        // 1 at the cell centers, -1 at their corners
+        let spot = ((2. * PI * u).cos() + (2. * PI * w).cos()) / 2.;
+        let coverage = 1. - v as f64 / 255.;
+        if spot > 1. - 2. * coverage {
+            0
+        } else {
+            255
+        }
+    })
+}
+
+/// Clustered-dot halftone, one rotated screen per CMY separation of RGB input
+pub fn run(img: Image, params: &Params) -> Image {
+    let width = img.info.width as usize;
+    let cell = params.float("cell");
+    let bytes = match img.info.color {
+        png::ColorType::Grayscale => {
+            screen(img.bytes.into_iter(), width, cell, params.float("angle")).collect()
+        }
+        png::ColorType::Rgb => {
+            // cyan ink absorbs red, magenta green and yellow blue
+            let channels: Vec<Vec<u8>> = ["angle_c", "angle_m", "angle_y"]
+                .iter()
+                .enumerate()
+                .map(|(c, angle)| {
+                    let channel = img.bytes.iter().skip(c).step_by(3).copied();
+                    screen(channel, width, cell, params.float(angle)).collect()
+                })
+                .collect();
+            (0..img.bytes.len())
+                .map(|i| channels[i % 3][i / 3])
+                .collect()
+        }
+        other => die!(
+            "[ERROR] halftone takes grayscale or RGB input, got {:?}",
+            other
+        ),
+    };
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,377 @@
+use gasyori100knock_rs::color::Hsv;
+use gasyori100knock_rs::point;
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, Image, Info};
+
+// joint: one histogram over all channels as q23 does, rgb: one per channel,
+// luma: Y of YCbCr with the chroma kept, value: V of HSV with hue and saturation kept
+pub static EQUALIZE_PARAMS: &[ParamSpec] = &[ParamSpec {
+    name: "mode",
+    kind: Kind::Choice(&["joint", "rgb", "luma", "value", "gray"]),
+    default: Some("joint"),
+    constraints: &[],
+}];
+
+pub static CLAHE_PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "mode",
+        kind: Kind::Choice(&["rgb", "luma", "value", "gray"]),
+        default: Some("luma"),
+        constraints: &[],
+    },
+    // per side
+    ParamSpec {
+        name: "tiles",
+        kind: Kind::Int,
+        default: Some("8"),
+        constraints: &[Constraint::Range(1., 64.)],
+    },
+    // in multiples of the mean bin count, 1 makes the histograms flat
+    ParamSpec {
+        name: "clip_limit",
+        kind: Kind::Float,
+        default: Some("2"),
+        constraints: &[Constraint::Range(1., 256.)],
+    },
+];
+
+pub static HS_PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "hue_bins",
+        kind: Kind::Int,
+        default: Some("180"),
+        constraints: &[Constraint::Range(1., 360.)],
+    },
+    ParamSpec {
+        name: "sat_bins",
+        kind: Kind::Int,
+        default: Some("64"),
+        constraints: &[Constraint::Range(1., 256.)],
+    },
+    CELL,
+    // hue: each cell in its own color at a brightness following the density
+    ParamSpec {
+        name: "color",
+        kind: Kind::Choice(&["hue", "gray"]),
+        default: Some("hue"),
+        constraints: &[],
+    },
+];
+
+pub static RGB_PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "bins",
+        kind: Kind::Int,
+        default: Some("64"),
+        constraints: &[Constraint::Range(1., 256.)],
+    },
+    CELL,
+];
+
+// side in pixels of one bin in the rendered histograms
+const CELL: ParamSpec = ParamSpec {
+    name: "cell",
+    kind: Kind::Int,
+    default: Some("2"),
+    constraints: &[Constraint::Range(1., 32.)],
+};
+
+// gap between the projection panels
+const PANEL_GAP: usize = 8;
+
+fn expect_rgb(img: &Image) {
+    if img.info.color != png::ColorType::Rgb {
+        die!("[ERROR] expected an RGB image, got {:?}", img.info.color);
+    }
+}
+
+// log(1 + count) relative to the fullest bin, so sparse colors stay visible
+fn densities(counts: &[u32]) -> Vec<f64> {
+    let max = counts.iter().copied().max().unwrap_or(0);
+    let norm = (1. + max as f64).ln();
+    counts
+        .iter()
+        .map(|&c| {
+            if max == 0 {
+                0.
+            } else {
+                (1. + c as f64).ln() / norm
+            }
+        })
+        .collect()
+}
+
+// enlarges a `cols` x `rows` grid of RGB cells to `cell` pixels each
+fn render(cells: &[[u8; 3]], cols: usize, rows: usize, cell: usize, info: Info) -> Image {
+    let width = cols * cell;
+    let mut bytes = Vec::with_capacity(width * rows * cell * 3);
+    for y in 0..rows * cell {
+        for x in 0..width {
+            bytes.extend(cells[(y / cell) * cols + x / cell]);
+        }
+    }
+    Image {
+        info: Info {
+            width: width as u32,
+            height: (rows * cell) as u32,
+            color: png::ColorType::Rgb,
+            ..info
+        },
+        bytes,
+        mask: None,
+    }
+}
+
+/// 2D histogram with hue along x and saturation (chroma / value) growing upwards
+pub fn hue_saturation(img: Image, params: &Params) -> Image {
+    expect_rgb(&img);
+    let (hue_bins, sat_bins) = (
+        params.int("hue_bins") as usize,
+        params.int("sat_bins") as usize,
+    );
+    let mut counts = vec![0u32; hue_bins * sat_bins];
+    for p in img.bytes.chunks_exact(3) {
+        let hsv = Hsv::from_rgb(p[0], p[1], p[2]);
+        let sat = if hsv.v > 0. { hsv.s / hsv.v } else { 0. };
+        let h = ((hsv.h / 360. * hue_bins as f64) as usize).min(hue_bins - 1);
+        let s = ((sat * sat_bins as f64) as usize).min(sat_bins - 1);
+        counts[(sat_bins - 1 - s) * hue_bins + h] += 1;
+    }
+    let colored = params.str("color") == "hue";
+    let cells: Vec<[u8; 3]> = densities(&counts)
+        .iter()
+        .enumerate()
+        .map(|(i, &d)| {
+            if !colored {
+                return [(d * 255.).round() as u8; 3];
+            }
+            let (h, s) = (i % hue_bins, sat_bins - 1 - i / hue_bins);
+            Hsv {
+                h: (h as f64 + 0.5) / hue_bins as f64 * 360.,
+                s: d * (s as f64 + 0.5) / sat_bins as f64,
+                v: d,
+            }
+            .into_rgb()
+        })
+        .collect();
+    render(
+        &cells,
+        hue_bins,
+        sat_bins,
+        params.int("cell") as usize,
+        img.info,
+    )
+}
+
+/// Density of the RGB cube projected onto the R-G, G-B and B-R planes, side by side;
+/// the first channel runs along x and the second grows upwards
+pub fn rgb_projection(img: Image, params: &Params) -> Image {
+    expect_rgb(&img);
+    let bins = params.int("bins") as usize;
+    let cell = params.int("cell") as usize;
+    let bin = |v: u8| v as usize * bins / 256;
+    let panels: Vec<Image> = [(0, 1), (1, 2), (2, 0)]
+        .iter()
+        .map(|&(a, b)| {
+            let mut counts = vec![0u32; bins * bins];
+            for p in img.bytes.chunks_exact(3) {
+                counts[(bins - 1 - bin(p[b])) * bins + bin(p[a])] += 1;
+            }
+            let cells: Vec<[u8; 3]> = densities(&counts)
+                .iter()
+                .map(|&d| [(d * 255.).round() as u8; 3])
+                .collect();
+            render(&cells, bins, bins, cell, img.info.clone())
+        })
+        .collect();
+
+    let side = bins * cell;
+    let width = 3 * side + 2 * PANEL_GAP;
+    let mut bytes = vec![255u8; width * side * 3];
+    for (n, panel) in panels.iter().enumerate() {
+        let left = n * (side + PANEL_GAP);
+        for y in 0..side {
+            let row = &panel.bytes[y * side * 3..(y + 1) * side * 3];
+            bytes[(y * width + left) * 3..(y * width + left + side) * 3].copy_from_slice(row);
+        }
+    }
+    Image {
+        info: Info {
+            width: width as u32,
+            height: side as u32,
+            color: png::ColorType::Rgb,
+            ..img.info
+        },
+        bytes,
+        mask: None,
+    }
+}
+
+/// Runs `f` on the planes `mode` selects and puts the results back
+fn apply_mode(img: Image, mode: &str, f: impl Fn(&[u8]) -> Vec<u8>) -> Image {
+    if mode == "gray" || img.info.color == png::ColorType::Grayscale {
+        let img = ensure_grayscale(img);
+        return Image {
+            bytes: f(&img.bytes),
+            info: img.info,
+            mask: None,
+        };
+    }
+    if img.info.color != png::ColorType::Rgb {
+        die!(
+            "[ERROR] expected an RGB or grayscale image, got {:?}",
+            img.info.color
+        );
+    }
+    let mut bytes = img.bytes;
+    match mode {
+        "joint" => bytes = f(&bytes),
+        "rgb" => {
+            for c in 0..3 {
+                let plane: Vec<u8> = bytes.iter().skip(c).step_by(3).copied().collect();
+                for (i, v) in f(&plane).into_iter().enumerate() {
+                    bytes[i * 3 + c] = v;
+                }
+            }
+        }
+        // adding the same amount to R, G and B changes Y alone
+        "luma" => {
+            let luma: Vec<f64> = bytes
+                .chunks_exact(3)
+                .map(|p| 0.2126 * p[0] as f64 + 0.7152 * p[1] as f64 + 0.0722 * p[2] as f64)
+                .collect();
+            let rounded: Vec<u8> = luma.iter().map(|y| y.round() as u8).collect();
+            for ((p, y), new) in bytes.chunks_exact_mut(3).zip(&luma).zip(f(&rounded)) {
+                let delta = new as f64 - y;
+                p.iter_mut()
+                    .for_each(|v| *v = (*v as f64 + delta).round().clamp(0., 255.) as u8);
+            }
+        }
+        // scaling R, G and B by the same factor changes V alone
+        "value" => {
+            let value: Vec<u8> = bytes
+                .chunks_exact(3)
+                .map(|p| p[0].max(p[1]).max(p[2]))
+                .collect();
+            for ((p, &v), new) in bytes.chunks_exact_mut(3).zip(&value).zip(f(&value)) {
+                if v == 0 {
+                    p.fill(new);
+                } else {
+                    let scale = new as f64 / v as f64;
+                    p.iter_mut()
+                        .for_each(|c| *c = (*c as f64 * scale).round().min(255.) as u8);
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// q23. With a mask the histogram is weighted by it and the mapping applied in
+/// proportion to it.
+pub fn equalize(img: Image, params: &Params) -> Image {
+    let mask = img.mask.clone();
+    apply_mode(img, params.str("mode"), |plane| {
+        let Some(mask) = &mask else {
+            let lut = point::equalization_lut(&point::histogram(plane).map(u64::from));
+            let mut out = plane.to_vec();
+            point::apply_lut(&mut out, &lut);
+            return out;
+        };
+        // the joint mode passes every sample of a pixel
+        let per_pixel = plane.len() / mask.len();
+        let weight = |i: usize| mask[i / per_pixel] as u64;
+        // weights up to 255 per sample overflow u32 bins on large images
+        let mut hist = [0u64; 256];
+        for (i, &v) in plane.iter().enumerate() {
+            hist[v as usize] += weight(i);
+        }
+        let lut = point::equalization_lut(&hist);
+        plane
+            .iter()
+            .enumerate()
+            .map(|(i, &v)| {
+                let w = weight(i) as f64 / 255.;
+                (v as f64 + w * (lut[v as usize] as f64 - v as f64)).round() as u8
+            })
+            .collect()
+    })
+}
+
+// clips the histogram at `limit` and spreads the excess over all bins
+fn clip(hist: &mut [u32; 256], limit: u32) {
+    let mut excess = 0;
+    for count in hist.iter_mut() {
+        if *count > limit {
+            excess += *count - limit;
+            *count = limit;
+        }
+    }
+    let (share, rest) = (excess / 256, (excess % 256) as usize);
+    for (i, count) in hist.iter_mut().enumerate() {
+        *count += share + (i < rest) as u32;
+    }
+}
+
+fn clahe_plane(
+    plane: &[u8],
+    width: usize,
+    height: usize,
+    tiles: usize,
+    clip_limit: f64,
+) -> Vec<u8> {
+    let (tx, ty) = (tiles.min(width), tiles.min(height));
+    let bounds = |i: usize, n: usize, len: usize| (i * len / n, (i + 1) * len / n);
+    let luts: Vec<[u8; 256]> = (0..ty)
+        .flat_map(|j| (0..tx).map(move |i| (i, j)))
+        .map(|(i, j)| {
+            let (x0, x1) = bounds(i, tx, width);
+            let (y0, y1) = bounds(j, ty, height);
+            let mut hist = [0u32; 256];
+            for y in y0..y1 {
+                for &v in &plane[y * width + x0..y * width + x1] {
+                    hist[v as usize] += 1;
+                }
+            }
+            let area = ((x1 - x0) * (y1 - y0)) as f64;
+            clip(&mut hist, ((clip_limit * area / 256.).ceil() as u32).max(1));
+            point::equalization_lut(&hist.map(u64::from))
+        })
+        .collect();
+
+    // position relative to the tile centers: index of the one before and the weight of the next
+    let locate = |p: usize, n: usize, len: usize| {
+        let f = (p as f64 + 0.5) * n as f64 / len as f64 - 0.5;
+        let i0 = (f.floor().max(0.) as usize).min(n - 1);
+        (i0, (i0 + 1).min(n - 1), (f - i0 as f64).clamp(0., 1.))
+    };
+    let mut out = Vec::with_capacity(plane.len());
+    for y in 0..height {
+        let (j0, j1, wy) = locate(y, ty, height);
+        for x in 0..width {
+            let (i0, i1, wx) = locate(x, tx, width);
+            let v = plane[y * width + x] as usize;
+            let at = |i: usize, j: usize| luts[j * tx + i][v] as f64;
+            let top = at(i0, j0) * (1. - wx) + at(i1, j0) * wx;
+            let bottom = at(i0, j1) * (1. - wx) + at(i1, j1) * wx;
+            out.push((top * (1. - wy) + bottom * wy).round() as u8);
+        }
+    }
+    out
+}
+
+/// Contrast limited adaptive histogram equalization, tile mappings blended bilinearly
+pub fn clahe(img: Image, params: &Params) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let tiles = params.int("tiles") as usize;
+    let clip_limit = params.float("clip_limit");
+    apply_mode(img, params.str("mode"), |plane| {
+        clahe_plane(plane, width, height, tiles, clip_limit)
+    })
+}
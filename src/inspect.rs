@@ -0,0 +1,161 @@
+use gasyori100knock_rs::{draw, font};
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{to_rgb, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // top left of the region
+    ParamSpec {
+        name: "x",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., 65535.), Constraint::Required],
+    },
+    ParamSpec {
+        name: "y",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., 65535.), Constraint::Required],
+    },
+    // of the region, clipped to the image
+    ParamSpec {
+        name: "width",
+        kind: Kind::Int,
+        default: Some("16"),
+        constraints: &[Constraint::Range(1., 256.)],
+    },
+    ParamSpec {
+        name: "height",
+        kind: Kind::Int,
+        default: Some("16"),
+        constraints: &[Constraint::Range(1., 256.)],
+    },
+    // output pixels per input pixel
+    ParamSpec {
+        name: "scale",
+        kind: Kind::Int,
+        default: Some("24"),
+        constraints: &[Constraint::Range(2., 128.)],
+    },
+    ParamSpec {
+        name: "grid",
+        kind: Kind::Choice(&["on", "off"]),
+        default: Some("on"),
+        constraints: &[],
+    },
+    // sample values printed in every cell, one line per channel, when they fit
+    ParamSpec {
+        name: "values",
+        kind: Kind::Choice(&["on", "off"]),
+        default: Some("on"),
+        constraints: &[],
+    },
+];
+
+/// Crops a region, enlarges it by nearest neighbor and overlays the pixel grid
+/// and the sample values
+pub fn run(img: Image, params: &Params) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let (x0, y0) = (params.int("x") as usize, params.int("y") as usize);
+    if x0 >= width || y0 >= height {
+        die!(
+            "[ERROR] ({}, {}) lies outside the {}x{} image",
+            x0,
+            y0,
+            width,
+            height
+        );
+    }
+    let w = (params.int("width") as usize).min(width - x0);
+    let h = (params.int("height") as usize).min(height - y0);
+    let scale = params.int("scale") as usize;
+    let channels = img.info.color.samples();
+    let rgb = to_rgb(img.clone());
+    let (out_w, out_h) = (w * scale, h * scale);
+    let mut bytes = vec![0u8; out_w * out_h * 3];
+    for y in 0..out_h {
+        for x in 0..out_w {
+            let src = ((y0 + y / scale) * width + x0 + x / scale) * 3;
+            bytes[(y * out_w + x) * 3..][..3].copy_from_slice(&rgb.bytes[src..src + 3]);
+        }
+    }
+
+    if params.str("grid") == "on" {
+        let gray = [128; 3];
+        for i in 0..w {
+            let x = (i * scale) as isize;
+            draw::line(
+                &mut bytes,
+                out_w,
+                out_h,
+                (x, 0),
+                (x, out_h as isize - 1),
+                &gray,
+            );
+        }
+        for j in 0..h {
+            let y = (j * scale) as isize;
+            draw::line(
+                &mut bytes,
+                out_w,
+                out_h,
+                (0, y),
+                (out_w as isize - 1, y),
+                &gray,
+            );
+        }
+    }
+
+    if params.str("values") == "on" {
+        // largest font scale that fits three digits per line and a line per channel
+        let room = scale.saturating_sub(2);
+        let font_scale =
+            (room / font::text_width("255", 1)).min(room / (channels * (font::HEIGHT + 1) - 1));
+        if font_scale == 0 {
+            println!(
+                "[INFO] values skipped, the scale is too small to fit them (try scale={})",
+                2 + (channels * (font::HEIGHT + 1) - 1).max(font::text_width("255", 1))
+            );
+        } else {
+            let line_height = (font::HEIGHT + 1) * font_scale;
+            let block = channels * line_height - font_scale;
+            for j in 0..h {
+                for i in 0..w {
+                    let p = ((y0 + j) * width + x0 + i) * channels;
+                    let samples = &img.bytes[p..p + channels];
+                    // black on light pixels, white on dark ones
+                    let luma = rgb.bytes[((y0 + j) * width + x0 + i) * 3..][..3]
+                        .iter()
+                        .map(|&v| v as usize)
+                        .sum::<usize>()
+                        / 3;
+                    let ink = if luma >= 128 { [0; 3] } else { [255; 3] };
+                    for (k, v) in samples.iter().enumerate() {
+                        let label = v.to_string();
+                        let left = i * scale + (scale - font::text_width(&label, font_scale)) / 2;
+                        let top = j * scale + (scale - block) / 2 + k * line_height;
+                        font::text(
+                            &mut bytes,
+                            out_w,
+                            out_h,
+                            (left as isize, top as isize),
+                            &label,
+                            font_scale,
+                            &ink,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    Image {
+        info: Info {
+            width: out_w as u32,
+            height: out_h as u32,
+            ..rgb.info
+        },
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,515 @@
+use std::f64::consts::{PI, SQRT_2};
+use std::fmt::Write;
+
+use gasyori100knock_rs::color::Hsv;
+
+use crate::bitwise::expect_mask;
+use crate::moments::Moments;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{report, Image, Info};
+
+pub const NEIGHBORS_4: &[(isize, isize)] = &[(0, -1), (-1, 0), (1, 0), (0, 1)];
+pub const NEIGHBORS_8: &[(isize, isize)] = &[
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+pub const CONNECTIVITY: ParamSpec = ParamSpec {
+    name: "connectivity",
+    kind: Kind::Choice(&["8", "4"]),
+    default: Some("8"),
+    constraints: &[],
+};
+
+pub static DESPECKLE_PARAMS: &[ParamSpec] = &[
+    // components with fewer pixels are removed
+    ParamSpec {
+        name: "min_area",
+        kind: Kind::Int,
+        default: Some("10"),
+        constraints: &[Constraint::Positive],
+    },
+    // background: fill small holes instead, both: foreground first
+    ParamSpec {
+        name: "target",
+        kind: Kind::Choice(&["foreground", "background", "both"]),
+        default: Some("foreground"),
+        constraints: &[],
+    },
+    CONNECTIVITY,
+];
+
+pub static FILL_PARAMS: &[ParamSpec] = &[
+    // only holes with at most this many pixels are filled, all of them when unset
+    ParamSpec {
+        name: "max_area",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    // of the foreground, holes are traced with the complementary one
+    CONNECTIVITY,
+];
+
+pub static SHAPE_PARAMS: &[ParamSpec] = &[
+    // bounds are inclusive and each is ignored when unset
+    ParamSpec {
+        name: "min_area",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    ParamSpec {
+        name: "max_area",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    // 4 pi area / perimeter^2, about 1 for a disc and lower for elongated or ragged shapes
+    ParamSpec {
+        name: "min_circularity",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Range(0., 2.)],
+    },
+    ParamSpec {
+        name: "max_circularity",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Range(0., 2.)],
+    },
+    // major over minor axis of the ellipse with the same second moments
+    ParamSpec {
+        name: "min_aspect",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    ParamSpec {
+        name: "max_aspect",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    // area over the area of the convex hull
+    ParamSpec {
+        name: "min_solidity",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    ParamSpec {
+        name: "max_solidity",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    // area over the area of the bounding box
+    ParamSpec {
+        name: "min_extent",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    ParamSpec {
+        name: "max_extent",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[Constraint::Range(0., 1.)],
+    },
+    // keep: only the components within all bounds stay, remove: those are cleared
+    ParamSpec {
+        name: "mode",
+        kind: Kind::Choice(&["keep", "remove"]),
+        default: Some("keep"),
+        constraints: &[],
+    },
+    CONNECTIVITY,
+    report::PARAM,
+];
+
+pub fn neighbors(params: &Params) -> &'static [(isize, isize)] {
+    match params.str(CONNECTIVITY.name) {
+        "8" => NEIGHBORS_8,
+        "4" => NEIGHBORS_4,
+        _ => unreachable!(),
+    }
+}
+
+/// Connected components of a mask, numbered from 1 in raster order of their first pixel
+pub struct Labels {
+    // 0 outside the mask
+    pub labels: Vec<u32>,
+    // pixel count of label `i + 1`
+    pub areas: Vec<usize>,
+}
+
+pub fn label(mask: &[bool], width: usize, height: usize, neighbors: &[(isize, isize)]) -> Labels {
+    assert_eq!(mask.len(), width * height);
+    let mut labels = vec![0u32; mask.len()];
+    let mut areas = Vec::new();
+    let mut stack = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || labels[start] != 0 {
+            continue;
+        }
+        areas.push(0);
+        let current = areas.len() as u32;
+        labels[start] = current;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            areas[current as usize - 1] += 1;
+            let (x, y) = ((i % width) as isize, (i / width) as isize);
+            for (dx, dy) in neighbors {
+                let (nx, ny) = (x + dx, y + dy);
+                if !(0..width as isize).contains(&nx) || !(0..height as isize).contains(&ny) {
+                    continue;
+                }
+                let j = ny as usize * width + nx as usize;
+                if mask[j] && labels[j] == 0 {
+                    labels[j] = current;
+                    stack.push(j);
+                }
+            }
+        }
+    }
+    Labels { labels, areas }
+}
+
+// spreads the hues by the golden angle so that neighboring labels differ
+fn label_color(label: u32) -> [u8; 3] {
+    Hsv {
+        h: (label as f64 * 137.507_764) % 360.,
+        s: 1.,
+        v: 1.,
+    }
+    .into_rgb()
+}
+
+fn colorize(img: Image, neighbors: &[(isize, isize)]) -> Image {
+    expect_mask(&img, "input");
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mask: Vec<bool> = img.bytes.iter().map(|&v| v != 0).collect();
+    let labels = label(&mask, width, height, neighbors);
+    println!("[INFO] {} components", labels.areas.len());
+    Image {
+        info: Info {
+            color: png::ColorType::Rgb,
+            ..img.info
+        },
+        bytes: labels
+            .labels
+            .iter()
+            .flat_map(|&l| if l == 0 { [0; 3] } else { label_color(l) })
+            .collect(),
+        mask: None,
+    }
+}
+
+/// q58, one color per 4-connected component
+pub fn labeling_4(img: Image, _: &Params) -> Image {
+    colorize(img, NEIGHBORS_4)
+}
+
+/// q59, one color per 8-connected component
+pub fn labeling_8(img: Image, _: &Params) -> Image {
+    colorize(img, NEIGHBORS_8)
+}
+
+// sets the components of `value` smaller than `min_area` to the opposite value
+fn remove_small(
+    bytes: &mut [u8],
+    width: usize,
+    height: usize,
+    value: u8,
+    min_area: usize,
+    neighbors: &[(isize, isize)],
+) -> usize {
+    let mask: Vec<bool> = bytes.iter().map(|&v| v == value).collect();
+    let labels = label(&mask, width, height, neighbors);
+    for (v, &l) in bytes.iter_mut().zip(&labels.labels) {
+        if l != 0 && labels.areas[l as usize - 1] < min_area {
+            *v = 255 - value;
+        }
+    }
+    labels.areas.iter().filter(|&&a| a < min_area).count()
+}
+
+pub fn despeckle(img: Image, params: &Params) -> Image {
+    expect_mask(&img, "input");
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let min_area = params.int("min_area") as usize;
+    let neighbors = neighbors(params);
+    let targets: &[u8] = match params.str("target") {
+        "foreground" => &[255],
+        "background" => &[0],
+        "both" => &[255, 0],
+        _ => unreachable!(),
+    };
+    let mut bytes = img.bytes;
+    for &value in targets {
+        let removed = remove_small(&mut bytes, width, height, value, min_area, neighbors);
+        println!(
+            "[INFO] removed {} {} components",
+            removed,
+            if value == 255 {
+                "foreground"
+            } else {
+                "background"
+            }
+        );
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// Fills background components that do not touch the border
+pub fn fill_holes(img: Image, params: &Params) -> Image {
+    expect_mask(&img, "input");
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let background_neighbors = match params.str(CONNECTIVITY.name) {
+        "8" => NEIGHBORS_4,
+        "4" => NEIGHBORS_8,
+        _ => unreachable!(),
+    };
+    let max_area = params
+        .int_opt("max_area")
+        .map_or(usize::MAX, |a| a as usize);
+
+    let mask: Vec<bool> = img.bytes.iter().map(|&v| v == 0).collect();
+    let labels = label(&mask, width, height, background_neighbors);
+    let mut fill: Vec<bool> = labels.areas.iter().map(|&a| a <= max_area).collect();
+    let border = (0..width)
+        .flat_map(|x| [x, (height - 1) * width + x])
+        .chain((0..height).flat_map(|y| [y * width, y * width + width - 1]));
+    for i in border {
+        if labels.labels[i] != 0 {
+            fill[labels.labels[i] as usize - 1] = false;
+        }
+    }
+    println!(
+        "[INFO] filled {} holes",
+        fill.iter().filter(|&&f| f).count()
+    );
+
+    let mut bytes = img.bytes;
+    for (v, &l) in bytes.iter_mut().zip(&labels.labels) {
+        if l != 0 && fill[l as usize - 1] {
+            *v = 255;
+        }
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// Shape descriptors of a connected component
+pub struct Shape {
+    pub area: usize,
+    // Cauchy-Crofton estimate from the boundary crossings in four directions
+    pub perimeter: f64,
+    pub circularity: f64,
+    pub aspect: f64,
+    pub solidity: f64,
+    pub extent: f64,
+}
+
+// twice the area of the convex hull of `points`, by the monotone chain
+fn hull_area2(mut points: Vec<(i64, i64)>) -> i64 {
+    points.sort_unstable();
+    points.dedup();
+    let cross = |o: (i64, i64), a: (i64, i64), b: (i64, i64)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let mut hull: Vec<(i64, i64)> = Vec::with_capacity(2 * points.len());
+    for pass in 0..2 {
+        let start = hull.len();
+        let ordered: Box<dyn Iterator<Item = &(i64, i64)>> = if pass == 0 {
+            Box::new(points.iter())
+        } else {
+            Box::new(points.iter().rev())
+        };
+        for &p in ordered {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+    }
+    (0..hull.len())
+        .map(|i| {
+            let (a, b) = (hull[i], hull[(i + 1) % hull.len()]);
+            a.0 * b.1 - b.0 * a.1
+        })
+        .sum::<i64>()
+        .abs()
+}
+
+/// Descriptors of every component of `labels`, in label order
+pub fn shapes(labels: &Labels, width: usize, height: usize) -> Vec<Shape> {
+    let n = labels.areas.len();
+    let at = |x: isize, y: isize| {
+        if (0..width as isize).contains(&x) && (0..height as isize).contains(&y) {
+            labels.labels[y as usize * width + x as usize]
+        } else {
+            0
+        }
+    };
+    // per component: bounding box, raw moments, crossings per direction and the
+    // horizontal extent of each row
+    let mut bbox = vec![(usize::MAX, usize::MAX, 0, 0); n];
+    let mut sums = vec![[0f64; 5]; n];
+    let mut crossings = vec![[0usize; 4]; n];
+    let mut rows: Vec<Vec<(usize, usize, usize)>> = vec![Vec::new(); n];
+    for y in 0..height {
+        for x in 0..width {
+            let l = labels.labels[y * width + x];
+            if l == 0 {
+                continue;
+            }
+            let c = l as usize - 1;
+            let b = &mut bbox[c];
+            *b = (b.0.min(x), b.1.min(y), b.2.max(x), b.3.max(y));
+            let (fx, fy) = (x as f64, y as f64);
+            for (s, v) in sums[c].iter_mut().zip([fx, fy, fx * fx, fy * fy, fx * fy]) {
+                *s += v;
+            }
+            for (d, (dx, dy)) in [(1, 0), (0, 1), (1, 1), (1, -1)].into_iter().enumerate() {
+                let (x, y) = (x as isize, y as isize);
+                crossings[c][d] += (at(x + dx, y + dy) != l) as usize;
+                crossings[c][d] += (at(x - dx, y - dy) != l) as usize;
+            }
+            match rows[c].last_mut() {
+                Some(row) if row.0 == y => row.2 = x,
+                _ => rows[c].push((y, x, x)),
+            }
+        }
+    }
+
+    (0..n)
+        .map(|c| {
+            let area = labels.areas[c];
+            let a = area as f64;
+            let [sx, sy, sxx, syy, sxy] = sums[c];
+            let (cx, cy) = (sx / a, sy / a);
+            let moments = Moments {
+                area: a,
+                cx,
+                cy,
+                mu20: sxx - sx * cx,
+                mu02: syy - sy * cy,
+                mu11: sxy - sx * cy,
+            };
+            let [h, v, d1, d2] = crossings[c].map(|k| k as f64);
+            let perimeter = PI / 8. * (h + v + (d1 + d2) / SQRT_2);
+            let corners = rows[c]
+                .iter()
+                .flat_map(|&(y, x0, x1)| {
+                    let (y, x0, x1) = (y as i64, x0 as i64, x1 as i64 + 1);
+                    [(x0, y), (x0, y + 1), (x1, y), (x1, y + 1)]
+                })
+                .collect();
+            let (x0, y0, x1, y1) = bbox[c];
+            Shape {
+                area,
+                perimeter,
+                circularity: 4. * PI * a / (perimeter * perimeter),
+                aspect: moments.elongation(),
+                solidity: 2. * a / hull_area2(corners) as f64,
+                extent: a / ((x1 - x0 + 1) * (y1 - y0 + 1)) as f64,
+            }
+        })
+        .collect()
+}
+
+/// Keeps or removes the components of a binary mask whose shape descriptors lie
+/// within the given bounds
+pub fn filter_shapes(img: Image, params: &Params) -> Image {
+    expect_mask(&img, "input");
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mask: Vec<bool> = img.bytes.iter().map(|&v| v != 0).collect();
+    let labels = label(&mask, width, height, neighbors(params));
+    let shapes = shapes(&labels, width, height);
+    let within = |value: f64, name: &str| {
+        params
+            .float_opt(&format!("min_{}", name))
+            .is_none_or(|min| value >= min)
+            && params
+                .float_opt(&format!("max_{}", name))
+                .is_none_or(|max| value <= max)
+    };
+    let keep_matching = params.str("mode") == "keep";
+    let kept: Vec<bool> = shapes
+        .iter()
+        .map(|s| {
+            let matches = within(s.area as f64, "area")
+                && within(s.circularity, "circularity")
+                && within(s.aspect, "aspect")
+                && within(s.solidity, "solidity")
+                && within(s.extent, "extent");
+            matches == keep_matching
+        })
+        .collect();
+    println!(
+        "[INFO] kept {} of {} components",
+        kept.iter().filter(|&&k| k).count(),
+        kept.len()
+    );
+
+    let entries: Vec<String> = shapes
+        .iter()
+        .zip(&kept)
+        .enumerate()
+        .map(|(i, (s, k))| {
+            format!(
+                "    {{\"label\": {}, \"area\": {}, \"perimeter\": {:.2}, \"circularity\": {:.3}, \"aspect\": {:.3}, \"solidity\": {:.3}, \"extent\": {:.3}, \"kept\": {}}}",
+                i + 1,
+                s.area,
+                s.perimeter,
+                s.circularity,
+                s.aspect,
+                s.solidity,
+                s.extent,
+                k
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"components\": [\n{}\n  ]\n}}",
+        entries.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    Image {
+        info: img.info,
+        bytes: labels
+            .labels
+            .iter()
+            .map(|&l| {
+                if l != 0 && kept[l as usize - 1] {
+                    255
+                } else {
+                    0
+                }
+            })
+            .collect(),
+        mask: None,
+    }
+}
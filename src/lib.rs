@@ -0,0 +1,19 @@
+//! Pixel-processing core of the knocks: color conversion, point operations,
+//! convolution, median filtering, error diffusion, pyramids, resampling and
+//! drawing, including bitmap text, on caller-provided buffers. Only `core` and
+//! `alloc` are used, so this part also builds for `no_std` targets; PNG I/O and
+//! the CLI live in the binary.
+
+#![no_std]
+
+extern crate alloc;
+
+pub mod color;
+pub mod convolve;
+pub mod diffusion;
+pub mod draw;
+pub mod font;
+pub mod median;
+pub mod point;
+pub mod pyramid;
+pub mod resize;
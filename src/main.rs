@@ -1,8 +1,11 @@
 use std::path::Path;
 
 use anyhow::Result;
+use gasyori100knock_rs::{color, point};
 use png::OutputInfo;
 
+use params::{Flags, Kind, Params};
+
 macro_rules! die {
     ($( $x:expr ),*) => {
         {
@@ -12,10 +15,101 @@ macro_rules! die {
     }
 }
 
+// declared after `die!` so that the modules can use it
+mod annotate;
+mod arith;
+mod background;
+mod batch;
+mod bitwise;
+mod blend;
+mod colorize;
+mod compare;
+mod completions;
+mod convert;
+mod corner;
+mod defect;
+mod dither;
+mod dog;
+mod fft;
+mod filter;
+mod finder;
+mod flow;
+mod focus;
+mod glyph;
+mod gradient;
+mod granulometry;
+mod halftone;
+mod histogram;
+mod inspect;
+mod label;
+mod man;
+mod moments;
+mod morphology;
+mod ops;
+mod params;
+mod phase;
+mod pool;
+mod quilt;
+mod radon;
+mod ransac;
+mod report;
+mod rng;
+mod saliency;
+mod sampling;
+mod skeleton;
+mod stack;
+mod stats;
+mod stipple;
+mod sweep;
+mod template;
+mod tensor;
+mod threshold;
+mod transfer;
+mod warp;
+
+enum Command {
+    Run(Args),
+    Compare {
+        image: String,
+        reference: String,
+        params: Vec<String>,
+    },
+    Rank {
+        dir: String,
+        params: Vec<String>,
+    },
+    Stack {
+        output: String,
+        frames: Vec<String>,
+        params: Vec<String>,
+    },
+    Batch {
+        input: String,
+        dir: String,
+        ops: Vec<String>,
+        params: Vec<String>,
+        flags: Flags,
+        mask: Option<String>,
+    },
+    Sweep {
+        input: String,
+        dir: String,
+        op: String,
+        params: Vec<String>,
+        flags: Flags,
+        mask: Option<String>,
+    },
+    Completions(completions::Shell),
+    Man,
+}
+
 struct Args {
     input: String,
     output: String,
-    num: usize,
+    op: String,
+    params: Vec<String>,
+    flags: Flags,
+    mask: Option<String>,
 }
 
 #[derive(Clone, Debug)]
@@ -37,68 +131,13 @@ impl From<OutputInfo> for Info {
     }
 }
 
+#[derive(Clone)]
 struct Image {
     info: Info,
     bytes: Vec<u8>,
-}
-
-struct HSV {
-    h: f64, // [0, 360] // [0, 180]
-    s: f64, // [0, 255]
-    v: f64, // [0, 255]
-}
-
-impl HSV {
-    fn from_rgb(r: u8, g: u8, b: u8) -> Self {
-        let colors = [r as f64 / 255., g as f64 / 255., b as f64 / 255.];
-        let v = *colors
-            .iter()
-            .max_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap();
-        let v_min = *colors
-            .iter()
-            .min_by(|a, b| a.partial_cmp(b).unwrap())
-            .unwrap();
-        let s = v - v_min;
-        let [r, g, b] = colors;
-        let h = if s == 0. {
-            0.
-        } else if b == v_min {
-            60. * ((g - r) / s) + 60.
-        } else if r == v_min {
-            60. * ((b - g) / s) + 180.
-        } else if g == v_min {
-            60. * ((r - b) / s) + 300.
-        } else {
-            unreachable!()
-        };
-        assert!((0.0..360.0).contains(&h));
-        assert!((0.0..=1.0).contains(&s));
-        assert!((0.0..=1.0).contains(&v));
-        Self { h, s, v }
-    }
-
-    fn into_rgb(self) -> [u8; 3] {
-        let s = self.s;
-        let h_prime = self.h / 60.;
-        let x = s * (1. - (h_prime % 2. - 1.).abs());
-        let z = 0.;
-        let rgb_float = match h_prime {
-            _ if h_prime < 1. => [s, x, z],
-            _ if h_prime < 2. => [x, s, z],
-            _ if h_prime < 3. => [z, s, x],
-            _ if h_prime < 4. => [z, x, s],
-            _ if h_prime < 5. => [x, z, s],
-            _ if h_prime < 6. => [s, z, x],
-            _ => unreachable!("h_prime should be [0, 6), got {}", h_prime),
-        };
-
-        rgb_float.map(|val| {
-            let modded = val + (self.v - s);
-            assert!(((0.)..=(1.)).contains(&modded));
-            (modded * 255.) as u8
-        })
-    }
+    // soft mask, one weight per pixel with 255 for fully selected; only the
+    // operations in `ops::MASKED` respect it
+    mask: Option<Vec<u8>>,
 }
 
 fn diff<T: PartialOrd + std::ops::Sub<Output = T>>(a: T, b: T) -> T {
@@ -110,100 +149,119 @@ fn diff<T: PartialOrd + std::ops::Sub<Output = T>>(a: T, b: T) -> T {
 }
 
 fn main() {
-    let funcs: &[fn(Image) -> Image] = &[
-        // identity
-        |img| img,
-        // q1
-        |img| {
-            // rgb -> bgr
-            assert_eq!(img.info.color, png::ColorType::Rgb);
-            assert!(img.bytes.len() % 3 == 0);
-            let out = img.bytes
-                .chunks(3)
-                .map(|chunk| match chunk {
-                    [r, g, b] => [b, g, r],
-                    _ => unreachable!(),
-                })
-                .flatten()
-                .copied()
-                .collect();
-            Image { info: img.info, bytes: out }
-        },
-        // q2
-        to_grayscale,
-        // q3
-        |img| {
-            let img = to_grayscale(img);
-            binarize(img, 128)
-        },
-        // q4
-        |img| {
-            // Otsu's method
-            let gray = to_grayscale(img);
-            let histo = {
-                let mut bins = [0usize; 256];
-                for i in &gray.bytes {
-                    bins[*i as usize] += 1;
-                }
-                bins
-            };
-
-            let (best_thres, _) = (0..=255)
-                .map(|n| {
-                    let sum_l: usize = histo[0..n].into_iter().sum();
-                    let sum_r: usize = histo[n..].into_iter().sum();
-                    let mulsum_l: usize =
-                        histo[0..n].into_iter().zip(0..n).map(|(x, y)| x * y).sum();
-                    let mulsum_r: usize = histo[n..255]
-                        .into_iter()
-                        .zip(n..255)
-                        .map(|(x, y)| x * y)
-                        .sum();
-                    let summul = sum_l * sum_r;
-                    if summul != 0 {
-                        let dividend = (diff(sum_l * mulsum_r, sum_r * mulsum_l) as f64).powi(2);
-                        let res = dividend / summul as f64;
-                        Some((n, res))
-                    } else {
-                        None
-                    }
-                })
-                .filter(Option::is_some)
-                .flatten()
-                .max_by(|(_, v1), (_, v2)| v1.partial_cmp(v2).expect("encountered NaN"))
-                .expect("Failed to find threshold");
-
-            println!("threshold: {}", best_thres);
+    let args = match read_command() {
+        Command::Run(args) => args,
+        Command::Completions(shell) => {
+            print!("{}", completions::generate(shell));
+            return;
+        }
+        Command::Man => {
+            print!("{}", man::generate());
+            return;
+        }
+        Command::Compare {
+            image,
+            reference,
+            params,
+        } => {
+            let params = Params::parse(compare::PARAMS, &params, Flags::default())
+                .unwrap_or_else(|e| die!("[ERROR] invalid parameters for compare ({})", e));
+            compare::run(&image, &reference, &params);
+            return;
+        }
+        Command::Rank { dir, params } => {
+            let params = Params::parse(focus::RANK_PARAMS, &params, Flags::default())
+                .unwrap_or_else(|e| die!("[ERROR] invalid parameters for rank ({})", e));
+            focus::rank(&dir, &params);
+            return;
+        }
+        Command::Stack {
+            output,
+            frames,
+            params,
+        } => {
+            let params = Params::parse(stack::PARAMS, &params, Flags::default())
+                .unwrap_or_else(|e| die!("[ERROR] invalid parameters for stack ({})", e));
+            stack::run(&output, &frames, &params);
+            return;
+        }
+        Command::Batch {
+            input,
+            dir,
+            ops,
+            params,
+            flags,
+            mask,
+        } => {
+            batch::run(&input, &dir, &ops, &params, flags, mask.as_deref());
+            return;
+        }
+        Command::Sweep {
+            input,
+            dir,
+            op,
+            params,
+            flags,
+            mask,
+        } => {
+            sweep::run(&input, &dir, &op, &params, flags, mask.as_deref());
+            return;
+        }
+    };
 
-            binarize(gray, best_thres as u8)
-        },
-        // q5
-        |img| {
-            // invert H in HSV
-            let mut hsv_bytes = rgb_to_hsv(img.bytes);
-            for hsv in &mut hsv_bytes {
-                hsv.h = (hsv.h + 180.) % 360.;
+    let op = ops::find(&args.op).unwrap_or_else(|| {
+        let suggestions = ops::suggest(&args.op);
+        if suggestions.is_empty() {
+            die!("[ERROR] no function named {}", args.op)
+        } else {
+            die!(
+                "[ERROR] no function named {}, did you mean: {}?",
+                args.op,
+                suggestions.join(", ")
+            )
+        }
+    });
+    // text parameters may name the paths of this run, see `Kind::Text`
+    let mut given: Vec<String> = args
+        .params
+        .into_iter()
+        .map(|arg| match arg.split_once('=') {
+            Some((name, value))
+                if op
+                    .params
+                    .iter()
+                    .any(|p| p.name == name && matches!(p.kind, Kind::Text)) =>
+            {
+                let value = value
+                    .replace("{input}", &args.input)
+                    .replace("{output}", &args.output);
+                format!("{}={}", name, value)
             }
-            let bytes = hsv_to_rgb(hsv_bytes);
-            Image { info: img.info, bytes }
-        },
-    ];
-
-    let args = read_args();
+            _ => arg,
+        })
+        .collect();
+    if args.flags.knock {
+        given.extend(ops::knock_defaults(op, &given));
+    }
+    let params = Params::parse(op.params, &given, args.flags)
+        .unwrap_or_else(|e| die!("[ERROR] invalid parameters for {} ({})", op.name, e));
 
-    let image =
+    let mut image =
         read_input(args.input).unwrap_or_else(|e| die!("[ERROR] failed to read input ({})", e));
     println!("[INFO] input read {:?}", image.info);
 
-    if image.info.depth != png::BitDepth::Eight {
-        die!("[ERROR] the only supported bit depth is 8");
+    if image.info.depth != png::BitDepth::Eight && !ops::ANY_DEPTH.contains(&op.name) {
+        die!("[ERROR] the only supported bit depth is 8, use convert to change it");
     }
 
+    if let Some(path) = args.mask {
+        if !ops::MASKED.contains(&op.name) {
+            die!("[ERROR] {} does not support --mask", op.name);
+        }
+        image.mask = Some(read_mask(&path, &image));
+    }
 
-    let trans = funcs
-        .get(args.num)
-        .unwrap_or_else(|| die!("[ERROR] no function for number {}", args.num));
-    let out = trans(image);
+    let out = (op.func)(image, &params);
 
     write_output(args.output, &out.info, out.bytes)
         .unwrap_or_else(|e| die!("[ERROR] failed to write output ({})", e));
@@ -216,7 +274,50 @@ fn read_input<T: AsRef<Path>>(input: T) -> Result<Image> {
     let mut reader = decoder.read_info()?;
     let mut buf = vec![0u8; reader.output_buffer_size()];
     let info = reader.next_frame(&mut buf)?.into();
-    Ok(Image { info, bytes: buf})
+    Ok(Image {
+        info,
+        bytes: buf,
+        mask: None,
+    })
+}
+
+/// Reads the extra input image named by the path parameter `name`
+fn read_param_image(params: &Params, name: &str) -> Image {
+    let path = params.str(name);
+    let image = read_input(path)
+        .unwrap_or_else(|e| die!("[ERROR] failed to read {} image {} ({})", name, path, e));
+    if image.info.depth != png::BitDepth::Eight {
+        die!("[ERROR] the only supported bit depth is 8 ({})", path);
+    }
+    image
+}
+
+/// Reads the image at `path` as a grayscale mask matching `img` in size
+fn read_mask(path: &str, img: &Image) -> Vec<u8> {
+    let mask =
+        read_input(path).unwrap_or_else(|e| die!("[ERROR] failed to read mask {} ({})", path, e));
+    if mask.info.depth != png::BitDepth::Eight {
+        die!("[ERROR] the only supported bit depth is 8 ({})", path);
+    }
+    if (mask.info.width, mask.info.height) != (img.info.width, img.info.height) {
+        die!(
+            "[ERROR] mask image is {}x{} but the input is {}x{}",
+            mask.info.width,
+            mask.info.height,
+            img.info.width,
+            img.info.height
+        );
+    }
+    ensure_grayscale(mask).bytes
+}
+
+/// Writes `img` to the path parameter `name` if it is set
+fn write_param_image(params: &Params, name: &str, img: &Image) {
+    if let Some(path) = params.str_opt(name) {
+        write_output(path, &img.info, &img.bytes)
+            .unwrap_or_else(|e| die!("[ERROR] failed to write {} image ({})", name, e));
+        println!("[INFO] wrote {} {:?}", name, img.info);
+    }
 }
 
 fn write_output<P, B>(output: P, info: &Info, buf: B) -> Result<()>
@@ -233,35 +334,141 @@ where
     Ok(())
 }
 
-fn read_args() -> Args {
-    let mut args = std::env::args();
+fn read_command() -> Command {
+    let mut args = std::env::args().peekable();
     let my_name = args
         .next()
         .unwrap_or_else(|| die!("[ERROR] args[0] is missing"));
     let args_info = || {
-        die!("{} [input] [output] [func number]", my_name);
+        die!(
+            "{0} [input] [output] [func name or number] [name=value]... [--integer-math] [--fast] [--knock] [--mask=path]\n\
+             {0} compare [image] [reference] [name=value]...\n\
+             {0} rank [directory] [name=value]...\n\
+             {0} stack [output] [frame]... [name=value]...\n\
+             {0} batch [input] [directory] [func name or number]... [name=value]... [--integer-math] [--fast] [--knock] [--mask=path]\n\
+             {0} sweep [input] [directory] [func name or number] [name=start..end[:step] or name=a,b,c] [name=value]... [--integer-math] [--fast] [--knock] [--mask=path]\n\
+             {0} completions [bash|zsh|fish]\n\
+             {0} man",
+            my_name
+        );
     };
 
+    match args.peek().map(String::as_str) {
+        Some("completions") => {
+            args.next();
+            let shell = args.next().unwrap_or_else(args_info);
+            let shell = shell
+                .parse()
+                .unwrap_or_else(|e| die!("[ERROR] failed to parse shell ({})", e));
+            return Command::Completions(shell);
+        }
+        Some("man") => return Command::Man,
+        // `verify` reads better when checking against a reference answer
+        Some("compare" | "verify") => {
+            args.next();
+            let image = args.next().unwrap_or_else(args_info);
+            let reference = args.next().unwrap_or_else(args_info);
+            return Command::Compare {
+                image,
+                reference,
+                params: args.collect(),
+            };
+        }
+        Some("rank") => {
+            args.next();
+            let dir = args.next().unwrap_or_else(args_info);
+            return Command::Rank {
+                dir,
+                params: args.collect(),
+            };
+        }
+        Some("stack") => {
+            args.next();
+            let output = args.next().unwrap_or_else(args_info);
+            let (params, frames) = args.partition(|arg| arg.contains('='));
+            return Command::Stack {
+                output,
+                frames,
+                params,
+            };
+        }
+        Some("batch") => {
+            args.next();
+            let input = args.next().unwrap_or_else(args_info);
+            let dir = args.next().unwrap_or_else(args_info);
+            let (flag_args, rest): (Vec<String>, Vec<String>) =
+                args.partition(|arg| arg.starts_with("--"));
+            let (params, ops) = rest.into_iter().partition(|arg| arg.contains('='));
+            let (flags, mask) = parse_flags(flag_args);
+            return Command::Batch {
+                input,
+                dir,
+                ops,
+                params,
+                flags,
+                mask,
+            };
+        }
+        Some("sweep") => {
+            args.next();
+            let input = args.next().unwrap_or_else(args_info);
+            let dir = args.next().unwrap_or_else(args_info);
+            let op = args.next().unwrap_or_else(args_info);
+            let (flag_args, params): (Vec<String>, Vec<String>) =
+                args.partition(|arg| arg.starts_with("--"));
+            let (flags, mask) = parse_flags(flag_args);
+            return Command::Sweep {
+                input,
+                dir,
+                op,
+                params,
+                flags,
+                mask,
+            };
+        }
+        _ => {}
+    }
+
+    let (flag_args, rest): (Vec<String>, Vec<String>) = args.partition(|arg| arg.starts_with("--"));
+    let (flags, mask) = parse_flags(flag_args);
+
+    let mut args = rest.into_iter();
     let input = args.next().unwrap_or_else(args_info);
     let output = args.next().unwrap_or_else(args_info);
-    let num = args
-        .next()
-        .unwrap_or_else(args_info)
-        .parse()
-        .unwrap_or_else(|e| die!("[ERROR] failed to parse num ({})", e));
-    Args { input, output, num }
+    let op = args.next().unwrap_or_else(args_info);
+    let params = args.collect();
+    Command::Run(Args {
+        input,
+        output,
+        op,
+        params,
+        flags,
+        mask,
+    })
+}
+
+// the switches and the path given with --mask=
+fn parse_flags(flag_args: Vec<String>) -> (Flags, Option<String>) {
+    let mut flags = Flags::default();
+    let mut mask = None;
+    for flag in flag_args {
+        match flag.as_str() {
+            "--integer-math" => flags.integer_math = true,
+            "--fast" => flags.fast = true,
+            "--knock" => flags.knock = true,
+            _ => match flag.strip_prefix("--mask=") {
+                Some(path) if !path.is_empty() => mask = Some(path.to_owned()),
+                _ => die!("[ERROR] unknown flag {}", flag),
+            },
+        }
+    }
+    (flags, mask)
 }
 
 fn to_grayscale(img: Image) -> Image {
     assert_eq!(img.info.color, png::ColorType::Rgb);
-    assert!(img.bytes.len() % 3 == 0);
-    let out = img.bytes
-        .chunks(3)
-        .filter_map(|chunk| match chunk {
-            [r, g, b] => Some((0.2126 * *r as f64 + 0.7152 * *g as f64 + 0.0722 * *b as f64) as u8),
-            _ => None,
-        })
-        .collect();
+    let mut out = vec![0; img.bytes.len() / 3];
+    color::rgb_to_gray(&img.bytes, &mut out);
     let info_mod = Info {
         color: png::ColorType::Grayscale,
         ..img.info
@@ -269,29 +476,48 @@ fn to_grayscale(img: Image) -> Image {
     Image {
         info: info_mod,
         bytes: out,
+        mask: None,
     }
 }
 
-fn binarize(img: Image, threshold: u8) -> Image {
-    assert_eq!(img.info.color, png::ColorType::Grayscale);
-    let out = img.bytes
-        .into_iter()
-        .map(|value| if value < threshold { 0 } else { 255 })
-        .collect();
-    Image { info: img.info, bytes: out }
+// replicates the gray level of a grayscale image into all three channels
+fn to_rgb(img: Image) -> Image {
+    match img.info.color {
+        png::ColorType::Rgb => img,
+        png::ColorType::Grayscale => Image {
+            bytes: img.bytes.iter().flat_map(|&v| [v; 3]).collect(),
+            info: Info {
+                color: png::ColorType::Rgb,
+                ..img.info
+            },
+            mask: None,
+        },
+        other => die!(
+            "[ERROR] expected an RGB or grayscale image, got {:?}",
+            other
+        ),
+    }
 }
 
-fn rgb_to_hsv(rgb_bytes: Vec<u8>) -> Vec<HSV> {
-    assert!(rgb_bytes.len() % 3 == 0);
-    rgb_bytes
-        .chunks(3)
-        .map(|chunk| match chunk {
-            [r, g, b] => HSV::from_rgb(*r, *g, *b),
-            _ => unreachable!(),
-        })
-        .collect()
+// passes grayscale images through and converts RGB ones
+fn ensure_grayscale(img: Image) -> Image {
+    match img.info.color {
+        png::ColorType::Grayscale => img,
+        png::ColorType::Rgb => to_grayscale(img),
+        other => die!(
+            "[ERROR] expected an RGB or grayscale image, got {:?}",
+            other
+        ),
+    }
 }
 
-fn hsv_to_rgb(hsvs: Vec<HSV>) -> Vec<u8> {
-    hsvs.into_iter().map(HSV::into_rgb).flatten().collect()
+fn binarize(img: Image, threshold: u8) -> Image {
+    assert_eq!(img.info.color, png::ColorType::Grayscale);
+    let mut bytes = img.bytes;
+    point::binarize(&mut bytes, threshold);
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
 }
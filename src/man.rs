@@ -0,0 +1,149 @@
+use std::fmt::Write;
+
+use crate::batch;
+use crate::compare;
+use crate::focus;
+use crate::ops::OPS;
+use crate::params::{Constraint, ParamSpec, FLAGS};
+use crate::stack;
+use crate::sweep;
+
+const BIN: &str = env!("CARGO_BIN_NAME");
+
+// roff treats a leading `.` or `'` as a request and `\` as an escape
+fn escape(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+pub fn generate() -> String {
+    let mut out = String::new();
+    let bin = escape(BIN);
+    writeln!(out, ".TH {} 1", escape(&BIN.to_uppercase())).unwrap();
+    writeln!(out, ".SH NAME").unwrap();
+    writeln!(out, "{} \\- {}", bin, escape(env!("CARGO_PKG_DESCRIPTION"))).unwrap();
+    writeln!(out, ".SH SYNOPSIS").unwrap();
+    writeln!(
+        out,
+        ".B {}\n.I input output operation\n.RI [ name = value ]...\n.RI [ flags ]\n.br",
+        bin
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".B {}\n.RB ( compare | verify )\n.I image reference\n.RI [ name = value ]...\n.br",
+        bin
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".B {}\n.B rank\n.I directory\n.RI [ name = value ]...\n.br",
+        bin
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".B {}\n.B stack\n.I output frame\n.IR frame ...\n.RI [ name = value ]...\n.br",
+        bin
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".B {}\n.B batch\n.I input directory\n.RI [ operation ]...\n.RI [ name = value ]...\n.RI [ flags ]\n.br",
+        bin
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".B {}\n.B sweep\n.I input directory operation\n.IR name = range\n.RI [ name = value ]...\n.RI [ flags ]\n.br",
+        bin
+    )
+    .unwrap();
+    writeln!(
+        out,
+        ".B {}\n.B completions\n.RB ( bash | zsh | fish )\n.br",
+        bin
+    )
+    .unwrap();
+    writeln!(out, ".B {}\n.B man", bin).unwrap();
+    writeln!(out, ".SH DESCRIPTION").unwrap();
+    writeln!(
+        out,
+        "Reads the PNG image \\fIinput\\fR, applies \\fIoperation\\fR and writes the result to \\fIoutput\\fR.\n\
+         An operation is selected by its name, one of its aliases or its knock number.\n\
+         Options of the operation are given as \\fIname\\fR=\\fIvalue\\fR pairs.\n\
+         .PP\n\
+         \\fBcompare\\fR (or \\fBverify\\fR) checks \\fIimage\\fR against \\fIreference\\fR tile by tile and fails when a tile differs by more than the tolerance.\n\
+         \\fBrank\\fR scores the sharpness of every PNG in \\fIdirectory\\fR and lists them sharpest first.\n\
+         \\fBstack\\fR combines equally sized frames sample by sample, optionally aligning them first, and writes the result to \\fIoutput\\fR.\n\
+         \\fBbatch\\fR runs each \\fIoperation\\fR, or every knock when none is given, on \\fIinput\\fR and writes the outputs to \\fIdirectory\\fR, optionally with an HTML or Markdown gallery of the results.\n\
+         \\fBsweep\\fR runs \\fIoperation\\fR on \\fIinput\\fR once per value of one parameter, given as \\fIname\\fR=\\fIstart\\fR..\\fIend\\fR[:\\fIstep\\fR] or, for a choice, \\fIname\\fR=\\fIa\\fR,\\fIb\\fR,..., and writes one output per value to \\fIdirectory\\fR, optionally with a labeled montage.\n\
+         \\fBcompletions\\fR prints a completion script for the given shell and \\fBman\\fR prints this page."
+    )
+    .unwrap();
+    writeln!(out, ".SH FLAGS").unwrap();
+    for (flag, help) in FLAGS {
+        writeln!(out, ".TP\n.B {}\n{}", escape(flag), escape(help)).unwrap();
+    }
+    writeln!(out, ".SH COMPARE").unwrap();
+    writeln!(out, "Parameters of \\fBcompare\\fR:").unwrap();
+    params(&mut out, compare::PARAMS);
+    writeln!(out, ".SH RANK").unwrap();
+    writeln!(out, "Parameters of \\fBrank\\fR:").unwrap();
+    params(&mut out, focus::RANK_PARAMS);
+    writeln!(out, ".SH STACK").unwrap();
+    writeln!(out, "Parameters of \\fBstack\\fR:").unwrap();
+    params(&mut out, stack::PARAMS);
+    writeln!(out, ".SH BATCH").unwrap();
+    writeln!(
+        out,
+        "Parameters of \\fBbatch\\fR; any other \\fIname\\fR=\\fIvalue\\fR is passed to the operations that take it:"
+    )
+    .unwrap();
+    params(&mut out, batch::PARAMS);
+    writeln!(out, ".SH SWEEP").unwrap();
+    writeln!(
+        out,
+        "Parameters of \\fBsweep\\fR; any other \\fIname\\fR=\\fIvalue\\fR is passed to the operation unchanged:"
+    )
+    .unwrap();
+    params(&mut out, sweep::PARAMS);
+    writeln!(out, ".SH OPERATIONS").unwrap();
+    for op in OPS {
+        writeln!(out, ".TP\n.B {}", escape(op.name)).unwrap();
+        writeln!(out, "{}", escape(op.help)).unwrap();
+        if !op.aliases.is_empty() {
+            writeln!(out, ".br\nAliases: {}", escape(&op.aliases.join(", "))).unwrap();
+        }
+        params(&mut out, op.params);
+    }
+    out
+}
+
+// one indented entry per parameter
+fn params(out: &mut String, specs: &[ParamSpec]) {
+    if specs.is_empty() {
+        return;
+    }
+    writeln!(out, ".RS").unwrap();
+    for spec in specs {
+        let required = spec
+            .constraints
+            .iter()
+            .any(|c| matches!(c, Constraint::Required));
+        let mut desc = match spec.default {
+            Some(default) => format!("{}, default {}", spec.kind, default),
+            None if required => spec.kind.to_string(),
+            None => format!("{}, optional", spec.kind),
+        };
+        for constraint in spec.constraints {
+            write!(desc, ", {}", constraint).unwrap();
+        }
+        writeln!(out, ".TP\n.B {}\n{}", escape(spec.name), escape(&desc)).unwrap();
+    }
+    writeln!(out, ".RE").unwrap();
+}
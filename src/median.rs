@@ -0,0 +1,95 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Window side from which `median_filter` switches from sorting to the sliding histogram
+pub const HISTOGRAM_MIN_SIZE: usize = 5;
+
+/// Median of the (2 * `radius` + 1)^2 window around each pixel of a plane, pixels
+/// outside the image counting as zero as in the knocks. Small windows are sorted,
+/// larger ones use `median_histogram`; both give the same result.
+pub fn median_filter(src: &[u8], dst: &mut [u8], width: usize, height: usize, radius: usize) {
+    if 2 * radius + 1 >= HISTOGRAM_MIN_SIZE {
+        median_histogram(src, dst, width, height, radius);
+    } else {
+        median_sort(src, dst, width, height, radius);
+    }
+}
+
+/// Sorts every window, O(r^2 log r) per pixel
+pub fn median_sort(src: &[u8], dst: &mut [u8], width: usize, height: usize, radius: usize) {
+    assert_eq!(src.len(), width * height);
+    assert_eq!(dst.len(), src.len());
+    let side = 2 * radius + 1;
+    let mut window: Vec<u8> = Vec::with_capacity(side * side);
+    for y in 0..height {
+        for x in 0..width {
+            window.clear();
+            for wy in y as isize - radius as isize..=(y + radius) as isize {
+                for wx in x as isize - radius as isize..=(x + radius) as isize {
+                    let inside =
+                        (0..width as isize).contains(&wx) && (0..height as isize).contains(&wy);
+                    window.push(if inside {
+                        src[wy as usize * width + wx as usize]
+                    } else {
+                        0
+                    });
+                }
+            }
+            window.sort_unstable();
+            dst[y * width + x] = window[window.len() / 2];
+        }
+    }
+}
+
+/// Huang's sliding histogram: along each row the column leaving the window is
+/// taken out of a 256-bin histogram and the one entering added, and the median
+/// moves from its previous value, O(r) per pixel
+pub fn median_histogram(src: &[u8], dst: &mut [u8], width: usize, height: usize, radius: usize) {
+    assert_eq!(src.len(), width * height);
+    assert_eq!(dst.len(), src.len());
+    let side = 2 * radius + 1;
+    let half = side * side / 2;
+    let at = |x: isize, y: isize| {
+        if (0..width as isize).contains(&x) && (0..height as isize).contains(&y) {
+            src[y as usize * width + x as usize]
+        } else {
+            0
+        }
+    };
+    let r = radius as isize;
+    let mut hist = vec![0usize; 256];
+    for y in 0..height as isize {
+        hist.iter_mut().for_each(|h| *h = 0);
+        for wy in y - r..=y + r {
+            for wx in -r..=r {
+                hist[at(wx, wy) as usize] += 1;
+            }
+        }
+        // `below` counts the window values less than `median`
+        let (mut median, mut below) = (0usize, 0usize);
+        for x in 0..width as isize {
+            if x > 0 {
+                for wy in y - r..=y + r {
+                    let (old, new) = (at(x - r - 1, wy) as usize, at(x + r, wy) as usize);
+                    hist[old] -= 1;
+                    hist[new] += 1;
+                    if old < median {
+                        below -= 1;
+                    }
+                    if new < median {
+                        below += 1;
+                    }
+                }
+            }
+            while below > half {
+                median -= 1;
+                below -= hist[median];
+            }
+            while below + hist[median] <= half {
+                below += hist[median];
+                median += 1;
+            }
+            dst[y as usize * width + x as usize] = median as u8;
+        }
+    }
+}
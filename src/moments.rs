@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+use crate::label::{self, label, CONNECTIVITY};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::threshold::{histogram, otsu_threshold};
+use crate::warp::per_channel;
+use crate::{ensure_grayscale, report, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // Otsu's when not given
+    ParamSpec {
+        name: "threshold",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    // which side of the threshold the objects are on
+    ParamSpec {
+        name: "foreground",
+        kind: Kind::Choice(&["bright", "dark"]),
+        default: Some("bright"),
+        constraints: &[],
+    },
+    // direction the major axis ends up in
+    ParamSpec {
+        name: "axis",
+        kind: Kind::Choice(&["horizontal", "vertical"]),
+        default: Some("horizontal"),
+        constraints: &[],
+    },
+    CONNECTIVITY,
+    report::PARAM,
+];
+
+/// Area, centroid and central second-order moments of the pixels `i` in `region`
+pub struct Moments {
+    pub area: f64,
+    pub cx: f64,
+    pub cy: f64,
+    pub mu20: f64,
+    pub mu02: f64,
+    pub mu11: f64,
+}
+
+impl Moments {
+    pub fn new(region: impl Iterator<Item = usize> + Clone, width: usize) -> Self {
+        let (mut area, mut sx, mut sy) = (0., 0., 0.);
+        for i in region.clone() {
+            area += 1.;
+            sx += (i % width) as f64;
+            sy += (i / width) as f64;
+        }
+        let (cx, cy) = (sx / area, sy / area);
+        let (mut mu20, mut mu02, mut mu11) = (0., 0., 0.);
+        for i in region {
+            let (dx, dy) = ((i % width) as f64 - cx, (i / width) as f64 - cy);
+            mu20 += dx * dx;
+            mu02 += dy * dy;
+            mu11 += dx * dy;
+        }
+        Self {
+            area,
+            cx,
+            cy,
+            mu20,
+            mu02,
+            mu11,
+        }
+    }
+
+    /// Angle of the major axis in radians, measured from +x towards +y (down)
+    pub fn orientation(&self) -> f64 {
+        0.5 * (2. * self.mu11).atan2(self.mu20 - self.mu02)
+    }
+
+    /// Ratio of the axis lengths, 1 for a disc
+    pub fn elongation(&self) -> f64 {
+        let (mean, diff) = (
+            (self.mu20 + self.mu02) / 2.,
+            (self.mu20 - self.mu02).hypot(2. * self.mu11) / 2.,
+        );
+        ((mean + diff) / (mean - diff).max(f64::MIN_POSITIVE)).sqrt()
+    }
+}
+
+/// Rotates the image about the centroid of the largest foreground component so
+/// that the major axis of its second-order moments lies along x (or y)
+pub fn normalize_orientation(img: Image, params: &Params) -> Image {
+    let gray = ensure_grayscale(img.clone());
+    let (width, height) = (gray.info.width as usize, gray.info.height as usize);
+    let threshold = match params.int_opt("threshold") {
+        Some(t) => t as u8,
+        None => otsu_threshold(&histogram(&gray.bytes, None)).unwrap_or_else(|| {
+            die!("[ERROR] failed to find a threshold, the image has fewer than two gray levels")
+        }),
+    };
+    let bright = params.str("foreground") == "bright";
+    let mask: Vec<bool> = gray
+        .bytes
+        .iter()
+        .map(|&v| (v >= threshold) == bright)
+        .collect();
+    let labels = label(&mask, width, height, label::neighbors(params));
+    let Some(largest) = (0..labels.areas.len()).max_by_key(|&l| labels.areas[l]) else {
+        die!("[ERROR] no foreground at threshold {}", threshold);
+    };
+    let region = (0..mask.len()).filter(|&i| labels.labels[i] == largest as u32 + 1);
+    let moments = Moments::new(region, width);
+
+    let mut theta = moments.orientation();
+    if params.str("axis") == "vertical" {
+        theta -= std::f64::consts::FRAC_PI_2;
+    }
+    // the output takes the input at the centroid plus the offset rotated by theta
+    let (c, s) = (theta.cos(), theta.sin());
+    let (cx, cy) = (moments.cx, moments.cy);
+    let rotate = move |x: f64, y: f64| {
+        let (dx, dy) = (x - cx, y - cy);
+        (cx + c * dx - s * dy, cy + s * dx + c * dy)
+    };
+
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"threshold\": {},\n  \"area\": {},\n  \"centroid\": [{:.2}, {:.2}],\n  \"orientation\": {:.3},\n  \"elongation\": {:.3},\n  \"rotation\": {:.3}\n}}",
+        threshold,
+        moments.area,
+        cx,
+        cy,
+        moments.orientation().to_degrees(),
+        moments.elongation(),
+        -theta.to_degrees()
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    let maps: Vec<&dyn Fn(f64, f64) -> (f64, f64)> = vec![&rotate; img.info.color.samples()];
+    per_channel(&img, &maps)
+}
@@ -0,0 +1,302 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "shape",
+        kind: Kind::Choice(&["cross", "rect", "ellipse"]),
+        default: Some("cross"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "width",
+        kind: Kind::Int,
+        default: Some("3"),
+        constraints: &[Constraint::Range(1., 99.)],
+    },
+    ParamSpec {
+        name: "height",
+        kind: Kind::Int,
+        default: Some("3"),
+        constraints: &[Constraint::Range(1., 99.)],
+    },
+    // text grid overriding shape, width and height
+    ParamSpec {
+        name: "kernel",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // the center of the kernel when unset
+    ParamSpec {
+        name: "anchor_x",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., 98.)],
+    },
+    ParamSpec {
+        name: "anchor_y",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., 98.)],
+    },
+    ParamSpec {
+        name: "iterations",
+        kind: Kind::Int,
+        default: Some("1"),
+        constraints: &[Constraint::Range(1., 100.)],
+    },
+];
+
+#[derive(Clone, Copy, Debug)]
+pub enum Step {
+    Erode,
+    Dilate,
+}
+
+pub struct StructuringElement {
+    width: usize,
+    height: usize,
+    anchor: (usize, usize),
+    mask: Vec<bool>,
+}
+
+impl StructuringElement {
+    fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> bool) -> Self {
+        let mask = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+        Self {
+            width,
+            height,
+            anchor: (width / 2, height / 2),
+            mask,
+        }
+    }
+
+    pub fn rect(width: usize, height: usize) -> Self {
+        Self::from_fn(width, height, |_, _| true)
+    }
+
+    pub fn cross(width: usize, height: usize) -> Self {
+        Self::from_fn(width, height, |x, y| x == width / 2 || y == height / 2)
+    }
+
+    // cells whose centers lie in the ellipse inscribed in the box
+    pub fn ellipse(width: usize, height: usize) -> Self {
+        let (rx, ry) = (width as f64 / 2., height as f64 / 2.);
+        Self::from_fn(width, height, |x, y| {
+            let dx = (x as f64 + 0.5 - rx) / rx;
+            let dy = (y as f64 + 0.5 - ry) / ry;
+            dx * dx + dy * dy <= 1.
+        })
+    }
+
+    /// Reads a grid of `1`/`#` (set) and `0`/`.` (unset) cells, one row per line
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let rows: Vec<&str> = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let width = rows.first().map_or(0, |row| row.chars().count());
+        if width == 0 {
+            bail!("kernel is empty");
+        }
+
+        let mut mask = Vec::with_capacity(width * rows.len());
+        for (y, row) in rows.iter().enumerate() {
+            if row.chars().count() != width {
+                bail!(
+                    "row {} has {} cells, expected {}",
+                    y + 1,
+                    row.chars().count(),
+                    width
+                );
+            }
+            for c in row.chars() {
+                mask.push(match c {
+                    '1' | '#' => true,
+                    '0' | '.' => false,
+                    _ => bail!("unexpected character `{}` in row {}", c, y + 1),
+                });
+            }
+        }
+        if !mask.contains(&true) {
+            bail!("kernel has no set cell");
+        }
+
+        let height = rows.len();
+        Ok(Self {
+            width,
+            height,
+            anchor: (width / 2, height / 2),
+            mask,
+        })
+    }
+
+    pub fn from_params(params: &Params) -> Self {
+        let mut se = match params.str_opt("kernel") {
+            Some(path) => Self::load(path)
+                .unwrap_or_else(|e| die!("[ERROR] failed to read kernel {} ({})", path, e)),
+            None => {
+                let width = params.int("width") as usize;
+                let height = params.int("height") as usize;
+                match params.str("shape") {
+                    "cross" => Self::cross(width, height),
+                    "rect" => Self::rect(width, height),
+                    "ellipse" => Self::ellipse(width, height),
+                    _ => unreachable!(),
+                }
+            }
+        };
+
+        if let Some(x) = params.int_opt("anchor_x") {
+            if x as usize >= se.width {
+                die!(
+                    "[ERROR] parameter `anchor_x` must be less than the kernel width {}, got {}",
+                    se.width,
+                    x
+                );
+            }
+            se.anchor.0 = x as usize;
+        }
+        if let Some(y) = params.int_opt("anchor_y") {
+            if y as usize >= se.height {
+                die!(
+                    "[ERROR] parameter `anchor_y` must be less than the kernel height {}, got {}",
+                    se.height,
+                    y
+                );
+            }
+            se.anchor.1 = y as usize;
+        }
+        se
+    }
+
+    // positions of the set cells relative to the anchor
+    fn offsets(&self) -> Vec<(isize, isize)> {
+        let (ax, ay) = (self.anchor.0 as isize, self.anchor.1 as isize);
+        (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .filter(|&(x, y)| self.mask[y * self.width + x])
+            .map(|(x, y)| (x as isize - ax, y as isize - ay))
+            .collect()
+    }
+}
+
+/// Applies `steps` to a single channel image, neighbors outside the image are ignored
+pub fn morph(
+    bytes: &[u8],
+    width: usize,
+    height: usize,
+    se: &StructuringElement,
+    steps: &[Step],
+) -> Vec<u8> {
+    let offsets = se.offsets();
+    let mut cur = bytes.to_vec();
+    for step in steps {
+        cur = match step {
+            // minimum over the kernel placed at the pixel
+            Step::Erode => neighborhood(&cur, width, height, &offsets, 255, u8::min),
+            // maximum over the kernel reflected about its anchor
+            Step::Dilate => {
+                let reflected: Vec<_> = offsets.iter().map(|&(dx, dy)| (-dx, -dy)).collect();
+                neighborhood(&cur, width, height, &reflected, 0, u8::max)
+            }
+        };
+    }
+    cur
+}
+
+fn neighborhood(
+    bytes: &[u8],
+    width: usize,
+    height: usize,
+    offsets: &[(isize, isize)],
+    init: u8,
+    fold: fn(u8, u8) -> u8,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(bytes.len());
+    for y in 0..height as isize {
+        for x in 0..width as isize {
+            let value = offsets
+                .iter()
+                .map(|&(dx, dy)| (x + dx, y + dy))
+                .filter(|&(nx, ny)| {
+                    (0..width as isize).contains(&nx) && (0..height as isize).contains(&ny)
+                })
+                .map(|(nx, ny)| bytes[ny as usize * width + nx as usize])
+                .fold(init, fold);
+            out.push(value);
+        }
+    }
+    out
+}
+
+fn apply(img: &Image, params: &Params, steps: &[Step]) -> Vec<u8> {
+    let se = StructuringElement::from_params(params);
+    let iterations = params.int("iterations") as usize;
+    let steps: Vec<Step> = steps
+        .iter()
+        .flat_map(|&step| std::iter::repeat_n(step, iterations))
+        .collect();
+    morph(
+        &img.bytes,
+        img.info.width as usize,
+        img.info.height as usize,
+        &se,
+        &steps,
+    )
+}
+
+/// Runs `steps`, each repeated `iterations` times, on the grayscale intensities
+pub fn run(img: Image, params: &Params, steps: &[Step]) -> Image {
+    let img = ensure_grayscale(img);
+    let bytes = apply(&img, params, steps);
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// Bright details smaller than the element: the image minus its opening
+pub fn top_hat(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let opened = apply(&img, params, &[Step::Erode, Step::Dilate]);
+    let bytes = img
+        .bytes
+        .iter()
+        .zip(opened)
+        .map(|(v, o)| v.saturating_sub(o))
+        .collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// Dark details smaller than the element: the closing minus the image
+pub fn black_hat(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let closed = apply(&img, params, &[Step::Dilate, Step::Erode]);
+    let bytes = img
+        .bytes
+        .iter()
+        .zip(closed)
+        .map(|(v, c)| c.saturating_sub(*v))
+        .collect();
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
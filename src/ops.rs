@@ -0,0 +1,701 @@
+use gasyori100knock_rs::color;
+
+use crate::annotate;
+use crate::arith;
+use crate::background;
+use crate::bitwise;
+use crate::blend;
+use crate::colorize;
+use crate::convert;
+use crate::corner;
+use crate::defect;
+use crate::dither;
+use crate::dog;
+use crate::filter;
+use crate::finder;
+use crate::flow;
+use crate::focus;
+use crate::glyph;
+use crate::gradient;
+use crate::granulometry;
+use crate::halftone;
+use crate::histogram;
+use crate::inspect;
+use crate::label;
+use crate::moments;
+use crate::morphology::{self, Step};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::pool;
+use crate::quilt;
+use crate::radon;
+use crate::ransac;
+use crate::saliency;
+use crate::skeleton;
+use crate::stats;
+use crate::stipple;
+use crate::template;
+use crate::tensor;
+use crate::threshold;
+use crate::transfer;
+use crate::warp;
+use crate::{to_grayscale, Image};
+
+pub struct Op {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub help: &'static str,
+    pub params: &'static [ParamSpec],
+    pub func: fn(Image, &Params) -> Image,
+}
+
+pub static OPS: &[Op] = &[
+    Op {
+        name: "identity",
+        aliases: &["q0"],
+        help: "Copy the input unchanged",
+        params: &[],
+        func: |img, _| img,
+    },
+    Op {
+        name: "q1",
+        aliases: &["rgb2bgr", "bgr"],
+        help: "Swap the red and blue channels",
+        params: &[],
+        func: |img, _| {
+            // rgb -> bgr
+            assert_eq!(img.info.color, png::ColorType::Rgb);
+            let mut bytes = img.bytes;
+            color::swap_red_blue(&mut bytes);
+            Image {
+                info: img.info,
+                bytes,
+                mask: None,
+            }
+        },
+    },
+    Op {
+        name: "q2",
+        aliases: &["grayscale", "gray"],
+        help: "Convert to grayscale",
+        params: &[],
+        func: |img, _| to_grayscale(img),
+    },
+    Op {
+        name: "q3",
+        aliases: &["binarize", "threshold"],
+        help: "Binarize with a fixed or automatically selected threshold",
+        params: threshold::BINARIZE_PARAMS,
+        func: threshold::binarize_op,
+    },
+    Op {
+        name: "q4",
+        aliases: &["otsu", "binarize-auto"],
+        help: "Binarize with a threshold chosen by Otsu's method",
+        params: threshold::OTSU_PARAMS,
+        func: threshold::otsu,
+    },
+    Op {
+        name: "q5",
+        aliases: &["hue-rotate", "hsv"],
+        help: "Rotate the hue in HSV space",
+        params: &[ParamSpec {
+            name: "shift",
+            kind: Kind::Float,
+            default: Some("180"),
+            constraints: &[Constraint::Range(0., 360.)],
+        }],
+        func: |img, params| {
+            // rotate H in HSV (180 inverts it)
+            let shift = params.float("shift");
+            let mut hsv_bytes = color::rgb_to_hsv(&img.bytes);
+            for hsv in &mut hsv_bytes {
+                hsv.h = (hsv.h + shift) % 360.;
+            }
+            let bytes = color::hsv_to_rgb(&hsv_bytes);
+            Image {
+                info: img.info,
+                bytes,
+                mask: None,
+            }
+        },
+    },
+    Op {
+        name: "q7",
+        aliases: &["average-pooling", "avg-pool"],
+        help: "Replace each block with its average",
+        params: pool::BLOCK_PARAMS,
+        func: pool::average_block,
+    },
+    Op {
+        name: "q8",
+        aliases: &["max-pooling", "max-pool"],
+        help: "Replace each block with its maximum",
+        params: pool::BLOCK_PARAMS,
+        func: pool::max_block,
+    },
+    Op {
+        name: "q9",
+        aliases: &["gaussian", "gaussian-blur", "blur"],
+        help: "Gaussian filter (zero padding outside the image)",
+        params: filter::GAUSSIAN_PARAMS,
+        func: filter::gaussian,
+    },
+    Op {
+        name: "q10",
+        aliases: &["median", "median-filter"],
+        help: "Median filter (zero padding outside the image)",
+        params: filter::MEDIAN_PARAMS,
+        func: filter::median,
+    },
+    Op {
+        name: "q11",
+        aliases: &["mean", "box", "smoothing"],
+        help: "Mean (box) filter",
+        params: filter::BOX_PARAMS,
+        func: filter::mean,
+    },
+    Op {
+        name: "q15",
+        aliases: &["sobel"],
+        help: "Sobel filter on the grayscale image, clipped to [0, 255]",
+        params: filter::SOBEL_PARAMS,
+        func: filter::sobel,
+    },
+    Op {
+        name: "q23",
+        aliases: &["equalize", "histogram-equalization"],
+        help: "Histogram equalization",
+        params: histogram::EQUALIZE_PARAMS,
+        func: histogram::equalize,
+    },
+    Op {
+        name: "q24",
+        aliases: &["gamma", "gamma-correction"],
+        help: "Power-law gamma encoding or decoding",
+        params: transfer::GAMMA_PARAMS,
+        func: transfer::gamma,
+    },
+    Op {
+        name: "q54",
+        aliases: &["template-matching", "match"],
+        help: "Template matching by SSD, the best match outlined in red",
+        params: template::PARAMS,
+        func: template::matching,
+    },
+    Op {
+        name: "q58",
+        aliases: &["labeling", "labeling-4"],
+        help: "Color the 4-connected components of a binary mask",
+        params: &[],
+        func: label::labeling_4,
+    },
+    Op {
+        name: "q59",
+        aliases: &["labeling-8"],
+        help: "Color the 8-connected components of a binary mask",
+        params: &[],
+        func: label::labeling_8,
+    },
+    Op {
+        name: "q65",
+        aliases: &["zhang-suen", "thinning"],
+        help: "Zhang-Suen thinning of a binary mask",
+        params: &[],
+        func: skeleton::zhang_suen,
+    },
+    Op {
+        name: "q76",
+        aliases: &["saliency"],
+        help: "Saliency map from differences of a Gaussian pyramid",
+        params: &[],
+        func: saliency::saliency,
+    },
+    Op {
+        name: "q83",
+        aliases: &["harris", "corners"],
+        help: "Harris corner detection with subpixel refinement",
+        params: corner::HARRIS_PARAMS,
+        func: corner::harris,
+    },
+    Op {
+        name: "dilate",
+        aliases: &["dilation"],
+        help: "Dilate (local maximum) with a structuring element",
+        params: morphology::PARAMS,
+        func: |img, params| morphology::run(img, params, &[Step::Dilate]),
+    },
+    Op {
+        name: "erode",
+        aliases: &["erosion"],
+        help: "Erode (local minimum) with a structuring element",
+        params: morphology::PARAMS,
+        func: |img, params| morphology::run(img, params, &[Step::Erode]),
+    },
+    Op {
+        name: "open",
+        aliases: &["opening"],
+        help: "Erode then dilate",
+        params: morphology::PARAMS,
+        func: |img, params| morphology::run(img, params, &[Step::Erode, Step::Dilate]),
+    },
+    Op {
+        name: "close",
+        aliases: &["closing"],
+        help: "Dilate then erode",
+        params: morphology::PARAMS,
+        func: |img, params| morphology::run(img, params, &[Step::Dilate, Step::Erode]),
+    },
+    Op {
+        name: "tophat",
+        aliases: &["top-hat", "white-tophat"],
+        help: "Subtract the opening from the image",
+        params: morphology::PARAMS,
+        func: morphology::top_hat,
+    },
+    Op {
+        name: "blackhat",
+        aliases: &["black-hat", "black-tophat"],
+        help: "Subtract the image from its closing",
+        params: morphology::PARAMS,
+        func: morphology::black_hat,
+    },
+    Op {
+        name: "granulometry",
+        aliases: &["pattern-spectrum"],
+        help: "Report the size distribution under openings of growing radius and plot it",
+        params: granulometry::PARAMS,
+        func: granulometry::run,
+    },
+    Op {
+        name: "add",
+        aliases: &[],
+        help: "Add another image, saturating at 255",
+        params: arith::PARAMS,
+        func: arith::add,
+    },
+    Op {
+        name: "subtract",
+        aliases: &["sub", "difference"],
+        help: "Subtract another image (saturating, with an offset or absolute)",
+        params: arith::SUBTRACT_PARAMS,
+        func: arith::subtract,
+    },
+    Op {
+        name: "multiply",
+        aliases: &["mul"],
+        help: "Multiply by another image as [0, 1] intensities",
+        params: arith::MULTIPLY_PARAMS,
+        func: arith::multiply,
+    },
+    Op {
+        name: "divide",
+        aliases: &["div", "flat-field"],
+        help: "Divide by another image and rescale (flat-field correction)",
+        params: arith::DIVIDE_PARAMS,
+        func: arith::divide,
+    },
+    Op {
+        name: "min",
+        aliases: &["minimum"],
+        help: "Per-sample minimum with another image",
+        params: arith::PARAMS,
+        func: arith::min,
+    },
+    Op {
+        name: "max",
+        aliases: &["maximum"],
+        help: "Per-sample maximum with another image",
+        params: arith::PARAMS,
+        func: arith::max,
+    },
+    Op {
+        name: "blend",
+        aliases: &["weighted-sum", "add-weighted"],
+        help: "Weighted sum alpha * input + beta * other + gamma",
+        params: arith::BLEND_PARAMS,
+        func: arith::blend,
+    },
+    Op {
+        name: "and",
+        aliases: &["mask-and"],
+        help: "Intersection of two binary masks",
+        params: bitwise::PARAMS,
+        func: bitwise::and,
+    },
+    Op {
+        name: "or",
+        aliases: &["mask-or"],
+        help: "Union of two binary masks",
+        params: bitwise::PARAMS,
+        func: bitwise::or,
+    },
+    Op {
+        name: "xor",
+        aliases: &["mask-xor"],
+        help: "Symmetric difference of two binary masks",
+        params: bitwise::PARAMS,
+        func: bitwise::xor,
+    },
+    Op {
+        name: "not",
+        aliases: &["mask-not", "invert-mask"],
+        help: "Complement of a binary mask",
+        params: &[],
+        func: bitwise::not,
+    },
+    Op {
+        name: "hysteresis",
+        aliases: &["double-threshold"],
+        help: "Keep pixels above high and those above low connected to them",
+        params: threshold::HYSTERESIS_PARAMS,
+        func: threshold::hysteresis_op,
+    },
+    Op {
+        name: "gradient",
+        aliases: &["gradient-magnitude"],
+        help: "Gradient magnitude, with the orientation as a second image",
+        params: gradient::PARAMS,
+        func: gradient::gradient,
+    },
+    Op {
+        name: "saliency-crop",
+        aliases: &["smart-crop", "thumbnail"],
+        help: "Crop to the most salient window of a given aspect ratio",
+        params: saliency::CROP_PARAMS,
+        func: saliency::crop,
+    },
+    Op {
+        name: "pool",
+        aliases: &["downsample"],
+        help: "Average, max or min pooling with separate window and stride",
+        params: pool::PARAMS,
+        func: pool::run,
+    },
+    Op {
+        name: "derivative",
+        aliases: &["deriv"],
+        help: "First (x, y) or second (xx, yy, xy) derivative with a selectable operator",
+        params: gradient::DERIVATIVE_PARAMS,
+        func: gradient::derivative,
+    },
+    Op {
+        name: "dog",
+        aliases: &["difference-of-gaussians"],
+        help: "Difference of two Gaussian blurs",
+        params: dog::PARAMS,
+        func: dog::dog,
+    },
+    Op {
+        name: "blobs",
+        aliases: &["blob-detect"],
+        help: "Scale-space DoG blob detection, marked with circles",
+        params: dog::BLOB_PARAMS,
+        func: dog::blobs,
+    },
+    Op {
+        name: "despeckle",
+        aliases: &["remove-small"],
+        help: "Remove connected components smaller than an area from a binary mask",
+        params: label::DESPECKLE_PARAMS,
+        func: label::despeckle,
+    },
+    Op {
+        name: "fill-holes",
+        aliases: &["hole-fill", "fill"],
+        help: "Fill interior holes of a binary mask, optionally only small ones",
+        params: label::FILL_PARAMS,
+        func: label::fill_holes,
+    },
+    Op {
+        name: "skeleton",
+        aliases: &["skeletonize"],
+        help: "Skeleton endpoints, branch points and branches, with spur pruning",
+        params: skeleton::PARAMS,
+        func: skeleton::run,
+    },
+    Op {
+        name: "ransac",
+        aliases: &["fit"],
+        help: "Robust line or homography fit to a points file",
+        params: ransac::PARAMS,
+        func: ransac::run,
+    },
+    Op {
+        name: "laplacian-blend",
+        aliases: &["pyramid-blend"],
+        help: "Blend with another image under a mask through Laplacian pyramids",
+        params: blend::PARAMS,
+        func: blend::laplacian_blend,
+    },
+    Op {
+        name: "flatten",
+        aliases: &["rolling-ball", "background-subtract"],
+        help: "Remove uneven illumination estimated by a large opening or blur",
+        params: background::PARAMS,
+        func: background::flatten,
+    },
+    Op {
+        name: "clahe",
+        aliases: &["adaptive-equalize"],
+        help: "Contrast limited adaptive histogram equalization",
+        params: histogram::CLAHE_PARAMS,
+        func: histogram::clahe,
+    },
+    Op {
+        name: "hs-histogram",
+        aliases: &["histogram-2d"],
+        help: "Render the 2D hue-saturation histogram",
+        params: histogram::HS_PARAMS,
+        func: histogram::hue_saturation,
+    },
+    Op {
+        name: "rgb-histogram",
+        aliases: &["histogram-3d"],
+        help: "Render the RGB histogram projected onto the R-G, G-B and B-R planes",
+        params: histogram::RGB_PARAMS,
+        func: histogram::rgb_projection,
+    },
+    Op {
+        name: "srgb",
+        aliases: &["srgb-transfer"],
+        help: "sRGB transfer function, to linear light (decode) or back (encode)",
+        params: transfer::SRGB_PARAMS,
+        func: transfer::srgb,
+    },
+    Op {
+        name: "convert",
+        aliases: &["cast"],
+        help: "Convert between gray, RGB and RGBA at 8 or 16 bits with explicit rounding or \
+             dithering",
+        params: convert::PARAMS,
+        func: convert::run,
+    },
+    Op {
+        name: "dither",
+        aliases: &["error-diffusion"],
+        help: "Error-diffusion dithering per channel with a selectable kernel",
+        params: dither::PARAMS,
+        func: dither::run,
+    },
+    Op {
+        name: "halftone",
+        aliases: &["screen"],
+        help: "Clustered-dot halftone with a rotated screen per CMY separation",
+        params: halftone::PARAMS,
+        func: halftone::run,
+    },
+    Op {
+        name: "edge-tangent-flow",
+        aliases: &["etf"],
+        help: "Edge tangent flow from the smoothed structure tensor, orientation as hue and \
+               coherence as value",
+        params: flow::PARAMS,
+        func: flow::run,
+    },
+    Op {
+        name: "coherent-lines",
+        aliases: &["fdog", "line-drawing"],
+        help: "Coherent line drawing: a flow-based DoG along the edge tangent flow, black lines \
+               on white",
+        params: flow::LINE_PARAMS,
+        func: flow::coherent_lines,
+    },
+    Op {
+        name: "quilt",
+        aliases: &["texture-synthesis"],
+        help: "Grow a larger texture from the input sample by image quilting",
+        params: quilt::PARAMS,
+        func: quilt::run,
+    },
+    Op {
+        name: "stipple",
+        aliases: &["stippling"],
+        help: "Point rendering: dots from Poisson-disk or jittered samples, denser where darker",
+        params: stipple::PARAMS,
+        func: stipple::run,
+    },
+    Op {
+        name: "chromatic-aberration",
+        aliases: &["ca"],
+        help: "Scale red and blue radially against green to simulate or correct lateral \
+               chromatic aberration",
+        params: warp::ABERRATION_PARAMS,
+        func: warp::chromatic_aberration,
+    },
+    Op {
+        name: "repair-pixels",
+        aliases: &["hot-pixels", "dead-pixels"],
+        help: "Detect hot and dead pixels against the robust local median and interpolate over \
+               them",
+        params: defect::PARAMS,
+        func: defect::repair,
+    },
+    Op {
+        name: "focus",
+        aliases: &["sharpness"],
+        help: "Report sharpness by variance of Laplacian or Tenengrad, globally and per tile",
+        params: focus::PARAMS,
+        func: focus::run,
+    },
+    Op {
+        name: "orientation-glyphs",
+        aliases: &["hog-glyphs", "glyphs"],
+        help: "Per-cell orientation histograms drawn as HOG-style line glyphs",
+        params: glyph::PARAMS,
+        func: glyph::run,
+    },
+    Op {
+        name: "structure-tensor",
+        aliases: &["tensor", "coherence"],
+        help: "Coherence of the structure tensor, with an orientation map and statistics",
+        params: tensor::PARAMS,
+        func: tensor::run,
+    },
+    Op {
+        name: "radon",
+        aliases: &["sinogram"],
+        help: "Radon transform as a sinogram, with filtered back-projection",
+        params: radon::PARAMS,
+        func: radon::run,
+    },
+    Op {
+        name: "finder-patterns",
+        aliases: &["qr-finder", "finder"],
+        help: "QR finder patterns outlined on the input and reported as JSON",
+        params: finder::PARAMS,
+        func: finder::run,
+    },
+    Op {
+        name: "normalize-orientation",
+        aliases: &["orient", "axis-align"],
+        help: "Rotates the largest object so its principal axis is axis-aligned",
+        params: moments::PARAMS,
+        func: moments::normalize_orientation,
+    },
+    Op {
+        name: "shape-filter",
+        aliases: &["filter-shapes", "component-filter"],
+        help: "Keep or remove mask components by area, circularity, aspect, solidity and extent",
+        params: label::SHAPE_PARAMS,
+        func: label::filter_shapes,
+    },
+    Op {
+        name: "inspect",
+        aliases: &["zoom", "pixel-peek"],
+        help: "Enlarged crop with a pixel grid and the sample values printed in each cell",
+        params: inspect::PARAMS,
+        func: inspect::run,
+    },
+    Op {
+        name: "statistics",
+        aliases: &["stats"],
+        help: "Report the mean, standard deviation and range of each channel",
+        params: stats::PARAMS,
+        func: stats::statistics,
+    },
+    Op {
+        name: "colorize",
+        aliases: &["false-color", "gradient-map"],
+        help: "Map gray levels to colors through a built-in or custom gradient",
+        params: colorize::PARAMS,
+        func: colorize::run,
+    },
+    Op {
+        name: "annotate",
+        aliases: &["guides", "caption"],
+        help: "Overlay composition guides and stamp a text caption",
+        params: annotate::PARAMS,
+        func: annotate::run,
+    },
+];
+
+/// Operations that read inputs of any bit depth, the rest need 8 bits
+pub static ANY_DEPTH: &[&str] = &["convert"];
+
+/// Operations that respect a mask attached with `--mask`, the rest refuse one
+pub static MASKED: &[&str] = &["q9", "q23", "laplacian-blend", "statistics"];
+
+/// Parameter values fixed by the knock problem statements, used under `--knock`
+/// in place of the general-purpose defaults
+pub static KNOCK_DEFAULTS: &[(&str, &[(&str, &str)])] = &[
+    ("q3", &[("threshold", "128"), ("method", "fixed")]),
+    ("q4", &[("apply", "all")]),
+    ("q5", &[("shift", "180")]),
+    ("q7", &[("size", "8")]),
+    ("q8", &[("size", "8")]),
+    ("q9", &[("sigma", "1.3"), ("size", "3")]),
+    ("q10", &[("size", "3")]),
+    ("q11", &[("size", "3")]),
+    ("q15", &[("direction", "vertical")]),
+    ("q23", &[("mode", "joint")]),
+    (
+        "q24",
+        &[("gamma", "2.2"), ("c", "1"), ("direction", "encode")],
+    ),
+    (
+        "q83",
+        &[("sigma", "3"), ("k", "0.04"), ("threshold", "0.1")],
+    ),
+];
+
+/// `name=value` arguments for the knock defaults of `op` that `given` does not set
+pub fn knock_defaults(op: &Op, given: &[String]) -> Vec<String> {
+    let is_given = |name: &str| {
+        given
+            .iter()
+            .any(|arg| arg.split_once('=').is_some_and(|(n, _)| n == name))
+    };
+    KNOCK_DEFAULTS
+        .iter()
+        .filter(|(knock, _)| *knock == op.name)
+        .flat_map(|(_, values)| values.iter())
+        .filter(|(name, _)| !is_given(name))
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect()
+}
+
+/// Looks up an operation by name, alias or knock number
+pub fn find(name: &str) -> Option<&'static Op> {
+    let name = match name.parse::<usize>() {
+        Ok(num) => format!("q{}", num),
+        Err(_) => name.to_owned(),
+    };
+    OPS.iter()
+        .find(|op| op.name == name || op.aliases.contains(&name.as_str()))
+}
+
+/// Returns names of the operations closest to `name`, best match first
+pub fn suggest(name: &str) -> Vec<&'static str> {
+    let max_dist = (name.chars().count() / 3).max(2);
+    let mut candidates: Vec<_> = OPS
+        .iter()
+        .filter_map(|op| {
+            std::iter::once(op.name)
+                .chain(op.aliases.iter().copied())
+                .map(|cand| (edit_distance(name, cand), cand))
+                .min()
+        })
+        .filter(|(dist, _)| *dist <= max_dist)
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(3)
+        .map(|(_, cand)| cand)
+        .collect()
+}
+
+// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let subst = prev[j] + usize::from(ca != *cb);
+            cur[j + 1] = subst.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use anyhow::{anyhow, bail, Result};
+
+#[derive(Clone, Copy, Debug)]
+pub enum Kind {
+    Int,
+    Float,
+    Choice(&'static [&'static str]),
+    Path,
+    // free text, in which `{input}` and `{output}` stand for the paths of the run
+    Text,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Int => write!(f, "an integer"),
+            Kind::Float => write!(f, "a number"),
+            Kind::Choice(choices) => write!(f, "one of {}", choices.join(", ")),
+            Kind::Path => write!(f, "a path"),
+            Kind::Text => write!(f, "a text"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Constraint {
+    // inclusive on both ends
+    Range(f64, f64),
+    Positive,
+    Odd,
+    // for parameters without a default
+    Required,
+}
+
+impl Constraint {
+    fn check(&self, value: &Value) -> bool {
+        let num = || {
+            value
+                .as_f64()
+                .expect("numeric constraint on a non-numeric parameter")
+        };
+        match *self {
+            Constraint::Range(min, max) => (min..=max).contains(&num()),
+            Constraint::Positive => num() > 0.,
+            Constraint::Odd => num() % 2. == 1.,
+            Constraint::Required => true,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Constraint::Range(min, max) => write!(f, "must be in {}..={}", min, max),
+            Constraint::Positive => write!(f, "must be > 0"),
+            Constraint::Odd => write!(f, "must be odd"),
+            Constraint::Required => write!(f, "is required"),
+        }
+    }
+}
+
+/// Declaration of a single option an operation accepts
+pub struct ParamSpec {
+    pub name: &'static str,
+    pub kind: Kind,
+    // `None` leaves the parameter unset unless given
+    pub default: Option<&'static str>,
+    pub constraints: &'static [Constraint],
+}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Int(i) => Some(i as f64),
+            Value::Float(f) => Some(f),
+            Value::Str(_) => None,
+        }
+    }
+}
+
+/// Names and descriptions of the global switches
+pub const FLAGS: &[(&str, &str)] = &[
+    (
+        "--integer-math",
+        "Use integer arithmetic in the filters that support it (q9, q11, q15); 3x3 and 5x5 Gaussians without a sigma become the exact binomial kernels",
+    ),
+    (
+        "--fast",
+        "Approximate the Gaussian blur (q9) with three box blurs, in constant time per pixel",
+    ),
+    (
+        "--knock",
+        "Default every parameter not given to the value fixed by the knock's problem statement",
+    ),
+    (
+        "--mask",
+        "As --mask=PATH, weight the pixels by a grayscale mask (q9, q23, laplacian-blend, statistics)",
+    ),
+];
+
+/// Global `--` switches, shared by every operation
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Flags {
+    // fixed-point arithmetic in the filters that support it
+    pub integer_math: bool,
+    // box-blur approximation of the Gaussian
+    pub fast: bool,
+    // defaults from the knock statements, see `ops::KNOCK_DEFAULTS`
+    pub knock: bool,
+}
+
+/// Validated option values for one invocation of an operation
+pub struct Params {
+    values: HashMap<&'static str, Value>,
+    pub flags: Flags,
+}
+
+impl Params {
+    /// Parses `name=value` arguments against `specs`, filling in defaults
+    pub fn parse(specs: &'static [ParamSpec], args: &[String], flags: Flags) -> Result<Self> {
+        let mut given = HashMap::new();
+        for arg in args {
+            let (name, value) = arg
+                .split_once('=')
+                .ok_or_else(|| anyhow!("expected `name=value`, got `{}`", arg))?;
+            let spec = specs.iter().find(|spec| spec.name == name).ok_or_else(|| {
+                let names: Vec<_> = specs.iter().map(|spec| spec.name).collect();
+                if names.is_empty() {
+                    anyhow!("unknown parameter `{}`, this operation takes none", name)
+                } else {
+                    anyhow!(
+                        "unknown parameter `{}`, available: {}",
+                        name,
+                        names.join(", ")
+                    )
+                }
+            })?;
+            if given.insert(spec.name, value).is_some() {
+                bail!("parameter `{}` is given more than once", name);
+            }
+        }
+
+        let mut values = HashMap::new();
+        for spec in specs {
+            match given.get(spec.name).copied().or(spec.default) {
+                Some(raw) => {
+                    values.insert(spec.name, parse_value(spec, raw)?);
+                }
+                None if spec
+                    .constraints
+                    .iter()
+                    .any(|c| matches!(c, Constraint::Required)) =>
+                {
+                    bail!("parameter `{}` is required", spec.name)
+                }
+                None => {}
+            }
+        }
+        Ok(Self { values, flags })
+    }
+
+    pub fn int(&self, name: &str) -> i64 {
+        self.int_opt(name)
+            .unwrap_or_else(|| panic!("no integer parameter `{}`", name))
+    }
+
+    pub fn int_opt(&self, name: &str) -> Option<i64> {
+        match self.values.get(name) {
+            Some(Value::Int(i)) => Some(*i),
+            _ => None,
+        }
+    }
+
+    pub fn float(&self, name: &str) -> f64 {
+        self.float_opt(name)
+            .unwrap_or_else(|| panic!("no numeric parameter `{}`", name))
+    }
+
+    pub fn float_opt(&self, name: &str) -> Option<f64> {
+        self.values.get(name).and_then(Value::as_f64)
+    }
+
+    pub fn str(&self, name: &str) -> &str {
+        self.str_opt(name)
+            .unwrap_or_else(|| panic!("no string parameter `{}`", name))
+    }
+
+    pub fn str_opt(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(Value::Str(s)) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+fn parse_value(spec: &ParamSpec, raw: &str) -> Result<Value> {
+    let value = match spec.kind {
+        Kind::Int => raw.parse().ok().map(Value::Int),
+        Kind::Float => raw
+            .parse()
+            .ok()
+            .filter(|f: &f64| f.is_finite())
+            .map(Value::Float),
+        Kind::Choice(choices) => choices.contains(&raw).then(|| Value::Str(raw.to_owned())),
+        Kind::Path => (!raw.is_empty()).then(|| Value::Str(raw.to_owned())),
+        Kind::Text => Some(Value::Str(raw.to_owned())),
+    }
+    .ok_or_else(|| {
+        anyhow!(
+            "parameter `{}` expects {}, got `{}`",
+            spec.name,
+            spec.kind,
+            raw
+        )
+    })?;
+
+    for constraint in spec.constraints {
+        if !constraint.check(&value) {
+            bail!("parameter `{}` {}, got {}", spec.name, constraint, raw);
+        }
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static SPECS: &[ParamSpec] = &[
+        ParamSpec {
+            name: "size",
+            kind: Kind::Int,
+            default: Some("3"),
+            constraints: &[Constraint::Range(1., 9.), Constraint::Odd],
+        },
+        ParamSpec {
+            name: "path",
+            kind: Kind::Path,
+            default: None,
+            constraints: &[Constraint::Required],
+        },
+    ];
+
+    fn parse(args: &[&str]) -> Result<Params> {
+        let args: Vec<String> = args.iter().map(|&arg| arg.to_owned()).collect();
+        Params::parse(SPECS, &args, Flags::default())
+    }
+
+    fn error(args: &[&str]) -> String {
+        parse(args).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn defaults() {
+        let params = parse(&["path=a.png"]).unwrap();
+        assert_eq!(params.int("size"), 3);
+        assert_eq!(params.str("path"), "a.png");
+    }
+
+    #[test]
+    fn range() {
+        assert_eq!(
+            error(&["path=a.png", "size=11"]),
+            "parameter `size` must be in 1..=9, got 11"
+        );
+    }
+
+    #[test]
+    fn odd() {
+        assert_eq!(
+            error(&["path=a.png", "size=4"]),
+            "parameter `size` must be odd, got 4"
+        );
+    }
+
+    #[test]
+    fn required() {
+        assert_eq!(error(&["size=5"]), "parameter `path` is required");
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(
+            error(&["path=a.png", "sigma=1"]),
+            "unknown parameter `sigma`, available: size, path"
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::f64::consts::PI;
+
+use crate::fft::{fft2d, Complex};
+
+/// Translation `(dx, dy)` that moves `a` onto `b`, i.e. `b(x, y) ~ a(x - dx, y - dy)`,
+/// by phase correlation of two equally sized planes. The peak is refined to
+/// subpixel precision by a parabola through its neighbors; shifts beyond half the
+/// size wrap around. Also returns the peak height in (0, 1], how well they match.
+pub fn phase_correlation(a: &[f64], b: &[f64], width: usize, height: usize) -> (f64, f64, f64) {
+    assert_eq!(a.len(), width * height);
+    assert_eq!(b.len(), a.len());
+    let (pw, ph) = (width.next_power_of_two(), height.next_power_of_two());
+    // Hann window over the image, mean removed, so the borders do not correlate
+    let window = |x: usize, n: usize| 0.5 - 0.5 * (2. * PI * (x as f64 + 0.5) / n as f64).cos();
+    let spectrum = |plane: &[f64]| {
+        let mean = plane.iter().sum::<f64>() / plane.len() as f64;
+        let mut buf = vec![Complex::default(); pw * ph];
+        for y in 0..height {
+            for x in 0..width {
+                let w = window(x, width) * window(y, height);
+                buf[y * pw + x] = Complex::new((plane[y * width + x] - mean) * w, 0.);
+            }
+        }
+        fft2d(&mut buf, pw, ph, false);
+        buf
+    };
+    let (fa, fb) = (spectrum(a), spectrum(b));
+    let mut cross: Vec<Complex> = fa
+        .iter()
+        .zip(&fb)
+        .map(|(&p, &q)| {
+            let c = q * p.conj();
+            let m = c.abs();
+            if m > 1e-12 {
+                Complex::new(c.re / m, c.im / m)
+            } else {
+                Complex::default()
+            }
+        })
+        .collect();
+    fft2d(&mut cross, pw, ph, true);
+
+    let peak = (0..cross.len())
+        .max_by(|&i, &j| cross[i].re.total_cmp(&cross[j].re))
+        .unwrap();
+    let (px, py) = (peak % pw, peak / pw);
+    let at = |x: usize, y: usize| cross[(y % ph) * pw + x % pw].re;
+    let refine = |minus: f64, center: f64, plus: f64| {
+        let denom = minus - 2. * center + plus;
+        if denom.abs() > 1e-12 {
+            ((minus - plus) / (2. * denom)).clamp(-0.5, 0.5)
+        } else {
+            0.
+        }
+    };
+    let center = at(px, py);
+    let fx = refine(at(px + pw - 1, py), center, at(px + 1, py));
+    let fy = refine(at(px, py + ph - 1), center, at(px, py + 1));
+    let signed = |p: usize, n: usize| {
+        if p > n / 2 {
+            p as f64 - n as f64
+        } else {
+            p as f64
+        }
+    };
+    (signed(px, pw) + fx, signed(py, ph) + fy, center)
+}
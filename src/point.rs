@@ -0,0 +1,38 @@
+/// Replaces every sample `v` by `lut[v]`
+pub fn apply_lut(buf: &mut [u8], lut: &[u8; 256]) {
+    for v in buf {
+        *v = lut[*v as usize];
+    }
+}
+
+/// 0 below `threshold`, 255 otherwise
+pub fn binarize(buf: &mut [u8], threshold: u8) {
+    let mut lut = [0u8; 256];
+    for (v, out) in lut.iter_mut().enumerate() {
+        *out = if (v as u8) < threshold { 0 } else { 255 };
+    }
+    apply_lut(buf, &lut);
+}
+
+/// Count of each sample value
+pub fn histogram(buf: &[u8]) -> [u32; 256] {
+    let mut hist = [0u32; 256];
+    for &v in buf {
+        hist[v as usize] += 1;
+    }
+    hist
+}
+
+/// Maps each value to 255 times the fraction of samples at or below it (q23)
+pub fn equalization_lut(hist: &[u64; 256]) -> [u8; 256] {
+    let total: u64 = hist.iter().sum();
+    let mut lut = [0u8; 256];
+    let mut cumulative = 0u64;
+    for (out, &count) in lut.iter_mut().zip(hist) {
+        cumulative += count;
+        *out = (cumulative * 255 + total / 2)
+            .checked_div(total)
+            .unwrap_or(0) as u8;
+    }
+    lut
+}
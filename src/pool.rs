@@ -0,0 +1,170 @@
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{Image, Info};
+
+pub static BLOCK_PARAMS: &[ParamSpec] = &[ParamSpec {
+    name: "size",
+    kind: Kind::Int,
+    default: Some("8"),
+    constraints: &[Constraint::Positive],
+}];
+
+pub static PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "mode",
+        kind: Kind::Choice(&["avg", "max", "min"]),
+        default: Some("max"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "window",
+        kind: Kind::Int,
+        default: Some("2"),
+        constraints: &[Constraint::Positive],
+    },
+    // equal to `window` when unset, smaller values make the windows overlap
+    ParamSpec {
+        name: "stride",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+];
+
+#[derive(Clone, Copy)]
+enum Mode {
+    Avg,
+    Max,
+    Min,
+}
+
+impl Mode {
+    fn from_params(params: &Params) -> Self {
+        match params.str("mode") {
+            "avg" => Mode::Avg,
+            "max" => Mode::Max,
+            "min" => Mode::Min,
+            _ => unreachable!(),
+        }
+    }
+}
+
+// windows needed to cover `len` pixels, the last one clipped at the border; with
+// gaps between the windows only those starting inside the image count
+fn count(len: usize, window: usize, stride: usize) -> usize {
+    if len <= window {
+        1
+    } else {
+        ((len - window).div_ceil(stride) + 1).min((len - 1) / stride + 1)
+    }
+}
+
+/// Reduces every `window` x `window` region, `stride` pixels apart, to one pixel
+fn pool(img: &Image, mode: Mode, window: usize, stride: usize) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let channels = img.info.color.samples();
+    let (out_w, out_h) = (count(width, window, stride), count(height, window, stride));
+    let mut bytes = Vec::with_capacity(out_w * out_h * channels);
+    for oy in 0..out_h {
+        let ys = oy * stride..(oy * stride + window).min(height);
+        for ox in 0..out_w {
+            let xs = ox * stride..(ox * stride + window).min(width);
+            for c in 0..channels {
+                let values = ys.clone().flat_map(|y| {
+                    xs.clone()
+                        .map(move |x| img.bytes[(y * width + x) * channels + c])
+                });
+                bytes.push(match mode {
+                    Mode::Avg => {
+                        let (sum, n) = values.fold((0u32, 0u32), |(s, n), v| (s + v as u32, n + 1));
+                        ((sum as f64) / (n as f64)).round() as u8
+                    }
+                    Mode::Max => values.max().unwrap(),
+                    Mode::Min => values.min().unwrap(),
+                });
+            }
+        }
+    }
+    Image {
+        info: Info {
+            width: out_w as u32,
+            height: out_h as u32,
+            ..img.info.clone()
+        },
+        bytes,
+        mask: None,
+    }
+}
+
+// pools non-overlapping blocks and paints each block with its value, as q7 and q8 do
+fn block(img: Image, mode: Mode, size: usize) -> Image {
+    let pooled = pool(&img, mode, size, size);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let channels = img.info.color.samples();
+    let pooled_w = pooled.info.width as usize;
+    let mut bytes = img.bytes;
+    for y in 0..height {
+        for x in 0..width {
+            let src = ((y / size) * pooled_w + x / size) * channels;
+            let dst = (y * width + x) * channels;
+            bytes[dst..dst + channels].copy_from_slice(&pooled.bytes[src..src + channels]);
+        }
+    }
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+pub fn average_block(img: Image, params: &Params) -> Image {
+    block(img, Mode::Avg, params.int("size") as usize)
+}
+
+pub fn max_block(img: Image, params: &Params) -> Image {
+    block(img, Mode::Max, params.int("size") as usize)
+}
+
+pub fn run(img: Image, params: &Params) -> Image {
+    let window = params.int("window") as usize;
+    let stride = params.int_opt("stride").map_or(window, |s| s as usize);
+    pool(&img, Mode::from_params(params), window, stride)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(width: usize, height: usize) -> Image {
+        Image {
+            info: Info {
+                width: width as u32,
+                height: height as u32,
+                color: png::ColorType::Grayscale,
+                depth: png::BitDepth::Eight,
+            },
+            bytes: (0..width * height).map(|i| i as u8).collect(),
+            mask: None,
+        }
+    }
+
+    #[test]
+    fn stride_larger_than_window() {
+        let img = gray(10, 4);
+        for mode in [Mode::Avg, Mode::Max, Mode::Min] {
+            let out = pool(&img, mode, 2, 3);
+            // windows start at 0, 3, 6 and 9
+            assert_eq!((out.info.width, out.info.height), (4, 2));
+        }
+        let out = pool(&img, Mode::Max, 2, 3);
+        assert_eq!(out.bytes, [11, 14, 17, 19, 31, 34, 37, 39]);
+        let out = pool(&img, Mode::Max, 2, 1000);
+        assert_eq!((out.info.width, out.info.height, out.bytes[0]), (1, 1, 11));
+    }
+
+    #[test]
+    fn blocks_clipped_at_the_border() {
+        let out = pool(&gray(10, 4), Mode::Min, 4, 4);
+        assert_eq!((out.info.width, out.info.height), (3, 1));
+        assert_eq!(out.bytes, [0, 4, 8]);
+    }
+}
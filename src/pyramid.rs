@@ -0,0 +1,153 @@
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// One channel of samples
+#[derive(Clone, Debug)]
+pub struct Plane {
+    pub width: usize,
+    pub height: usize,
+    pub data: Vec<f64>,
+}
+
+// binomial 5-tap kernel of Burt and Adelson
+const TAPS: [f64; 5] = [1. / 16., 4. / 16., 6. / 16., 4. / 16., 1. / 16.];
+
+impl Plane {
+    pub fn new(width: usize, height: usize, data: Vec<f64>) -> Self {
+        assert_eq!(data.len(), width * height);
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    // edge pixels repeat outside the plane
+    fn at(&self, x: isize, y: isize) -> f64 {
+        let x = x.clamp(0, self.width as isize - 1) as usize;
+        let y = y.clamp(0, self.height as isize - 1) as usize;
+        self.data[y * self.width + x]
+    }
+
+    /// Blurs and halves the size, rounding up
+    pub fn reduce(&self) -> Self {
+        let (width, height) = (self.width.div_ceil(2), self.height.div_ceil(2));
+        let mut data = vec![0.; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let mut acc = 0.;
+                for (j, wy) in TAPS.iter().enumerate() {
+                    for (i, wx) in TAPS.iter().enumerate() {
+                        acc += wy
+                            * wx
+                            * self.at(
+                                2 * x as isize + i as isize - 2,
+                                2 * y as isize + j as isize - 2,
+                            );
+                    }
+                }
+                data[y * width + x] = acc;
+            }
+        }
+        Self::new(width, height, data)
+    }
+
+    /// Upsamples to `width` x `height` by zero insertion and interpolation with the same kernel
+    pub fn expand(&self, width: usize, height: usize) -> Self {
+        let mut data = vec![0.; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let mut acc = 0.;
+                for (j, wy) in TAPS.iter().enumerate() {
+                    let sy = y as isize + j as isize - 2;
+                    if sy % 2 != 0 {
+                        continue;
+                    }
+                    for (i, wx) in TAPS.iter().enumerate() {
+                        let sx = x as isize + i as isize - 2;
+                        if sx % 2 != 0 {
+                            continue;
+                        }
+                        acc += 4. * wy * wx * self.at(sx / 2, sy / 2);
+                    }
+                }
+                data[y * width + x] = acc;
+            }
+        }
+        Self::new(width, height, data)
+    }
+}
+
+/// A stack of planes, finest first
+#[derive(Clone, Debug)]
+pub struct Pyramid {
+    pub levels: Vec<Plane>,
+}
+
+impl Pyramid {
+    /// Levels that fit in `width` x `height`, halving down to a single pixel
+    pub fn max_levels(width: usize, height: usize) -> usize {
+        let mut levels = 1;
+        let (mut w, mut h) = (width, height);
+        while w > 1 || h > 1 {
+            (w, h) = (w.div_ceil(2), h.div_ceil(2));
+            levels += 1;
+        }
+        levels
+    }
+
+    /// `levels` successive reductions of `base`, itself included
+    pub fn gaussian(base: Plane, levels: usize) -> Self {
+        assert!(levels >= 1);
+        let mut planes = vec![base];
+        while planes.len() < levels {
+            let next = planes.last().unwrap().reduce();
+            planes.push(next);
+        }
+        Self { levels: planes }
+    }
+
+    /// Band-pass differences between Gaussian levels, the coarsest level kept as is
+    pub fn laplacian(base: Plane, levels: usize) -> Self {
+        let gaussian = Self::gaussian(base, levels);
+        let mut planes: Vec<Plane> = gaussian
+            .levels
+            .windows(2)
+            .map(|pair| {
+                let up = pair[1].expand(pair[0].width, pair[0].height);
+                let data = pair[0]
+                    .data
+                    .iter()
+                    .zip(&up.data)
+                    .map(|(a, b)| a - b)
+                    .collect();
+                Plane::new(pair[0].width, pair[0].height, data)
+            })
+            .collect();
+        planes.push(gaussian.levels.last().unwrap().clone());
+        Self { levels: planes }
+    }
+
+    pub fn level(&self, i: usize) -> &Plane {
+        &self.levels[i]
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+
+    /// Inverse of `laplacian`: expands from the coarsest level, adding each band back
+    pub fn collapse(&self) -> Plane {
+        let mut acc = self.levels.last().unwrap().clone();
+        for band in self.levels.iter().rev().skip(1) {
+            let up = acc.expand(band.width, band.height);
+            let data = up.data.iter().zip(&band.data).map(|(a, b)| a + b).collect();
+            acc = Plane::new(band.width, band.height, data);
+        }
+        acc
+    }
+}
@@ -0,0 +1,211 @@
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::rng::XorShift;
+use crate::template::{ssd, ssd_map, View};
+use crate::{to_rgb, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // of the output, twice the sample when not given
+    ParamSpec {
+        name: "width",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(1., 16384.)],
+    },
+    ParamSpec {
+        name: "height",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(1., 16384.)],
+    },
+    // side of the square blocks copied from the sample
+    ParamSpec {
+        name: "patch",
+        kind: Kind::Int,
+        default: Some("24"),
+        constraints: &[Constraint::Range(2., 1024.)],
+    },
+    // pixels shared by neighboring blocks, a sixth of the patch when not given
+    ParamSpec {
+        name: "overlap",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    // blocks within this fraction of the best overlap error are picked at random
+    ParamSpec {
+        name: "tolerance",
+        kind: Kind::Float,
+        default: Some("0.1"),
+        constraints: &[Constraint::Range(0., 10.)],
+    },
+    // cut: seam along the minimum error boundary, overwrite: the new block covers the overlap
+    ParamSpec {
+        name: "seam",
+        kind: Kind::Choice(&["cut", "overwrite"]),
+        default: Some("cut"),
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "seed",
+        kind: Kind::Int,
+        default: Some("1"),
+        constraints: &[],
+    },
+];
+
+// cheapest 8-connected path through `cost` (`len` steps of `across` values each),
+// as the position across at every step
+fn min_path(cost: &[u64], len: usize, across: usize) -> Vec<usize> {
+    let mut total = cost.to_vec();
+    for step in 1..len {
+        for a in 0..across {
+            let prev = (a.saturating_sub(1)..(a + 2).min(across))
+                .map(|p| total[(step - 1) * across + p])
+                .min()
+                .unwrap();
+            total[step * across + a] += prev;
+        }
+    }
+    let mut path = vec![0; len];
+    path[len - 1] = (0..across)
+        .min_by_key(|&a| total[(len - 1) * across + a])
+        .unwrap();
+    for step in (0..len - 1).rev() {
+        let next = path[step + 1];
+        path[step] = (next.saturating_sub(1)..(next + 2).min(across))
+            .min_by_key(|&p| total[step * across + p])
+            .unwrap();
+    }
+    path
+}
+
+/// Efros-Freeman image quilting: grows a texture from blocks of the sample, each
+/// matching the blocks already placed on its overlap, joined along the seam of
+/// least difference
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = to_rgb(img);
+    let sample = View::new(&img);
+    let patch = params.int("patch") as usize;
+    let overlap = params.int_opt("overlap").map_or(patch / 6, |v| v as usize);
+    if patch > sample.width || patch > sample.height {
+        die!(
+            "[ERROR] patch {} does not fit in the {}x{} sample",
+            patch,
+            sample.width,
+            sample.height
+        );
+    }
+    if overlap == 0 || overlap >= patch {
+        die!(
+            "[ERROR] overlap must be between 1 and patch - 1 ({}), got {}",
+            patch - 1,
+            overlap
+        );
+    }
+    let out_width = params
+        .int_opt("width")
+        .map_or(2 * sample.width, |v| v as usize);
+    let out_height = params
+        .int_opt("height")
+        .map_or(2 * sample.height, |v| v as usize);
+    let step = patch - overlap;
+    let blocks = |len: usize| (len.saturating_sub(overlap)).div_ceil(step).max(1);
+    let (bw, bh) = (blocks(out_width), blocks(out_height));
+    let (cw, ch) = (bw * step + overlap, bh * step + overlap);
+    let mut canvas = vec![0u8; cw * ch * 3];
+    let mut rng = XorShift::new(params.int("seed") as u64);
+    let tolerance = params.float("tolerance");
+    let cut = params.str("seam") == "cut";
+    let positions = (sample.width - patch + 1, sample.height - patch + 1);
+
+    for by in 0..bh {
+        for bx in 0..bw {
+            let (x0, y0) = (bx * step, by * step);
+            let (left, top) = (bx > 0, by > 0);
+            let mut existing = vec![0u8; patch * patch * 3];
+            for py in 0..patch {
+                let row = ((y0 + py) * cw + x0) * 3;
+                existing[py * patch * 3..(py + 1) * patch * 3]
+                    .copy_from_slice(&canvas[row..row + patch * 3]);
+            }
+            let target = View {
+                bytes: &existing,
+                width: patch,
+                height: patch,
+                channels: 3,
+            };
+            let overlaps: Vec<bool> = (0..patch * patch)
+                .map(|i| (left && i % patch < overlap) || (top && i / patch < overlap))
+                .collect();
+
+            let (sx, sy) = if !left && !top {
+                (rng.below(positions.0), rng.below(positions.1))
+            } else {
+                let map = ssd_map(sample, target, Some(&overlaps));
+                let best = *map.iter().min().unwrap() as f64;
+                let candidates: Vec<usize> = (0..map.len())
+                    .filter(|&i| map[i] as f64 <= best * (1. + tolerance))
+                    .collect();
+                let pick = candidates[rng.below(candidates.len())];
+                (pick % positions.0, pick / positions.0)
+            };
+
+            // per pixel of the block: whether the new block shows there
+            let mut take = vec![true; patch * patch];
+            if cut {
+                let error = |px: usize, py: usize| {
+                    let single = View {
+                        bytes: &existing[(py * patch + px) * 3..][..3],
+                        width: 1,
+                        height: 1,
+                        channels: 3,
+                    };
+                    ssd(sample, single, sx + px, sy + py, None)
+                };
+                if left {
+                    let cost: Vec<u64> = (0..patch * overlap)
+                        .map(|i| error(i % overlap, i / overlap))
+                        .collect();
+                    for (py, seam) in min_path(&cost, patch, overlap).into_iter().enumerate() {
+                        for px in 0..seam {
+                            take[py * patch + px] = false;
+                        }
+                    }
+                }
+                if top {
+                    let cost: Vec<u64> = (0..patch * overlap)
+                        .map(|i| error(i / overlap, i % overlap))
+                        .collect();
+                    for (px, seam) in min_path(&cost, patch, overlap).into_iter().enumerate() {
+                        for py in 0..seam {
+                            take[py * patch + px] = false;
+                        }
+                    }
+                }
+            }
+
+            for py in 0..patch {
+                for px in 0..patch {
+                    if take[py * patch + px] {
+                        let src = ((sy + py) * sample.width + sx + px) * 3;
+                        let dst = ((y0 + py) * cw + x0 + px) * 3;
+                        canvas[dst..dst + 3].copy_from_slice(&sample.bytes[src..src + 3]);
+                    }
+                }
+            }
+        }
+    }
+
+    let bytes = (0..out_height)
+        .flat_map(|y| canvas[y * cw * 3..(y * cw + out_width) * 3].iter().copied())
+        .collect();
+    Image {
+        info: Info {
+            width: out_width as u32,
+            height: out_height as u32,
+            ..img.info
+        },
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,243 @@
+use std::f64::consts::PI;
+
+use crate::fft::{fft, Complex};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, write_param_image, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // projection angles in degrees, from the start included to the end excluded
+    ParamSpec {
+        name: "angle_start",
+        kind: Kind::Float,
+        default: Some("0"),
+        constraints: &[Constraint::Range(-360., 360.)],
+    },
+    ParamSpec {
+        name: "angle_end",
+        kind: Kind::Float,
+        default: Some("180"),
+        constraints: &[Constraint::Range(-360., 360.)],
+    },
+    // projections, one row of the sinogram each
+    ParamSpec {
+        name: "angles",
+        kind: Kind::Int,
+        default: Some("180"),
+        constraints: &[Constraint::Range(1., 4096.)],
+    },
+    // where to write the filtered back-projection, skipped when unset
+    ParamSpec {
+        name: "reconstruct",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // ramp: Ram-Lak, shepp-logan: ramp damped by a sinc, none: plain back-projection
+    ParamSpec {
+        name: "filter",
+        kind: Kind::Choice(&["ramp", "shepp-logan", "none"]),
+        default: Some("ramp"),
+        constraints: &[],
+    },
+];
+
+// bilinear sample with zero outside the image
+fn sample(plane: &[f64], width: usize, height: usize, x: f64, y: f64) -> f64 {
+    if x <= -1. || y <= -1. || x >= width as f64 || y >= height as f64 {
+        return 0.;
+    }
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let at = |x: f64, y: f64| {
+        if x < 0. || y < 0. || x >= width as f64 || y >= height as f64 {
+            0.
+        } else {
+            plane[y as usize * width + x as usize]
+        }
+    };
+    let top = at(x0, y0) * (1. - fx) + at(x0 + 1., y0) * fx;
+    let bottom = at(x0, y0 + 1.) * (1. - fx) + at(x0 + 1., y0 + 1.) * fx;
+    top * (1. - fy) + bottom * fy
+}
+
+/// Line integrals of `plane` for every angle in `thetas` (radians) and every one
+/// of `detectors` offsets from the image center, one projection per row. The
+/// projection at theta runs along (cos theta, sin theta).
+pub fn radon(
+    plane: &[f64],
+    width: usize,
+    height: usize,
+    thetas: &[f64],
+    detectors: usize,
+) -> Vec<f64> {
+    let (cx, cy) = ((width as f64 - 1.) / 2., (height as f64 - 1.) / 2.);
+    let half = (detectors as f64 - 1.) / 2.;
+    let mut sinogram = Vec::with_capacity(thetas.len() * detectors);
+    for &theta in thetas {
+        let (c, s) = (theta.cos(), theta.sin());
+        for d in 0..detectors {
+            let offset = d as f64 - half;
+            let sum: f64 = (0..detectors)
+                .map(|t| {
+                    let t = t as f64 - half;
+                    let x = cx + offset * c - t * s;
+                    let y = cy + offset * s + t * c;
+                    sample(plane, width, height, x, y)
+                })
+                .sum();
+            sinogram.push(sum);
+        }
+    }
+    sinogram
+}
+
+// applies the reconstruction filter to every projection through the FFT
+fn filter_projections(sinogram: &mut [f64], detectors: usize, filter: &str) {
+    let n = (2 * detectors).next_power_of_two();
+    // spatial Ram-Lak kernel, so that the zero frequency is not lost to sampling
+    let mut response: Vec<Complex> = (0..n)
+        .map(|i| {
+            let k = if i <= n / 2 {
+                i as f64
+            } else {
+                i as f64 - n as f64
+            };
+            let h = if k == 0. {
+                0.25
+            } else if k as i64 % 2 != 0 {
+                -1. / (PI * k).powi(2)
+            } else {
+                0.
+            };
+            Complex::new(h, 0.)
+        })
+        .collect();
+    fft(&mut response, false);
+    if filter == "shepp-logan" {
+        for (i, r) in response.iter_mut().enumerate() {
+            let k = if i <= n / 2 {
+                i as f64
+            } else {
+                i as f64 - n as f64
+            };
+            let arg = PI * k / n as f64;
+            if arg != 0. {
+                *r = Complex::new(r.re * arg.sin() / arg, r.im * arg.sin() / arg);
+            }
+        }
+    }
+    let mut buf = vec![Complex::default(); n];
+    for projection in sinogram.chunks_mut(detectors) {
+        buf.iter_mut().for_each(|b| *b = Complex::default());
+        for (b, &v) in buf.iter_mut().zip(projection.iter()) {
+            b.re = v;
+        }
+        fft(&mut buf, false);
+        for (b, r) in buf.iter_mut().zip(&response) {
+            *b = *b * *r;
+        }
+        fft(&mut buf, true);
+        for (v, b) in projection.iter_mut().zip(&buf) {
+            *v = b.re;
+        }
+    }
+}
+
+/// Smears every (filtered) projection back across a `width` x `height` image
+pub fn back_project(
+    sinogram: &[f64],
+    thetas: &[f64],
+    detectors: usize,
+    width: usize,
+    height: usize,
+) -> Vec<f64> {
+    let (cx, cy) = ((width as f64 - 1.) / 2., (height as f64 - 1.) / 2.);
+    let half = (detectors as f64 - 1.) / 2.;
+    let mut out = vec![0.; width * height];
+    for (projection, &theta) in sinogram.chunks(detectors).zip(thetas) {
+        let (c, s) = (theta.cos(), theta.sin());
+        for (i, v) in out.iter_mut().enumerate() {
+            let (x, y) = ((i % width) as f64 - cx, (i / width) as f64 - cy);
+            let d = x * c + y * s + half;
+            let d0 = d.floor();
+            if d0 < 0. || d0 + 1. >= detectors as f64 {
+                continue;
+            }
+            let f = d - d0;
+            *v += projection[d0 as usize] * (1. - f) + projection[d0 as usize + 1] * f;
+        }
+    }
+    // each angle stands for pi / angles of the half turn
+    let scale = PI / thetas.len() as f64;
+    out.iter_mut().for_each(|v| *v *= scale);
+    out
+}
+
+/// Sinogram of the image, one projection per row scaled to the largest line
+/// integral, and optionally the filtered back-projection from it
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let plane: Vec<f64> = img.bytes.iter().map(|&v| v as f64).collect();
+    let (start, end) = (params.float("angle_start"), params.float("angle_end"));
+    if start >= end {
+        die!(
+            "[ERROR] angle_start ({}) must be less than angle_end ({})",
+            start,
+            end
+        );
+    }
+    let angles = params.int("angles") as usize;
+    let thetas: Vec<f64> = (0..angles)
+        .map(|a| (start + (end - start) * a as f64 / angles as f64).to_radians())
+        .collect();
+    // every line through the image fits on the detector
+    let detectors = ((width * width + height * height) as f64).sqrt().ceil() as usize | 1;
+    let mut sinogram = radon(&plane, width, height, &thetas, detectors);
+
+    let max = sinogram.iter().copied().fold(0., f64::max);
+    let bytes = sinogram
+        .iter()
+        .map(|v| {
+            if max > 0. {
+                (v / max * 255.).round() as u8
+            } else {
+                0
+            }
+        })
+        .collect();
+
+    if params.str_opt("reconstruct").is_some() {
+        let filter = params.str("filter");
+        if filter != "none" {
+            filter_projections(&mut sinogram, detectors, filter);
+        }
+        let mut reconstruction = back_project(&sinogram, &thetas, detectors, width, height);
+        // unfiltered back-projection is only meaningful up to scale
+        if filter == "none" {
+            let max = reconstruction.iter().copied().fold(0., f64::max);
+            if max > 0. {
+                reconstruction.iter_mut().for_each(|v| *v *= 255. / max);
+            }
+        }
+        let image = Image {
+            info: img.info.clone(),
+            bytes: reconstruction
+                .iter()
+                .map(|v| v.round().clamp(0., 255.) as u8)
+                .collect(),
+            mask: None,
+        };
+        write_param_image(params, "reconstruct", &image);
+    }
+
+    Image {
+        info: Info {
+            width: detectors as u32,
+            height: angles as u32,
+            ..img.info
+        },
+        bytes,
+        mask: None,
+    }
+}
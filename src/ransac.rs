@@ -0,0 +1,400 @@
+use std::fmt::Write;
+
+use gasyori100knock_rs::draw;
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::rng::XorShift;
+use crate::{report, to_rgb, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "model",
+        kind: Kind::Choice(&["line", "homography"]),
+        default: Some("homography"),
+        constraints: &[],
+    },
+    // text file, one `x y` (line) or `x1 y1 x2 y2` (homography) per row
+    ParamSpec {
+        name: "points",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[Constraint::Required],
+    },
+    // maximum distance in pixels for an inlier
+    ParamSpec {
+        name: "threshold",
+        kind: Kind::Float,
+        default: Some("2"),
+        constraints: &[Constraint::Positive],
+    },
+    ParamSpec {
+        name: "iterations",
+        kind: Kind::Int,
+        default: Some("1000"),
+        constraints: &[Constraint::Range(1., 1e7)],
+    },
+    ParamSpec {
+        name: "seed",
+        kind: Kind::Int,
+        default: Some("1"),
+        constraints: &[],
+    },
+    report::PARAM,
+];
+
+#[derive(Clone, Copy)]
+pub struct Pair {
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+}
+
+#[derive(Clone, Copy)]
+pub enum ModelKind {
+    Line,
+    Homography,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Model {
+    // a * x + b * y + c = 0 with a^2 + b^2 = 1
+    Line([f64; 3]),
+    // row major, h[8] = 1
+    Homography([f64; 9]),
+}
+
+impl ModelKind {
+    // numbers per row of the points file
+    fn columns(self) -> usize {
+        match self {
+            ModelKind::Line => 2,
+            ModelKind::Homography => 4,
+        }
+    }
+
+    fn sample_size(self) -> usize {
+        match self {
+            ModelKind::Line => 2,
+            ModelKind::Homography => 4,
+        }
+    }
+
+    /// Least-squares model through `pairs`, `None` when they are degenerate
+    pub fn fit(self, pairs: &[Pair]) -> Option<Model> {
+        match self {
+            ModelKind::Line => fit_line(pairs),
+            ModelKind::Homography => fit_homography(pairs),
+        }
+    }
+}
+
+impl Model {
+    /// Distance of `pair` from the model: to the line, or from `to` to the mapped `from`
+    pub fn error(&self, pair: &Pair) -> f64 {
+        match self {
+            Model::Line([a, b, c]) => (a * pair.from.0 + b * pair.from.1 + c).abs(),
+            Model::Homography(_) => match self.map(pair.from) {
+                Some((x, y)) => (x - pair.to.0).hypot(y - pair.to.1),
+                None => f64::INFINITY,
+            },
+        }
+    }
+
+    /// `p` under the homography
+    pub fn map(&self, (x, y): (f64, f64)) -> Option<(f64, f64)> {
+        match self {
+            Model::Homography(h) => {
+                let w = h[6] * x + h[7] * y + h[8];
+                (w.abs() > 1e-12).then(|| {
+                    (
+                        (h[0] * x + h[1] * y + h[2]) / w,
+                        (h[3] * x + h[4] * y + h[5]) / w,
+                    )
+                })
+            }
+            Model::Line(_) => None,
+        }
+    }
+}
+
+// total least squares: the normal is the minor axis of the scatter
+fn fit_line(pairs: &[Pair]) -> Option<Model> {
+    let n = pairs.len() as f64;
+    let (cx, cy) = pairs
+        .iter()
+        .fold((0., 0.), |(x, y), p| (x + p.from.0, y + p.from.1));
+    let (cx, cy) = (cx / n, cy / n);
+    let (mut sxx, mut syy, mut sxy) = (0., 0., 0.);
+    for p in pairs {
+        let (dx, dy) = (p.from.0 - cx, p.from.1 - cy);
+        sxx += dx * dx;
+        syy += dy * dy;
+        sxy += dx * dy;
+    }
+    if sxx + syy < 1e-12 {
+        return None;
+    }
+    let theta = 0.5 * (2. * sxy).atan2(sxx - syy);
+    let (a, b) = (-theta.sin(), theta.cos());
+    Some(Model::Line([a, b, -(a * cx + b * cy)]))
+}
+
+// similarity moving the centroid to the origin and the mean distance to sqrt(2)
+fn normalization(points: impl Iterator<Item = (f64, f64)> + Clone) -> (f64, f64, f64) {
+    let n = points.clone().count() as f64;
+    let (cx, cy) = points
+        .clone()
+        .fold((0., 0.), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (cx, cy) = (cx / n, cy / n);
+    let mean = points.map(|(x, y)| (x - cx).hypot(y - cy)).sum::<f64>() / n;
+    (cx, cy, if mean > 0. { 2f64.sqrt() / mean } else { 1. })
+}
+
+// normalized DLT with h[8] fixed to 1, solved through the normal equations
+fn fit_homography(pairs: &[Pair]) -> Option<Model> {
+    let (fx, fy, fs) = normalization(pairs.iter().map(|p| p.from));
+    let (tx, ty, ts) = normalization(pairs.iter().map(|p| p.to));
+    let mut ata = [[0.; 8]; 8];
+    let mut atb = [0.; 8];
+    for p in pairs {
+        let (x, y) = ((p.from.0 - fx) * fs, (p.from.1 - fy) * fs);
+        let (u, v) = ((p.to.0 - tx) * ts, (p.to.1 - ty) * ts);
+        for (row, rhs) in [
+            ([x, y, 1., 0., 0., 0., -x * u, -y * u], u),
+            ([0., 0., 0., x, y, 1., -x * v, -y * v], v),
+        ] {
+            for i in 0..8 {
+                for j in 0..8 {
+                    ata[i][j] += row[i] * row[j];
+                }
+                atb[i] += row[i] * rhs;
+            }
+        }
+    }
+    let h = solve(ata, atb)?;
+    let hn = [h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], 1.];
+    // undo the normalizations: T_to^-1 * Hn * T_from
+    let t_from = [fs, 0., -fs * fx, 0., fs, -fs * fy, 0., 0., 1.];
+    let t_to_inv = [1. / ts, 0., tx, 0., 1. / ts, ty, 0., 0., 1.];
+    let m = mul3(&mul3(&t_to_inv, &hn), &t_from);
+    if m[8].abs() < 1e-12 {
+        return None;
+    }
+    Some(Model::Homography(m.map(|v| v / m[8])))
+}
+
+fn mul3(a: &[f64; 9], b: &[f64; 9]) -> [f64; 9] {
+    std::array::from_fn(|i| {
+        let (r, c) = (i / 3, i % 3);
+        (0..3).map(|k| a[r * 3 + k] * b[k * 3 + c]).sum()
+    })
+}
+
+/// Gaussian elimination with partial pivoting, `None` for a (nearly) singular system
+pub fn solve<const N: usize>(mut a: [[f64; N]; N], mut b: [f64; N]) -> Option<[f64; N]> {
+    for col in 0..N {
+        let pivot = (col..N).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))?;
+        if a[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        a.swap(col, pivot);
+        b.swap(col, pivot);
+        let pivot_row = a[col];
+        for row in col + 1..N {
+            let f = a[row][col] / pivot_row[col];
+            for (v, p) in a[row][col..].iter_mut().zip(&pivot_row[col..]) {
+                *v -= f * p;
+            }
+            b[row] -= f * b[col];
+        }
+    }
+    let mut x = [0.; N];
+    for row in (0..N).rev() {
+        let sum: f64 = (row + 1..N).map(|k| a[row][k] * x[k]).sum();
+        x[row] = (b[row] - sum) / a[row][row];
+    }
+    Some(x)
+}
+
+pub struct Estimate {
+    pub model: Model,
+    pub inliers: Vec<usize>,
+}
+
+fn inliers(model: &Model, pairs: &[Pair], threshold: f64) -> Vec<usize> {
+    (0..pairs.len())
+        .filter(|&i| model.error(&pairs[i]) <= threshold)
+        .collect()
+}
+
+/// Best model over `iterations` random minimal samples, refitted to its inliers
+pub fn ransac(
+    kind: ModelKind,
+    pairs: &[Pair],
+    threshold: f64,
+    iterations: usize,
+    rng: &mut XorShift,
+) -> Option<Estimate> {
+    let k = kind.sample_size();
+    if pairs.len() < k {
+        return None;
+    }
+    let mut best: Option<Estimate> = None;
+    for _ in 0..iterations {
+        let sample: Vec<Pair> = rng
+            .sample(pairs.len(), k)
+            .iter()
+            .map(|&i| pairs[i])
+            .collect();
+        let Some(model) = kind.fit(&sample) else {
+            continue;
+        };
+        let found = inliers(&model, pairs, threshold);
+        if best.as_ref().is_none_or(|b| found.len() > b.inliers.len()) {
+            best = Some(Estimate {
+                model,
+                inliers: found,
+            });
+        }
+    }
+    let best = best?;
+    let refined: Vec<Pair> = best.inliers.iter().map(|&i| pairs[i]).collect();
+    match kind.fit(&refined) {
+        Some(model) => {
+            let refined_inliers = inliers(&model, pairs, threshold);
+            // keep the refit unless it loses support
+            if refined_inliers.len() >= best.inliers.len() {
+                return Some(Estimate {
+                    model,
+                    inliers: refined_inliers,
+                });
+            }
+            Some(best)
+        }
+        None => Some(best),
+    }
+}
+
+pub fn read_pairs(path: &str, kind: ModelKind) -> Vec<Pair> {
+    let text = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| die!("[ERROR] failed to read points {} ({})", path, e));
+    let columns = kind.columns();
+    let mut pairs = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let values: Vec<f64> = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse().unwrap_or_else(|_| {
+                    die!("[ERROR] points line {}: `{}` is not a number", n + 1, s)
+                })
+            })
+            .collect();
+        if values.len() != columns {
+            die!(
+                "[ERROR] points line {}: expected {} numbers, got {}",
+                n + 1,
+                columns,
+                values.len()
+            );
+        }
+        pairs.push(Pair {
+            from: (values[0], values[1]),
+            to: if columns == 4 {
+                (values[2], values[3])
+            } else {
+                (values[0], values[1])
+            },
+        });
+    }
+    pairs
+}
+
+/// Fits the model to the points file and draws the inliers (green) and outliers (red) on the input
+pub fn run(img: Image, params: &Params) -> Image {
+    let kind = match params.str("model") {
+        "line" => ModelKind::Line,
+        "homography" => ModelKind::Homography,
+        _ => unreachable!(),
+    };
+    let pairs = read_pairs(params.str("points"), kind);
+    let mut rng = XorShift::new(params.int("seed") as u64);
+    let estimate = ransac(
+        kind,
+        &pairs,
+        params.float("threshold"),
+        params.int("iterations") as usize,
+        &mut rng,
+    )
+    .unwrap_or_else(|| {
+        die!(
+            "[ERROR] no model found, need at least {} non-degenerate points, got {}",
+            kind.sample_size(),
+            pairs.len()
+        )
+    });
+
+    let mut text = String::new();
+    let model = match estimate.model {
+        Model::Line([a, b, c]) => {
+            format!("\"model\": \"line\",\n  \"line\": [{}, {}, {}]", a, b, c)
+        }
+        Model::Homography(h) => format!(
+            "\"model\": \"homography\",\n  \"matrix\": [[{}, {}, {}], [{}, {}, {}], [{}, {}, {}]]",
+            h[0], h[1], h[2], h[3], h[4], h[5], h[6], h[7], h[8]
+        ),
+    };
+    writeln!(
+        text,
+        "{{\n  {},\n  \"points\": {},\n  \"inliers\": [{}]\n}}",
+        model,
+        pairs.len(),
+        estimate
+            .inliers
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mut out = to_rgb(img);
+    if let Model::Line([a, b, c]) = estimate.model {
+        let reach = (width + height) as f64;
+        let (px, py) = (-a * c, -b * c);
+        let end = |t: f64| ((px - b * t).round() as isize, (py + a * t).round() as isize);
+        draw::line(
+            &mut out.bytes,
+            width,
+            height,
+            end(-reach),
+            end(reach),
+            &[0, 0, 255],
+        );
+    }
+    for (i, pair) in pairs.iter().enumerate() {
+        let color = if estimate.inliers.contains(&i) {
+            [0, 255, 0]
+        } else {
+            [255, 0, 0]
+        };
+        let point = |(x, y): (f64, f64)| (x.round() as isize, y.round() as isize);
+        if let ModelKind::Homography = kind {
+            draw::line(
+                &mut out.bytes,
+                width,
+                height,
+                point(pair.from),
+                point(pair.to),
+                &color,
+            );
+        }
+        draw::circle(&mut out.bytes, width, height, point(pair.from), 2, &color);
+    }
+    out
+}
@@ -0,0 +1,20 @@
+use crate::params::{Kind, ParamSpec, Params};
+
+/// Destination of textual results, stdout when unset
+pub const PARAM: ParamSpec = ParamSpec {
+    name: "report",
+    kind: Kind::Path,
+    default: None,
+    constraints: &[],
+};
+
+pub fn emit(params: &Params, text: &str) {
+    match params.str_opt(PARAM.name) {
+        Some(path) => {
+            std::fs::write(path, text)
+                .unwrap_or_else(|e| die!("[ERROR] failed to write report ({})", e));
+            println!("[INFO] wrote report {}", path);
+        }
+        None => print!("{}", text),
+    }
+}
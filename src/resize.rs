@@ -0,0 +1,58 @@
+use alloc::vec::Vec;
+
+/// Bilinear interpolation of a single plane at (`x`, `y`) in pixel-center
+/// coordinates, clamping to the edge pixels outside the plane
+pub fn sample(src: &[f64], width: usize, height: usize, x: f64, y: f64) -> f64 {
+    let fx = x.clamp(0., (width - 1) as f64);
+    let fy = y.clamp(0., (height - 1) as f64);
+    let (x0, dx) = (fx as usize, fx - (fx as usize) as f64);
+    let (y0, dy) = (fy as usize, fy - (fy as usize) as f64);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let top = src[y0 * width + x0] * (1. - dx) + src[y0 * width + x1] * dx;
+    let bottom = src[y1 * width + x0] * (1. - dx) + src[y1 * width + x1] * dx;
+    top * (1. - dy) + bottom * dy
+}
+
+/// Resamples a single plane to `new_width` x `new_height` by bilinear interpolation,
+/// aligning pixel centers and clamping at the edges
+pub fn bilinear(
+    src: &[f64],
+    width: usize,
+    height: usize,
+    new_width: usize,
+    new_height: usize,
+) -> Vec<f64> {
+    assert_eq!(src.len(), width * height);
+    let (sx, sy) = (
+        width as f64 / new_width as f64,
+        height as f64 / new_height as f64,
+    );
+    let mut dst = Vec::with_capacity(new_width * new_height);
+    for y in 0..new_height {
+        let fy = (y as f64 + 0.5) * sy - 0.5;
+        for x in 0..new_width {
+            let fx = (x as f64 + 0.5) * sx - 0.5;
+            dst.push(sample(src, width, height, fx, fy));
+        }
+    }
+    dst
+}
+
+/// Inverse warp of a single plane: the output pixel (`x`, `y`) takes the bilinear
+/// sample of `src` at `map(x, y)`, both in pixel-center coordinates
+pub fn warp(
+    src: &[f64],
+    width: usize,
+    height: usize,
+    map: impl Fn(f64, f64) -> (f64, f64),
+) -> Vec<f64> {
+    assert_eq!(src.len(), width * height);
+    let mut dst = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let (sx, sy) = map(x as f64, y as f64);
+            dst.push(sample(src, width, height, sx, sy));
+        }
+    }
+    dst
+}
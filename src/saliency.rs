@@ -0,0 +1,139 @@
+use std::fmt::Write;
+
+use gasyori100knock_rs::resize;
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, report, Image, Info};
+
+pub static CROP_PARAMS: &[ParamSpec] = &[
+    // width / height of the window
+    ParamSpec {
+        name: "aspect",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive],
+    },
+    // relative to the largest window of that aspect which fits in the image
+    ParamSpec {
+        name: "scale",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive, Constraint::Range(0., 1.)],
+    },
+    report::PARAM,
+];
+
+const LEVELS: usize = 6;
+const PAIRS: &[(usize, usize)] = &[(0, 1), (0, 3), (0, 5), (1, 4), (2, 3), (3, 5)];
+
+/// Saliency of q76: differences between levels of a Gaussian pyramid built by
+/// bilinear shrinking and enlarging, summed and scaled to [0, 255]
+pub fn saliency_map(img: &Image) -> Vec<f64> {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let gray = ensure_grayscale(img.clone());
+    let plane: Vec<f64> = gray.bytes.iter().map(|&v| v as f64).collect();
+
+    let pyramid: Vec<Vec<f64>> = (0..LEVELS)
+        .map(|level| {
+            let factor = 1 << level;
+            let (w, h) = ((width / factor).max(1), (height / factor).max(1));
+            let small = resize::bilinear(&plane, width, height, w, h);
+            resize::bilinear(&small, w, h, width, height)
+        })
+        .collect();
+
+    let mut map = vec![0.; plane.len()];
+    for &(a, b) in PAIRS {
+        for (out, (x, y)) in map.iter_mut().zip(pyramid[a].iter().zip(&pyramid[b])) {
+            *out += (x - y).abs();
+        }
+    }
+    let max = map.iter().copied().fold(0., f64::max);
+    if max > 0. {
+        map.iter_mut().for_each(|v| *v = *v / max * 255.);
+    }
+    map
+}
+
+pub fn saliency(img: Image, _: &Params) -> Image {
+    let map = saliency_map(&img);
+    Image {
+        info: Info {
+            color: png::ColorType::Grayscale,
+            ..img.info
+        },
+        bytes: map.iter().map(|v| v.round() as u8).collect(),
+        mask: None,
+    }
+}
+
+/// Crops to the window of the requested aspect ratio holding the most saliency
+pub fn crop(img: Image, params: &Params) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let (aspect, scale) = (params.float("aspect"), params.float("scale"));
+    let (full_w, full_h) = if width as f64 / height as f64 > aspect {
+        ((height as f64 * aspect).round(), height as f64)
+    } else {
+        (width as f64, (width as f64 / aspect).round())
+    };
+    let win_w = ((full_w * scale).round() as usize).clamp(1, width);
+    let win_h = ((full_h * scale).round() as usize).clamp(1, height);
+
+    // summed-area table with a zero row and column in front
+    let map = saliency_map(&img);
+    let stride = width + 1;
+    let mut table = vec![0.; stride * (height + 1)];
+    for y in 0..height {
+        for x in 0..width {
+            table[(y + 1) * stride + x + 1] =
+                map[y * width + x] + table[y * stride + x + 1] + table[(y + 1) * stride + x]
+                    - table[y * stride + x];
+        }
+    }
+    let sum = |x: usize, y: usize| {
+        table[(y + win_h) * stride + x + win_w]
+            - table[y * stride + x + win_w]
+            - table[(y + win_h) * stride + x]
+            + table[y * stride + x]
+    };
+
+    let (mut best, mut best_sum) = ((0, 0), f64::MIN);
+    for y in 0..=height - win_h {
+        for x in 0..=width - win_w {
+            let s = sum(x, y);
+            if s > best_sum {
+                best = (x, y);
+                best_sum = s;
+            }
+        }
+    }
+    let total = table[height * stride + width];
+    let score = if total > 0. { best_sum / total } else { 0. };
+
+    let (x0, y0) = best;
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\"x\": {}, \"y\": {}, \"width\": {}, \"height\": {}, \"score\": {}}}",
+        x0, y0, win_w, win_h, score
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    let channels = img.info.color.samples();
+    let bytes = (y0..y0 + win_h)
+        .flat_map(|y| {
+            let start = (y * width + x0) * channels;
+            img.bytes[start..start + win_w * channels].iter().copied()
+        })
+        .collect();
+    Image {
+        info: Info {
+            width: win_w as u32,
+            height: win_h as u32,
+            ..img.info
+        },
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,76 @@
+use crate::rng::XorShift;
+
+// candidates tried around each active point before it is retired
+const ATTEMPTS: usize = 30;
+
+/// Points in [0, `width`) x [0, `height`) at least `radius` apart, added until no
+/// more fit (Bridson, "Fast Poisson disk sampling in arbitrary dimensions")
+pub fn poisson_disk(width: f64, height: f64, radius: f64, rng: &mut XorShift) -> Vec<(f64, f64)> {
+    // a cell is small enough to hold at most one point
+    let cell = radius / std::f64::consts::SQRT_2;
+    let (cols, rows) = (
+        (width / cell).ceil() as usize,
+        (height / cell).ceil() as usize,
+    );
+    let mut grid: Vec<Option<usize>> = vec![None; cols * rows];
+    let cell_of = |(x, y): (f64, f64)| ((x / cell) as usize, (y / cell) as usize);
+    let mut points = vec![(rng.next_f64() * width, rng.next_f64() * height)];
+    let (c, r) = cell_of(points[0]);
+    grid[r * cols + c] = Some(0);
+    let mut active = vec![0];
+
+    while !active.is_empty() {
+        let slot = rng.below(active.len());
+        let (px, py) = points[active[slot]];
+        let found = (0..ATTEMPTS).find_map(|_| {
+            // uniform over the annulus between radius and twice that
+            let angle = rng.next_f64() * 2. * std::f64::consts::PI;
+            let dist = radius * (1. + 3. * rng.next_f64()).sqrt();
+            let candidate = (px + dist * angle.cos(), py + dist * angle.sin());
+            if !(0. ..width).contains(&candidate.0) || !(0. ..height).contains(&candidate.1) {
+                return None;
+            }
+            let (c, r) = cell_of(candidate);
+            let clear = (r.saturating_sub(2)..(r + 3).min(rows)).all(|gy| {
+                (c.saturating_sub(2)..(c + 3).min(cols)).all(|gx| {
+                    grid[gy * cols + gx].is_none_or(|j| {
+                        let (qx, qy) = points[j];
+                        (qx - candidate.0).hypot(qy - candidate.1) >= radius
+                    })
+                })
+            });
+            clear.then_some((candidate, c, r))
+        });
+        match found {
+            Some((point, c, r)) => {
+                grid[r * cols + c] = Some(points.len());
+                active.push(points.len());
+                points.push(point);
+            }
+            None => {
+                active.swap_remove(slot);
+            }
+        }
+    }
+    points
+}
+
+/// One point placed uniformly at random in every `cell` x `cell` square of the area
+pub fn jittered(width: f64, height: f64, cell: f64, rng: &mut XorShift) -> Vec<(f64, f64)> {
+    let (cols, rows) = (
+        (width / cell).ceil() as usize,
+        (height / cell).ceil() as usize,
+    );
+    let mut points = Vec::with_capacity(cols * rows);
+    for r in 0..rows {
+        for c in 0..cols {
+            let x = (c as f64 + rng.next_f64()) * cell;
+            let y = (r as f64 + rng.next_f64()) * cell;
+            // squares cut by the border may miss
+            if x < width && y < height {
+                points.push((x, y));
+            }
+        }
+    }
+    points
+}
@@ -0,0 +1,359 @@
+use std::fmt::Write;
+
+use crate::bitwise::expect_mask;
+use crate::label::{label, NEIGHBORS_8};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{report, Image, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // spurs shorter than this many pixels are removed, none when 0
+    ParamSpec {
+        name: "prune",
+        kind: Kind::Int,
+        default: Some("0"),
+        constraints: &[Constraint::Range(0., 65535.)],
+    },
+    // annotated: skeleton in white with endpoints red and branch points blue
+    ParamSpec {
+        name: "output",
+        kind: Kind::Choice(&["annotated", "mask"]),
+        default: Some("annotated"),
+        constraints: &[],
+    },
+    report::PARAM,
+];
+
+// P2..P9 of Zhang-Suen: clockwise from north
+const RING: [(isize, isize); 8] = [
+    (0, -1),
+    (1, -1),
+    (1, 0),
+    (1, 1),
+    (0, 1),
+    (-1, 1),
+    (-1, 0),
+    (-1, -1),
+];
+
+fn ring(skel: &[bool], width: usize, height: usize, i: usize) -> [bool; 8] {
+    let (x, y) = ((i % width) as isize, (i / width) as isize);
+    RING.map(|(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        (0..width as isize).contains(&nx)
+            && (0..height as isize).contains(&ny)
+            && skel[ny as usize * width + nx as usize]
+    })
+}
+
+// number of 0 -> 1 transitions around the ring, i.e. the 8-connectivity number
+fn crossings(p: &[bool; 8]) -> usize {
+    (0..8).filter(|&k| !p[k] && p[(k + 1) % 8]).count()
+}
+
+/// Zhang-Suen thinning to a one pixel wide, 8-connected skeleton
+pub fn thin(mask: &[bool], width: usize, height: usize) -> Vec<bool> {
+    let mut skel = mask.to_vec();
+    loop {
+        let mut changed = false;
+        for step in 0..2 {
+            let remove: Vec<usize> = (0..skel.len())
+                .filter(|&i| {
+                    if !skel[i] {
+                        return false;
+                    }
+                    let p = ring(&skel, width, height, i);
+                    let count = p.iter().filter(|&&v| v).count();
+                    let (a, b) = if step == 0 {
+                        (p[0] && p[2] && p[4], p[2] && p[4] && p[6])
+                    } else {
+                        (p[0] && p[2] && p[6], p[0] && p[4] && p[6])
+                    };
+                    (2..=6).contains(&count) && crossings(&p) == 1 && !a && !b
+                })
+                .collect();
+            changed |= !remove.is_empty();
+            for i in remove {
+                skel[i] = false;
+            }
+        }
+        if !changed {
+            return skel;
+        }
+    }
+}
+
+/// q65
+pub fn zhang_suen(img: Image, _: &Params) -> Image {
+    expect_mask(&img, "input");
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mask: Vec<bool> = img.bytes.iter().map(|&v| v != 0).collect();
+    Image {
+        bytes: thin(&mask, width, height)
+            .iter()
+            .map(|&v| if v { 255 } else { 0 })
+            .collect(),
+        info: img.info,
+        mask: None,
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Node {
+    None,
+    End,
+    Branch,
+}
+
+struct Branch {
+    pixels: Vec<usize>,
+    // adjacent endpoints and branch points
+    nodes: Vec<usize>,
+}
+
+struct Analysis {
+    nodes: Vec<Node>,
+    branches: Vec<Branch>,
+}
+
+fn neighborhood(width: usize, height: usize, i: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = ((i % width) as isize, (i / width) as isize);
+    NEIGHBORS_8.iter().filter_map(move |(dx, dy)| {
+        let (nx, ny) = (x + dx, y + dy);
+        ((0..width as isize).contains(&nx) && (0..height as isize).contains(&ny))
+            .then(|| ny as usize * width + nx as usize)
+    })
+}
+
+// splits the skeleton at its endpoints and branch points into branches
+fn analyze(skel: &[bool], width: usize, height: usize) -> Analysis {
+    let nodes: Vec<Node> = (0..skel.len())
+        .map(|i| {
+            if !skel[i] {
+                return Node::None;
+            }
+            match crossings(&ring(skel, width, height, i)) {
+                0 | 1 => Node::End,
+                2 => Node::None,
+                _ => Node::Branch,
+            }
+        })
+        .collect();
+    // the pixels around a branch point belong to its junction, otherwise the
+    // branches leaving it would touch diagonally and merge
+    let mut junction: Vec<Option<usize>> = vec![None; skel.len()];
+    for i in (0..skel.len()).filter(|&i| nodes[i] == Node::Branch) {
+        junction[i] = Some(i);
+        for j in neighborhood(width, height, i) {
+            if skel[j] && nodes[j] == Node::None && junction[j].is_none() {
+                junction[j] = Some(i);
+            }
+        }
+    }
+    // branches with an endpoint are walked from it to the next node, so that an
+    // endpoint touching a junction still makes a branch of its own
+    let mut taken = vec![false; skel.len()];
+    let mut branches = Vec::new();
+    for end in (0..skel.len()).filter(|&i| nodes[i] == Node::End) {
+        if taken[end] {
+            continue;
+        }
+        taken[end] = true;
+        let mut branch = Branch {
+            pixels: Vec::new(),
+            nodes: vec![end],
+        };
+        let mut at = end;
+        loop {
+            let next: Vec<usize> = neighborhood(width, height, at)
+                .filter(|&j| skel[j] && !taken[j])
+                .collect();
+            if let Some(b) = next.iter().find_map(|&j| junction[j]) {
+                branch.nodes.push(b);
+                break;
+            }
+            if let Some(&j) = next.iter().find(|&&j| nodes[j] == Node::End) {
+                taken[j] = true;
+                branch.nodes.push(j);
+                break;
+            }
+            // a straight step before a diagonal one, which would skip the corner
+            let diagonal = |j: &&usize| **j % width != at % width && **j / width != at / width;
+            let Some(&j) = next.iter().min_by_key(diagonal) else {
+                break;
+            };
+            taken[j] = true;
+            branch.pixels.push(j);
+            at = j;
+        }
+        // a lone pixel is no branch
+        if branch.nodes.len() > 1 || !branch.pixels.is_empty() {
+            branches.push(branch);
+        }
+    }
+    // the rest runs between branch points or around a loop
+    let interior: Vec<bool> = (0..skel.len())
+        .map(|i| skel[i] && !taken[i] && nodes[i] == Node::None && junction[i].is_none())
+        .collect();
+    let labels = label(&interior, width, height, NEIGHBORS_8);
+    let mut rest: Vec<Branch> = labels
+        .areas
+        .iter()
+        .map(|_| Branch {
+            pixels: Vec::new(),
+            nodes: Vec::new(),
+        })
+        .collect();
+    for (i, &l) in labels.labels.iter().enumerate() {
+        if l == 0 {
+            continue;
+        }
+        let branch = &mut rest[l as usize - 1];
+        branch.pixels.push(i);
+        for j in neighborhood(width, height, i) {
+            let node = match (nodes[j], junction[j]) {
+                (Node::End, _) => Some(j),
+                (_, Some(b)) => Some(b),
+                _ => None,
+            };
+            if let Some(n) = node.filter(|n| !branch.nodes.contains(n)) {
+                branch.nodes.push(n);
+            }
+        }
+    }
+    branches.extend(rest);
+    Analysis { nodes, branches }
+}
+
+impl Branch {
+    // a branch hanging off the rest of the skeleton by one end
+    fn is_spur(&self, nodes: &[Node]) -> bool {
+        let count = |kind| self.nodes.iter().filter(|&&n| nodes[n] == kind).count();
+        count(Node::End) == 1 && count(Node::Branch) >= 1
+    }
+
+    // from node to node, counting one pixel for each end
+    fn length(&self) -> usize {
+        self.pixels.len() + self.nodes.len()
+    }
+}
+
+/// Thins a mask and reports its endpoints, branch points and branches, optionally pruning spurs
+pub fn run(img: Image, params: &Params) -> Image {
+    expect_mask(&img, "input");
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mask: Vec<bool> = img.bytes.iter().map(|&v| v != 0).collect();
+    let mut skel = thin(&mask, width, height);
+
+    let prune = params.int("prune") as usize;
+    let mut pruned = 0;
+    if prune > 0 {
+        let analysis = analyze(&skel, width, height);
+        for branch in &analysis.branches {
+            if branch.is_spur(&analysis.nodes) && branch.length() < prune {
+                pruned += 1;
+                let ends = branch
+                    .nodes
+                    .iter()
+                    .filter(|&&n| analysis.nodes[n] == Node::End);
+                for &i in branch.pixels.iter().chain(ends) {
+                    skel[i] = false;
+                }
+            }
+        }
+        // removing a spur can leave a corner pixel at its junction
+        skel = thin(&skel, width, height);
+    }
+
+    let Analysis { nodes, branches } = analyze(&skel, width, height);
+    let point = |i: usize| format!("[{}, {}]", i % width, i / width);
+    let list = |kind| {
+        (0..nodes.len())
+            .filter(|&i| nodes[i] == kind)
+            .map(point)
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let entries: Vec<String> = branches
+        .iter()
+        .map(|b| {
+            format!(
+                "    {{\"length\": {}, \"spur\": {}, \"nodes\": [{}]}}",
+                b.length(),
+                b.is_spur(&nodes),
+                b.nodes
+                    .iter()
+                    .map(|&n| point(n))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"pixels\": {},\n  \"pruned\": {},\n  \"endpoints\": [{}],\n  \"branch_points\": [{}],\n  \"branches\": [\n{}\n  ]\n}}",
+        skel.iter().filter(|&&v| v).count(),
+        pruned,
+        list(Node::End),
+        list(Node::Branch),
+        entries.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    match params.str("output") {
+        "mask" => Image {
+            bytes: skel.iter().map(|&v| if v { 255 } else { 0 }).collect(),
+            info: img.info,
+            mask: None,
+        },
+        "annotated" => Image {
+            bytes: (0..skel.len())
+                .flat_map(|i| match (skel[i], nodes[i]) {
+                    (false, _) => [0; 3],
+                    (true, Node::End) => [255, 0, 0],
+                    (true, Node::Branch) => [0, 0, 255],
+                    (true, Node::None) => [255; 3],
+                })
+                .collect(),
+            info: Info {
+                color: png::ColorType::Rgb,
+                ..img.info
+            },
+            mask: None,
+        },
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spur_next_to_junction() {
+        // a T of three arms with a one pixel spur on top of its branch point
+        let (width, height) = (11, 11);
+        let mut skel = vec![false; width * height];
+        for x in 0..width {
+            skel[5 * width + x] = true;
+        }
+        for y in 4..height {
+            skel[y * width + 5] = true;
+        }
+        let analysis = analyze(&skel, width, height);
+        let (junction, spur) = (5 * width + 5, 4 * width + 5);
+        assert!(analysis.nodes[junction] == Node::Branch);
+        assert!(analysis.nodes[spur] == Node::End);
+        assert_eq!(analysis.branches.len(), 4);
+        let branch = analysis
+            .branches
+            .iter()
+            .find(|b| b.nodes.contains(&spur))
+            .unwrap();
+        assert!(branch.pixels.is_empty());
+        assert_eq!(branch.nodes, [spur, junction]);
+        assert!(branch.is_spur(&analysis.nodes));
+        assert_eq!(branch.length(), 2);
+    }
+}
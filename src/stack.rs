@@ -0,0 +1,141 @@
+use std::fmt::Write;
+
+use crate::filter::split_channels;
+use crate::params::{Kind, ParamSpec, Params};
+use crate::phase::phase_correlation;
+use crate::warp::per_channel;
+use crate::{ensure_grayscale, read_input, report, write_output, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // median: drops moving objects and outliers, mean: lowest noise, max: star trails
+    ParamSpec {
+        name: "mode",
+        kind: Kind::Choice(&["median", "mean", "max"]),
+        default: Some("median"),
+        constraints: &[],
+    },
+    // phase: shift every frame onto the first by phase correlation before combining
+    ParamSpec {
+        name: "align",
+        kind: Kind::Choice(&["none", "phase"]),
+        default: Some("none"),
+        constraints: &[],
+    },
+    report::PARAM,
+];
+
+fn read(path: &str) -> Image {
+    let img =
+        read_input(path).unwrap_or_else(|e| die!("[ERROR] failed to read frame {} ({})", path, e));
+    if img.info.depth != png::BitDepth::Eight {
+        die!("[ERROR] the only supported bit depth is 8 ({})", path);
+    }
+    img
+}
+
+fn gray_plane(img: &Image) -> Vec<f64> {
+    split_channels(&ensure_grayscale(img.clone())).swap_remove(0)
+}
+
+/// Combines `frames` sample by sample into one image written to `output`
+pub fn run(output: &str, frames: &[String], params: &Params) {
+    if frames.len() < 2 {
+        die!(
+            "[ERROR] stacking needs at least two frames, got {}",
+            frames.len()
+        );
+    }
+    let first = read(&frames[0]);
+    let mut images = vec![first.clone()];
+    let mut shifts = vec![(0., 0.)];
+    let reference = gray_plane(&first);
+    let (width, height) = (first.info.width as usize, first.info.height as usize);
+    for path in &frames[1..] {
+        let img = read(path);
+        if (img.info.width, img.info.height, img.info.color)
+            != (first.info.width, first.info.height, first.info.color)
+        {
+            die!(
+                "[ERROR] frame {} is {}x{} {:?} but the first is {}x{} {:?}",
+                path,
+                img.info.width,
+                img.info.height,
+                img.info.color,
+                first.info.width,
+                first.info.height,
+                first.info.color
+            );
+        }
+        let img = match params.str("align") {
+            "none" => img,
+            "phase" => {
+                let (dx, dy, _) = phase_correlation(&reference, &gray_plane(&img), width, height);
+                shifts.push((dx, dy));
+                let shift = move |x: f64, y: f64| (x + dx, y + dy);
+                let maps: Vec<&dyn Fn(f64, f64) -> (f64, f64)> =
+                    vec![&shift; img.info.color.samples()];
+                per_channel(&img, &maps)
+            }
+            _ => unreachable!(),
+        };
+        images.push(img);
+    }
+
+    let mode = params.str("mode");
+    let mut column = vec![0u8; images.len()];
+    let bytes: Vec<u8> = (0..first.bytes.len())
+        .map(|i| {
+            for (v, img) in column.iter_mut().zip(&images) {
+                *v = img.bytes[i];
+            }
+            match mode {
+                "median" => {
+                    column.sort_unstable();
+                    let n = column.len();
+                    if n % 2 == 1 {
+                        column[n / 2]
+                    } else {
+                        (column[n / 2 - 1] as u16 + column[n / 2] as u16).div_ceil(2) as u8
+                    }
+                }
+                "mean" => {
+                    let sum: u32 = column.iter().map(|&v| v as u32).sum();
+                    ((sum + column.len() as u32 / 2) / column.len() as u32) as u8
+                }
+                "max" => *column.iter().max().unwrap(),
+                _ => unreachable!(),
+            }
+        })
+        .collect();
+
+    if params.str("align") == "phase" {
+        let entries: Vec<String> = frames
+            .iter()
+            .zip(&shifts)
+            .map(|(path, (dx, dy))| {
+                format!(
+                    "    {{\"file\": \"{}\", \"shift\": [{:.3}, {:.3}]}}",
+                    path.replace('\\', "\\\\").replace('"', "\\\""),
+                    dx,
+                    dy
+                )
+            })
+            .collect();
+        let mut text = String::new();
+        writeln!(
+            text,
+            "{{\n  \"frames\": [\n{}\n  ]\n}}",
+            entries.join(",\n")
+        )
+        .unwrap();
+        report::emit(params, &text);
+    }
+
+    write_output(output, &first.info, &bytes)
+        .unwrap_or_else(|e| die!("[ERROR] failed to write output ({})", e));
+    println!(
+        "[INFO] stacked {} frames into {:?}",
+        images.len(),
+        first.info
+    );
+}
@@ -0,0 +1,49 @@
+use std::fmt::Write;
+
+use crate::params::{ParamSpec, Params};
+use crate::{report, Image};
+
+pub static PARAMS: &[ParamSpec] = &[report::PARAM];
+
+/// Per-channel sample statistics, weighted by the attached mask when there is
+/// one; the input is passed through
+pub fn statistics(img: Image, params: &Params) -> Image {
+    let channels = img.info.color.samples();
+    let pixels = img.bytes.len() / channels;
+    let (bytes, mask) = (&img.bytes, img.mask.as_deref());
+    let weight = move |i: usize| mask.map_or(1., |m| m[i] as f64 / 255.);
+    let total: f64 = (0..pixels).map(weight).sum();
+    if total <= 0. {
+        die!("[ERROR] the mask selects no pixels");
+    }
+    let entries: Vec<String> = (0..channels)
+        .map(|c| {
+            let samples = || {
+                (0..pixels)
+                    .filter(|&i| weight(i) > 0.)
+                    .map(move |i| (bytes[i * channels + c] as f64, weight(i)))
+            };
+            let mean = samples().map(|(v, w)| v * w).sum::<f64>() / total;
+            let variance = samples().map(|(v, w)| w * (v - mean).powi(2)).sum::<f64>() / total;
+            let min = samples().map(|(v, _)| v).fold(f64::MAX, f64::min);
+            let max = samples().map(|(v, _)| v).fold(f64::MIN, f64::max);
+            format!(
+                "    {{\"mean\": {:.3}, \"std\": {:.3}, \"min\": {}, \"max\": {}}}",
+                mean,
+                variance.sqrt(),
+                min,
+                max
+            )
+        })
+        .collect();
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"weight\": {:.1},\n  \"channels\": [\n{}\n  ]\n}}",
+        total,
+        entries.join(",\n")
+    )
+    .unwrap();
+    report::emit(params, &text);
+    img
+}
@@ -0,0 +1,66 @@
+use gasyori100knock_rs::draw;
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::rng::XorShift;
+use crate::sampling;
+use crate::{ensure_grayscale, Image};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // poisson: candidates at least `spacing` apart, jitter: one per `spacing` square
+    ParamSpec {
+        name: "method",
+        kind: Kind::Choice(&["poisson", "jitter"]),
+        default: Some("poisson"),
+        constraints: &[],
+    },
+    // between the candidate dots, which black areas get all of
+    ParamSpec {
+        name: "spacing",
+        kind: Kind::Float,
+        default: Some("3"),
+        constraints: &[Constraint::Range(1., 1024.)],
+    },
+    ParamSpec {
+        name: "dot",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Range(0.5, 64.)],
+    },
+    ParamSpec {
+        name: "seed",
+        kind: Kind::Int,
+        default: Some("1"),
+        constraints: &[],
+    },
+];
+
+/// Black dots on white, each candidate sample kept with a probability equal to
+/// the darkness under it
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mut rng = XorShift::new(params.int("seed") as u64);
+    let spacing = params.float("spacing");
+    let (w, h) = (width as f64, height as f64);
+    let candidates = match params.str("method") {
+        "poisson" => sampling::poisson_disk(w, h, spacing, &mut rng),
+        "jitter" => sampling::jittered(w, h, spacing, &mut rng),
+        _ => unreachable!(),
+    };
+    let dot = params.float("dot");
+    let mut bytes = vec![255u8; width * height];
+    let mut placed = 0;
+    for (x, y) in candidates {
+        let darkness = 1. - img.bytes[y as usize * width + x as usize] as f64 / 255.;
+        if rng.next_f64() < darkness {
+            draw::disc(&mut bytes, width, height, (x, y), dot, &[0]);
+            placed += 1;
+        }
+    }
+    println!("dots: {}", placed);
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,250 @@
+use std::path::Path;
+use std::process::Command;
+
+use gasyori100knock_rs::font;
+
+use crate::batch::{failure, flag_args, param_name, thumbnail};
+use crate::ops::{self, Op};
+use crate::params::{Constraint, Flags, Kind, ParamSpec, Params};
+use crate::{read_input, to_rgb, write_output, Info};
+
+pub static PARAMS: &[ParamSpec] = &[
+    // where to write all outputs side by side, each labeled with its value
+    ParamSpec {
+        name: "montage",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // of the montage, roughly square when unset
+    ParamSpec {
+        name: "columns",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(1., 256.)],
+    },
+    // longer side of the montage tiles
+    ParamSpec {
+        name: "thumbnail",
+        kind: Kind::Int,
+        default: Some("256"),
+        constraints: &[Constraint::Range(16., 1024.)],
+    },
+];
+
+// sweeps longer than this are almost certainly a typo in the step
+const MAX_VALUES: usize = 1000;
+
+// between the tiles and around the montage
+const GAP: usize = 8;
+
+// `start..end` or `start..end:step` of a numeric parameter, `a,b,c` of a choice
+fn is_sweep(op: &Op, arg: &str) -> bool {
+    let Some((name, value)) = arg.split_once('=') else {
+        return false;
+    };
+    match op.params.iter().find(|p| p.name == name).map(|p| &p.kind) {
+        Some(Kind::Int | Kind::Float) => value.contains(".."),
+        Some(Kind::Choice(_)) => value.contains(','),
+        _ => false,
+    }
+}
+
+fn parse_number(name: &str, text: &str) -> f64 {
+    text.parse()
+        .unwrap_or_else(|_| die!("[ERROR] sweep of {}: `{}` is not a number", name, text))
+}
+
+// the values of a sweep, formatted as they are passed to the operation
+fn values(name: &str, spec: &str) -> Vec<String> {
+    let Some((start, rest)) = spec.split_once("..") else {
+        return spec.split(',').map(str::to_owned).collect();
+    };
+    let (end, step) = rest.split_once(':').unwrap_or((rest, "1"));
+    let (start, end, step) = (
+        parse_number(name, start),
+        parse_number(name, end),
+        parse_number(name, step),
+    );
+    if step <= 0. || end < start {
+        die!(
+            "[ERROR] sweep of {}: needs start <= end and a positive step",
+            name
+        );
+    }
+    // the tolerance keeps the end when it is a rounding error away
+    let count = ((end - start) / step + 1e-9).floor() as usize + 1;
+    if count > MAX_VALUES {
+        die!(
+            "[ERROR] sweep of {}: {} values, at most {} are allowed",
+            name,
+            count,
+            MAX_VALUES
+        );
+    }
+    (0..count)
+        .map(|k| {
+            // computed from the start so that errors do not build up
+            let v = start + k as f64 * step;
+            format!("{}", (v * 1e9).round() / 1e9)
+        })
+        .collect()
+}
+
+// the outputs in a grid, each tile centered in its cell above its label
+fn montage(dir: &Path, name: &str, outputs: &[(String, Option<String>)], params: &Params) {
+    let size = params.int("thumbnail") as usize;
+    let tiles: Vec<_> = outputs
+        .iter()
+        .map(|(value, file)| {
+            let tile = file.as_ref().and_then(|file| {
+                let img = read_input(dir.join(file)).ok()?;
+                (img.info.depth == png::BitDepth::Eight
+                    && matches!(
+                        img.info.color,
+                        png::ColorType::Grayscale | png::ColorType::Rgb
+                    ))
+                .then(|| to_rgb(thumbnail(&img, size)))
+            });
+            (value, tile)
+        })
+        .collect();
+    let (cell_w, cell_h) = tiles
+        .iter()
+        .filter_map(|(_, tile)| tile.as_ref())
+        .fold((1, 1), |(w, h), t| {
+            (w.max(t.info.width as usize), h.max(t.info.height as usize))
+        });
+    let scale = (size / 128).max(1);
+    let label_h = (font::HEIGHT + 2) * scale;
+    let columns = params.int_opt("columns").map_or_else(
+        || (tiles.len() as f64).sqrt().ceil() as usize,
+        |c| c as usize,
+    );
+    let rows = tiles.len().div_ceil(columns);
+    let (width, height) = (
+        GAP + columns * (cell_w + GAP),
+        GAP + rows * (cell_h + label_h + GAP),
+    );
+    let mut bytes = vec![255; width * height * 3];
+    for (k, (value, tile)) in tiles.iter().enumerate() {
+        let (left, top) = (
+            GAP + k % columns * (cell_w + GAP),
+            GAP + k / columns * (cell_h + label_h + GAP),
+        );
+        match tile {
+            Some(tile) => {
+                let (tw, th) = (tile.info.width as usize, tile.info.height as usize);
+                let (x0, y0) = (left + (cell_w - tw) / 2, top + (cell_h - th) / 2);
+                for y in 0..th {
+                    let row = &tile.bytes[y * tw * 3..(y + 1) * tw * 3];
+                    let start = ((y0 + y) * width + x0) * 3;
+                    bytes[start..start + tw * 3].copy_from_slice(row);
+                }
+            }
+            // a failed value leaves a gray cell
+            None => {
+                for y in top..top + cell_h {
+                    bytes[(y * width + left) * 3..(y * width + left + cell_w) * 3].fill(192);
+                }
+            }
+        }
+        let x = left + cell_w.saturating_sub(font::text_width(value, scale)) / 2;
+        font::text(
+            &mut bytes,
+            width,
+            height,
+            (x as isize, (top + cell_h + scale) as isize),
+            value,
+            scale,
+            &[0, 0, 0],
+        );
+    }
+    let info = Info {
+        width: width as u32,
+        height: height as u32,
+        color: png::ColorType::Rgb,
+        depth: png::BitDepth::Eight,
+    };
+    write_output(name, &info, &bytes)
+        .unwrap_or_else(|e| die!("[ERROR] failed to write montage ({})", e));
+    println!("[INFO] wrote montage {}", name);
+}
+
+/// Runs `op` on `input` once per value of the one swept parameter, given as
+/// `name=start..end[:step]` or, for a choice, `name=a,b,c`, and writes the
+/// outputs to `dir` as `<op>-<name>-<value>.png`, optionally with a montage of
+/// them all. The other parameters are passed unchanged. Exits with 1 when any
+/// value failed.
+pub fn run(input: &str, dir: &str, name: &str, args: &[String], flags: Flags, mask: Option<&str>) {
+    let op = ops::find(name).unwrap_or_else(|| die!("[ERROR] no function named {}", name));
+    let (own, rest): (Vec<String>, Vec<String>) = args
+        .iter()
+        .cloned()
+        .partition(|arg| PARAMS.iter().any(|p| p.name == param_name(arg)));
+    let params = Params::parse(PARAMS, &own, Flags::default())
+        .unwrap_or_else(|e| die!("[ERROR] invalid parameters for sweep ({})", e));
+    let (swept, fixed): (Vec<String>, Vec<String>) =
+        rest.into_iter().partition(|arg| is_sweep(op, arg));
+    let [swept] = swept.as_slice() else {
+        die!(
+            "[ERROR] sweep needs exactly one parameter given as name=start..end[:step] or name=a,b,c, got {}",
+            swept.len()
+        );
+    };
+    let (param, spec) = swept.split_once('=').unwrap();
+    let values = values(param, spec);
+    for value in &values {
+        let mut op_args = fixed.clone();
+        op_args.push(format!("{}={}", param, value));
+        if let Err(e) = Params::parse(op.params, &op_args, flags) {
+            die!("[ERROR] invalid parameters for {} ({})", op.name, e);
+        }
+    }
+    if mask.is_some() && !ops::MASKED.contains(&op.name) {
+        die!("[ERROR] {} does not support --mask", op.name);
+    }
+
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir)
+        .unwrap_or_else(|e| die!("[ERROR] failed to create {} ({})", dir.display(), e));
+    let exe = std::env::current_exe()
+        .unwrap_or_else(|e| die!("[ERROR] failed to locate the executable ({})", e));
+    let mask_arg = mask.map(|path| format!("--mask={}", path));
+    let mut outputs = Vec::new();
+    for value in values {
+        let file = format!("{}-{}-{}.png", op.name, param, value);
+        let result = Command::new(&exe)
+            .arg(input)
+            .arg(dir.join(&file))
+            .arg(op.name)
+            .args(&fixed)
+            .arg(format!("{}={}", param, value))
+            .args(flag_args(flags))
+            .args(&mask_arg)
+            .output()
+            .unwrap_or_else(|e| die!("[ERROR] failed to run {} ({})", op.name, e));
+        match failure(&result) {
+            Some(e) => {
+                println!("[INFO] {}={} failed ({})", param, value, e);
+                outputs.push((value, None));
+            }
+            None => {
+                println!("[INFO] {}={} wrote {}", param, value, file);
+                outputs.push((value, Some(file)));
+            }
+        }
+    }
+    let failed = outputs.iter().filter(|(_, file)| file.is_none()).count();
+    println!(
+        "[INFO] {} of {} values succeeded",
+        outputs.len() - failed,
+        outputs.len()
+    );
+    if let Some(name) = params.str_opt("montage") {
+        montage(dir, name, &outputs, &params);
+    }
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
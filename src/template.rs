@@ -0,0 +1,97 @@
+use gasyori100knock_rs::draw;
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{read_param_image, to_rgb, Image};
+
+pub static PARAMS: &[ParamSpec] = &[ParamSpec {
+    name: "template",
+    kind: Kind::Path,
+    default: None,
+    constraints: &[Constraint::Required],
+}];
+
+/// A borrowed image with interleaved channels
+#[derive(Clone, Copy)]
+pub struct View<'a> {
+    pub bytes: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+    pub channels: usize,
+}
+
+impl<'a> View<'a> {
+    pub fn new(img: &'a Image) -> Self {
+        Self {
+            bytes: &img.bytes,
+            width: img.info.width as usize,
+            height: img.info.height as usize,
+            channels: img.info.color.samples(),
+        }
+    }
+}
+
+/// Sum of squared differences between `patch` and the window of `src` with its
+/// top left at (`x`, `y`), over the patch pixels set in `mask` or all of them
+pub fn ssd(src: View, patch: View, x: usize, y: usize, mask: Option<&[bool]>) -> u64 {
+    let c = src.channels;
+    let mut sum = 0u64;
+    for py in 0..patch.height {
+        let row = ((y + py) * src.width + x) * c;
+        for px in 0..patch.width {
+            if mask.is_some_and(|m| !m[py * patch.width + px]) {
+                continue;
+            }
+            let (s, p) = (row + px * c, (py * patch.width + px) * c);
+            for k in 0..c {
+                let d = src.bytes[s + k] as i64 - patch.bytes[p + k] as i64;
+                sum += (d * d) as u64;
+            }
+        }
+    }
+    sum
+}
+
+/// `ssd` at every position where `patch` fits inside `src`, row by row
+pub fn ssd_map(src: View, patch: View, mask: Option<&[bool]>) -> Vec<u64> {
+    assert_eq!(src.channels, patch.channels);
+    assert!(patch.width <= src.width && patch.height <= src.height);
+    let (w, h) = (src.width - patch.width + 1, src.height - patch.height + 1);
+    (0..w * h)
+        .map(|i| ssd(src, patch, i % w, i / w, mask))
+        .collect()
+}
+
+/// q54, template matching by SSD, the best match outlined in red
+pub fn matching(img: Image, params: &Params) -> Image {
+    let img = to_rgb(img);
+    let template = to_rgb(read_param_image(params, "template"));
+    if template.info.width > img.info.width || template.info.height > img.info.height {
+        die!(
+            "[ERROR] template is {}x{} but the input only {}x{}",
+            template.info.width,
+            template.info.height,
+            img.info.width,
+            img.info.height
+        );
+    }
+    let map = ssd_map(View::new(&img), View::new(&template), None);
+    let w = (img.info.width - template.info.width + 1) as usize;
+    let best = (0..map.len()).min_by_key(|&i| map[i]).unwrap();
+    let (x, y) = (best % w, best / w);
+    println!("match: ({}, {})", x, y);
+
+    let mut bytes = img.bytes;
+    draw::rectangle(
+        &mut bytes,
+        img.info.width as usize,
+        img.info.height as usize,
+        (x as isize, y as isize),
+        (template.info.width as isize, template.info.height as isize),
+        &[255, 0, 0],
+    );
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
@@ -0,0 +1,147 @@
+use std::f64::consts::PI;
+use std::fmt::Write;
+
+use gasyori100knock_rs::convolve::Operator;
+
+use crate::filter::gaussian_blur;
+use crate::gradient::{self, derivatives, OPERATOR};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{ensure_grayscale, report, write_param_image, Image};
+
+// of the window the gradient products are averaged over
+pub const SIGMA: ParamSpec = ParamSpec {
+    name: "sigma",
+    kind: Kind::Float,
+    default: Some("2"),
+    constraints: &[Constraint::Positive],
+};
+
+pub static PARAMS: &[ParamSpec] = &[
+    OPERATOR,
+    SIGMA,
+    // where to write the dominant gradient orientation, in the gray encoding of
+    // gradient so that orientation-glyphs can read it; skipped when unset
+    ParamSpec {
+        name: "orientation",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    report::PARAM,
+];
+
+/// Gaussian-smoothed products of the derivatives, per pixel
+/// `[[xx, xy], [xy, yy]]`
+pub struct StructureTensor {
+    pub width: usize,
+    pub height: usize,
+    pub xx: Vec<f64>,
+    pub yy: Vec<f64>,
+    pub xy: Vec<f64>,
+}
+
+impl StructureTensor {
+    pub fn new(img: &Image, operator: Operator, sigma: f64) -> Self {
+        let (width, height) = (img.info.width as usize, img.info.height as usize);
+        let (gx, gy) = derivatives(img, operator);
+        let product = |a: &[f64], b: &[f64]| {
+            let p: Vec<f64> = a.iter().zip(b).map(|(a, b)| a * b).collect();
+            gaussian_blur(&p, width, height, sigma)
+        };
+        Self {
+            width,
+            height,
+            xx: product(&gx, &gx),
+            yy: product(&gy, &gy),
+            xy: product(&gx, &gy),
+        }
+    }
+
+    /// From the `operator` and `sigma` parameters
+    pub fn from_params(img: &Image, params: &Params) -> Self {
+        Self::new(img, gradient::operator(params), params.float(SIGMA.name))
+    }
+
+    /// Eigenvalues at `i`, larger first
+    pub fn eigenvalues(&self, i: usize) -> (f64, f64) {
+        let (mean, diff) = (
+            (self.xx[i] + self.yy[i]) / 2.,
+            (self.xx[i] - self.yy[i]).hypot(2. * self.xy[i]) / 2.,
+        );
+        (mean + diff, (mean - diff).max(0.))
+    }
+
+    /// Angle of the major eigenvector at `i`, the dominant gradient direction, in
+    /// (-pi/2, pi/2] measured from +x towards +y
+    pub fn orientation(&self, i: usize) -> f64 {
+        0.5 * (2. * self.xy[i]).atan2(self.xx[i] - self.yy[i])
+    }
+
+    /// `(l1 - l2) / (l1 + l2)` at `i` in [0, 1], zero where the image is flat
+    pub fn coherence(&self, i: usize) -> f64 {
+        let trace = self.xx[i] + self.yy[i];
+        if trace > 0. {
+            ((self.xx[i] - self.yy[i]).hypot(2. * self.xy[i]) / trace).min(1.)
+        } else {
+            0.
+        }
+    }
+
+    /// Harris response `det - k * trace^2` at `i`
+    pub fn harris(&self, i: usize, k: f64) -> f64 {
+        self.xx[i] * self.yy[i] - self.xy[i] * self.xy[i] - k * (self.xx[i] + self.yy[i]).powi(2)
+    }
+}
+
+/// Coherence of the structure tensor as intensity, with the orientation map and
+/// summary statistics on the side
+pub fn run(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let tensor = StructureTensor::from_params(&img, params);
+    let len = tensor.xx.len();
+    let coherence: Vec<f64> = (0..len).map(|i| tensor.coherence(i)).collect();
+
+    if params.str_opt("orientation").is_some() {
+        let orientation = Image {
+            info: img.info.clone(),
+            bytes: (0..len)
+                .map(|i| ((tensor.orientation(i) + PI) / (2. * PI) * 255.).round() as u8)
+                .collect(),
+            mask: None,
+        };
+        write_param_image(params, "orientation", &orientation);
+    }
+
+    // orientations repeat every 180 degrees, so they are averaged as doubled
+    // angles weighted by l1 - l2
+    let (mut c, mut s, mut eigen) = (0., 0., (0., 0.));
+    for i in 0..len {
+        let (l1, l2) = tensor.eigenvalues(i);
+        let phi = 2. * tensor.orientation(i);
+        c += (l1 - l2) * phi.cos();
+        s += (l1 - l2) * phi.sin();
+        eigen.0 += l1;
+        eigen.1 += l2;
+    }
+    let n = len as f64;
+    let dominant = (0.5 * s.atan2(c)).to_degrees();
+    let mean_coherence = coherence.iter().sum::<f64>() / n;
+    let mut text = String::new();
+    writeln!(
+        text,
+        "{{\n  \"mean_coherence\": {:.4},\n  \"dominant_orientation\": {:.2},\n  \"orientation_strength\": {:.4},\n  \"mean_eigenvalues\": [{:.3}, {:.3}]\n}}",
+        mean_coherence,
+        dominant,
+        c.hypot(s) / (eigen.0 + eigen.1).max(f64::MIN_POSITIVE),
+        eigen.0 / n,
+        eigen.1 / n
+    )
+    .unwrap();
+    report::emit(params, &text);
+
+    Image {
+        info: img.info,
+        bytes: coherence.iter().map(|c| (c * 255.).round() as u8).collect(),
+        mask: None,
+    }
+}
@@ -0,0 +1,318 @@
+use crate::label;
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{binarize, diff, ensure_grayscale, read_param_image, Image};
+
+pub static BINARIZE_PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "threshold",
+        kind: Kind::Int,
+        default: Some("128"),
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    // anything but fixed ignores `threshold`
+    ParamSpec {
+        name: "method",
+        kind: Kind::Choice(&["fixed", "otsu", "kapur", "triangle"]),
+        default: Some("fixed"),
+        constraints: &[],
+    },
+];
+
+pub static OTSU_PARAMS: &[ParamSpec] = &[
+    // only nonzero pixels of this grayscale image contribute to the histogram
+    ParamSpec {
+        name: "mask",
+        kind: Kind::Path,
+        default: None,
+        constraints: &[],
+    },
+    // rectangle the histogram is taken from, all four or none
+    ParamSpec {
+        name: "roi_x",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., u32::MAX as f64)],
+    },
+    ParamSpec {
+        name: "roi_y",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Range(0., u32::MAX as f64)],
+    },
+    ParamSpec {
+        name: "roi_width",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    ParamSpec {
+        name: "roi_height",
+        kind: Kind::Int,
+        default: None,
+        constraints: &[Constraint::Positive],
+    },
+    // all: threshold the whole image, region: only the mask/ROI, the rest turns 0
+    ParamSpec {
+        name: "apply",
+        kind: Kind::Choice(&["all", "region"]),
+        default: Some("all"),
+        constraints: &[],
+    },
+];
+
+pub static HYSTERESIS_PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "high",
+        kind: Kind::Int,
+        default: Some("50"),
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    ParamSpec {
+        name: "low",
+        kind: Kind::Int,
+        default: Some("20"),
+        constraints: &[Constraint::Range(0., 255.)],
+    },
+    label::CONNECTIVITY,
+];
+
+/// Keeps pixels >= `high` and those >= `low` connected to them through pixels >= `low`
+pub fn hysteresis(
+    bytes: &[u8],
+    width: usize,
+    height: usize,
+    high: u8,
+    low: u8,
+    neighbors: &[(isize, isize)],
+) -> Vec<u8> {
+    let mut out = vec![0u8; bytes.len()];
+    let mut stack: Vec<usize> = (0..bytes.len()).filter(|&i| bytes[i] >= high).collect();
+    for &i in &stack {
+        out[i] = 255;
+    }
+    while let Some(i) = stack.pop() {
+        let (x, y) = ((i % width) as isize, (i / width) as isize);
+        for (dx, dy) in neighbors {
+            let (nx, ny) = (x + dx, y + dy);
+            if !(0..width as isize).contains(&nx) || !(0..height as isize).contains(&ny) {
+                continue;
+            }
+            let j = ny as usize * width + nx as usize;
+            if out[j] == 0 && bytes[j] >= low {
+                out[j] = 255;
+                stack.push(j);
+            }
+        }
+    }
+    out
+}
+
+pub fn hysteresis_op(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let (high, low) = (params.int("high") as u8, params.int("low") as u8);
+    if low > high {
+        die!(
+            "[ERROR] parameter `low` must not exceed `high` ({}), got {}",
+            high,
+            low
+        );
+    }
+    let neighbors = label::neighbors(params);
+    let bytes = hysteresis(
+        &img.bytes,
+        img.info.width as usize,
+        img.info.height as usize,
+        high,
+        low,
+        neighbors,
+    );
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// Pixels selected by the `mask` image and the `roi_*` rectangle, all of them when neither is given
+pub fn region(img: &Image, params: &Params) -> Option<Vec<bool>> {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let mut selected: Option<Vec<bool>> = None;
+
+    if params.str_opt("mask").is_some() {
+        let mask = ensure_grayscale(read_param_image(params, "mask"));
+        if (mask.info.width, mask.info.height) != (img.info.width, img.info.height) {
+            die!(
+                "[ERROR] mask image is {}x{} but the input is {}x{}",
+                mask.info.width,
+                mask.info.height,
+                img.info.width,
+                img.info.height
+            );
+        }
+        selected = Some(mask.bytes.iter().map(|&v| v != 0).collect());
+    }
+
+    let roi = ["roi_x", "roi_y", "roi_width", "roi_height"].map(|name| params.int_opt(name));
+    match roi {
+        [None, None, None, None] => {}
+        [Some(x), Some(y), Some(w), Some(h)] => {
+            let (x, y, w, h) = (x as usize, y as usize, w as usize, h as usize);
+            if x + w > width || y + h > height {
+                die!(
+                    "[ERROR] ROI {}x{}+{}+{} exceeds the {}x{} image",
+                    w,
+                    h,
+                    x,
+                    y,
+                    width,
+                    height
+                );
+            }
+            let inside = (0..width * height)
+                .map(|i| (x..x + w).contains(&(i % width)) && (y..y + h).contains(&(i / width)));
+            selected = Some(match selected {
+                Some(mask) => mask.iter().zip(inside).map(|(&m, r)| m && r).collect(),
+                None => inside.collect(),
+            });
+        }
+        _ => die!("[ERROR] roi_x, roi_y, roi_width and roi_height must be given together"),
+    }
+    selected
+}
+
+/// Histogram of the selected pixels of a grayscale image
+pub fn histogram(bytes: &[u8], selected: Option<&[bool]>) -> [usize; 256] {
+    let mut bins = [0usize; 256];
+    for (i, &v) in bytes.iter().enumerate() {
+        if selected.is_none_or(|s| s[i]) {
+            bins[v as usize] += 1;
+        }
+    }
+    bins
+}
+
+/// Otsu's method: the split maximizing the between-class variance
+pub fn otsu_threshold(histo: &[usize; 256]) -> Option<u8> {
+    (0..=255)
+        .map(|n| {
+            let sum_l: usize = histo[0..n].iter().sum();
+            let sum_r: usize = histo[n..].iter().sum();
+            let mulsum_l: usize = histo[0..n].iter().zip(0..n).map(|(x, y)| x * y).sum();
+            let mulsum_r: usize = histo[n..255].iter().zip(n..255).map(|(x, y)| x * y).sum();
+            let summul = sum_l * sum_r;
+            if summul != 0 {
+                let dividend = (diff(sum_l * mulsum_r, sum_r * mulsum_l) as f64).powi(2);
+                let res = dividend / summul as f64;
+                Some((n, res))
+            } else {
+                None
+            }
+        })
+        .filter(Option::is_some)
+        .flatten()
+        .max_by(|(_, v1), (_, v2)| v1.partial_cmp(v2).expect("encountered NaN"))
+        .map(|(n, _)| n as u8)
+}
+
+/// Kapur's maximum entropy: the split maximizing the summed entropies of both classes.
+/// Returns the first value of the upper class.
+pub fn kapur_threshold(histo: &[usize; 256]) -> Option<u8> {
+    let total: usize = histo.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let p: Vec<f64> = histo.iter().map(|&c| c as f64 / total as f64).collect();
+    let entropy = |range: &[f64]| {
+        let mass: f64 = range.iter().sum();
+        if mass <= 0. {
+            return None;
+        }
+        Some(
+            -range
+                .iter()
+                .filter(|&&q| q > 0.)
+                .map(|q| q / mass * (q / mass).ln())
+                .sum::<f64>(),
+        )
+    };
+    (1..256)
+        .filter_map(|n| Some((n, entropy(&p[..n])? + entropy(&p[n..])?)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(n, _)| n as u8)
+}
+
+/// Triangle method: the level farthest below the line from the histogram peak to
+/// the far end of its longer tail. Returns the first value of the upper class.
+pub fn triangle_threshold(histo: &[usize; 256]) -> Option<u8> {
+    let first = histo.iter().position(|&c| c > 0)?;
+    let last = histo.iter().rposition(|&c| c > 0)?;
+    let peak = (0..256).max_by_key(|&i| (histo[i], std::cmp::Reverse(i)))?;
+    let end = if peak - first > last - peak {
+        first
+    } else {
+        last
+    };
+    if end == peak {
+        return None;
+    }
+    // distance to the line, up to a constant factor
+    let (px, py, ex, ey) = (
+        peak as f64,
+        histo[peak] as f64,
+        end as f64,
+        histo[end] as f64,
+    );
+    let distance =
+        |i: usize| ((ey - py) * i as f64 - (ex - px) * histo[i] as f64 + ex * py - ey * px).abs();
+    let range = if end > peak {
+        peak..end + 1
+    } else {
+        end..peak + 1
+    };
+    let level = range.max_by(|&a, &b| distance(a).total_cmp(&distance(b)))?;
+    // the tail side is the upper class when it lies above the peak
+    Some(if end > peak {
+        (level + 1).min(255)
+    } else {
+        level
+    } as u8)
+}
+
+/// q3
+pub fn binarize_op(img: Image, params: &Params) -> Image {
+    let img = ensure_grayscale(img);
+    let threshold = match params.str("method") {
+        "fixed" => return binarize(img, params.int("threshold") as u8),
+        "otsu" => otsu_threshold(&histogram(&img.bytes, None)),
+        "kapur" => kapur_threshold(&histogram(&img.bytes, None)),
+        "triangle" => triangle_threshold(&histogram(&img.bytes, None)),
+        _ => unreachable!(),
+    }
+    .unwrap_or_else(|| {
+        die!("[ERROR] failed to find a threshold, the image has fewer than two gray levels")
+    });
+    println!("threshold: {}", threshold);
+    binarize(img, threshold)
+}
+
+/// q4, optionally with the histogram restricted to a mask or ROI
+pub fn otsu(img: Image, params: &Params) -> Image {
+    let gray = ensure_grayscale(img);
+    let selected = region(&gray, params);
+    let histo = histogram(&gray.bytes, selected.as_deref());
+    let best_thres = otsu_threshold(&histo).unwrap_or_else(|| {
+        die!("[ERROR] failed to find a threshold, the region has fewer than two gray levels")
+    });
+
+    println!("threshold: {}", best_thres);
+
+    let mut out = binarize(gray, best_thres);
+    if let (Some(selected), "region") = (&selected, params.str("apply")) {
+        for (v, &s) in out.bytes.iter_mut().zip(selected) {
+            if !s {
+                *v = 0;
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,82 @@
+use gasyori100knock_rs::point;
+
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::Image;
+
+// decode: stored values to linear light, encode: linear light to stored values
+const DIRECTION: ParamSpec = ParamSpec {
+    name: "direction",
+    kind: Kind::Choice(&["encode", "decode"]),
+    default: Some("encode"),
+    constraints: &[],
+};
+
+pub static GAMMA_PARAMS: &[ParamSpec] = &[
+    ParamSpec {
+        name: "gamma",
+        kind: Kind::Float,
+        default: Some("2.2"),
+        constraints: &[Constraint::Positive],
+    },
+    // input scale of q24, `(v / c)^(1 / gamma)`
+    ParamSpec {
+        name: "c",
+        kind: Kind::Float,
+        default: Some("1"),
+        constraints: &[Constraint::Positive],
+    },
+    DIRECTION,
+];
+
+pub static SRGB_PARAMS: &[ParamSpec] = &[ParamSpec {
+    default: Some("decode"),
+    ..DIRECTION
+}];
+
+/// Tabulates `f` over [0, 1] for every 8-bit value
+fn lut(f: impl Fn(f64) -> f64) -> [u8; 256] {
+    std::array::from_fn(|v| (f(v as f64 / 255.) * 255.).round().clamp(0., 255.) as u8)
+}
+
+fn apply(img: Image, lut: &[u8; 256]) -> Image {
+    let mut bytes = img.bytes;
+    point::apply_lut(&mut bytes, lut);
+    Image {
+        info: img.info,
+        bytes,
+        mask: None,
+    }
+}
+
+/// q24 when encoding: `(v / c)^(1 / gamma)`, decoding is its inverse `c * v^gamma`
+pub fn gamma(img: Image, params: &Params) -> Image {
+    let (gamma, c) = (params.float("gamma"), params.float("c"));
+    let table = match params.str(DIRECTION.name) {
+        "encode" => lut(|v| (v / c).powf(1. / gamma)),
+        "decode" => lut(|v| c * v.powf(gamma)),
+        _ => unreachable!(),
+    };
+    apply(img, &table)
+}
+
+/// sRGB EOTF (`decode`) and OETF (`encode`) of IEC 61966-2-1
+pub fn srgb(img: Image, params: &Params) -> Image {
+    let table = match params.str(DIRECTION.name) {
+        "decode" => lut(|v| {
+            if v <= 0.04045 {
+                v / 12.92
+            } else {
+                ((v + 0.055) / 1.055).powf(2.4)
+            }
+        }),
+        "encode" => lut(|l| {
+            if l <= 0.0031308 {
+                12.92 * l
+            } else {
+                1.055 * l.powf(1. / 2.4) - 0.055
+            }
+        }),
+        _ => unreachable!(),
+    };
+    apply(img, &table)
+}
@@ -0,0 +1,66 @@
+use gasyori100knock_rs::resize;
+
+use crate::filter::{merge_channels, split_channels};
+use crate::params::{Constraint, Kind, ParamSpec, Params};
+use crate::{to_rgb, Image};
+
+// relative magnification, 0.002 moves a pixel 500 px from the center by one pixel
+const SCALE: Constraint = Constraint::Range(-0.1, 0.1);
+
+pub static ABERRATION_PARAMS: &[ParamSpec] = &[
+    // of red against green, positive to simulate, its negative to correct
+    ParamSpec {
+        name: "red",
+        kind: Kind::Float,
+        default: Some("0.003"),
+        constraints: &[SCALE],
+    },
+    ParamSpec {
+        name: "blue",
+        kind: Kind::Float,
+        default: Some("-0.003"),
+        constraints: &[SCALE],
+    },
+    // optical center, the image center when not given
+    ParamSpec {
+        name: "center_x",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[],
+    },
+    ParamSpec {
+        name: "center_y",
+        kind: Kind::Float,
+        default: None,
+        constraints: &[],
+    },
+];
+
+/// Warps every channel by its own inverse map, see `resize::warp`
+pub fn per_channel(img: &Image, maps: &[&dyn Fn(f64, f64) -> (f64, f64)]) -> Image {
+    let (width, height) = (img.info.width as usize, img.info.height as usize);
+    let planes = split_channels(img);
+    assert_eq!(planes.len(), maps.len());
+    let warped: Vec<Vec<f64>> = planes
+        .iter()
+        .zip(maps)
+        .map(|(plane, map)| resize::warp(plane, width, height, map))
+        .collect();
+    merge_channels(&warped, img.info.clone())
+}
+
+/// Lateral chromatic aberration: red and blue scaled radially against green
+pub fn chromatic_aberration(img: Image, params: &Params) -> Image {
+    let img = to_rgb(img);
+    let cx = params
+        .float_opt("center_x")
+        .unwrap_or((img.info.width as f64 - 1.) / 2.);
+    let cy = params
+        .float_opt("center_y")
+        .unwrap_or((img.info.height as f64 - 1.) / 2.);
+    // a channel magnified by 1 + k shows at p what lies at c + (p - c) / (1 + k)
+    let radial =
+        |k: f64| move |x: f64, y: f64| (cx + (x - cx) / (1. + k), cy + (y - cy) / (1. + k));
+    let (red, blue) = (radial(params.float("red")), radial(params.float("blue")));
+    per_channel(&img, &[&red, &|x, y| (x, y), &blue])
+}